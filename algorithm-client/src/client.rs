@@ -0,0 +1,527 @@
+use std::time::Duration;
+
+use rand::Rng;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+
+use crate::{
+    error::{Error, Result},
+    types::*,
+};
+
+/// 失败重试策略，退避时间按指数增长并加入随机抖动
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        backoff.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+pub struct ClientBuilder {
+    base_url: String,
+    headers: HeaderMap,
+    timeout: Option<Duration>,
+    retry: RetryPolicy,
+    error: Option<Error>,
+}
+
+impl ClientBuilder {
+    /// 每个请求都会携带 `Authorization: Bearer <token>`
+    pub fn bearer_auth(self, token: &str) -> Self {
+        match HeaderValue::from_str(&format!("Bearer {}", token)) {
+            Ok(mut value) => {
+                value.set_sensitive(true);
+                self.header(AUTHORIZATION, value)
+            }
+            Err(_) => self.fail(Error::InvalidHeader(AUTHORIZATION.to_string())),
+        }
+    }
+
+    /// 每个请求都会携带的额外请求头
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn build(self) -> Result<Client> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        let base_url = reqwest::Url::parse(&self.base_url)
+            .map_err(|e| Error::BaseUrl(format!("{}: {}", self.base_url, e)))?;
+
+        let mut builder = reqwest::Client::builder().default_headers(self.headers);
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        Ok(Client {
+            http: builder.build()?,
+            base_url: base_url.as_str().trim_end_matches('/').to_string(),
+            retry: self.retry,
+        })
+    }
+
+    fn fail(mut self, e: Error) -> Self {
+        self.error.get_or_insert(e);
+        self
+    }
+}
+
+/// 训练集与测试集共用同一组接口
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DatasetKind {
+    Trainset,
+    Testset,
+}
+
+impl DatasetKind {
+    fn path(self) -> &'static str {
+        match self {
+            DatasetKind::Trainset => "trainsets",
+            DatasetKind::Testset => "testsets",
+        }
+    }
+}
+
+/// 算法服务的异步客户端
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    retry: RetryPolicy,
+}
+
+impl Client {
+    pub fn builder<U: Into<String>>(base_url: U) -> ClientBuilder {
+        ClientBuilder {
+            base_url: base_url.into(),
+            headers: HeaderMap::new(),
+            timeout: None,
+            retry: RetryPolicy::default(),
+            error: None,
+        }
+    }
+
+    /// 创建算法，返回新算法的 ID。创建不是幂等操作，只在连接失败时重试
+    pub async fn create_algorithm(&self, req: &CreateAlgorithmRequest) -> Result<String> {
+        let url = format!("{}/algorithms", self.base_url);
+        self.execute(false, || self.http.post(&url).json(req)).await
+    }
+
+    pub async fn get_algorithm(&self, id: &str) -> Result<AlgorithmInfo> {
+        let url = format!("{}/algorithms/{}", self.base_url, id);
+        self.execute(true, || self.http.get(&url)).await
+    }
+
+    /// 修改算法的镜像，名称不能修改，位置由上传算法包设置
+    pub async fn update_algorithm(
+        &self,
+        id: &str,
+        req: &UpdateAlgorithmRequest,
+    ) -> Result<AlgorithmInfo> {
+        let url = format!("{}/algorithms/{}", self.base_url, id);
+        self.execute(true, || self.http.put(&url).json(req)).await
+    }
+
+    /// 按标签选择器列出算法，例如 `task=cv,framework!=tf`
+    pub async fn list_algorithms(&self, selector: Option<&str>) -> Result<Vec<AlgorithmInfo>> {
+        let url = format!("{}/algorithms", self.base_url);
+        let query = ListAlgorithmsQuery {
+            labels: selector.map(str::to_string),
+        };
+        self.execute(true, || self.http.get(&url).query(&query))
+            .await
+    }
+
+    pub async fn add_labels(&self, id: &str, labels: Labels) -> Result<Labels> {
+        let url = format!("{}/algorithms/{}/labels", self.base_url, id);
+        let req = AddLabelsRequest { labels };
+        self.execute(true, || self.http.post(&url).json(&req)).await
+    }
+
+    pub async fn remove_labels(&self, id: &str, keys: Vec<String>) -> Result<Labels> {
+        let url = format!("{}/algorithms/{}/labels", self.base_url, id);
+        let req = RemoveLabelsRequest { keys };
+        self.execute(true, || self.http.delete(&url).json(&req))
+            .await
+    }
+
+    /// 上传算法包，覆盖已有的算法包，返回包含校验和与大小的算法信息
+    pub async fn upload_package(&self, id: &str, package: Vec<u8>) -> Result<AlgorithmInfo> {
+        let url = format!("{}/algorithms/{}/package", self.base_url, id);
+        self.execute(true, || self.http.put(&url).body(package.clone()))
+            .await
+    }
+
+    /// 下载算法包。内容与记录的 SHA-256 不一致时服务端会中断连接，返回 `Error::Http`
+    pub async fn download_package(&self, id: &str) -> Result<Vec<u8>> {
+        let url = format!("{}/algorithms/{}/package", self.base_url, id);
+        self.retry(true, || async {
+            let response = self.http.get(&url).send().await?;
+            // 算法包不存在等错误以 JSON 响应体返回，状态码可能是 200
+            let is_json = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.starts_with("application/json"));
+            if !response.status().is_success() || is_json {
+                return Err(error(response).await);
+            }
+            Ok(response.bytes().await?.to_vec())
+        })
+        .await
+    }
+
+    /// 创建数据集，返回新数据集的 ID。与创建算法一样只在连接失败时重试
+    pub async fn create_dataset(
+        &self,
+        kind: DatasetKind,
+        req: &CreateDatasetRequest,
+    ) -> Result<String> {
+        let url = format!("{}/{}", self.base_url, kind.path());
+        self.execute(false, || self.http.post(&url).json(req)).await
+    }
+
+    pub async fn get_dataset(&self, kind: DatasetKind, id: &str) -> Result<DatasetInfo> {
+        let url = format!("{}/{}/{}", self.base_url, kind.path(), id);
+        self.execute(true, || self.http.get(&url)).await
+    }
+
+    pub async fn update_dataset(
+        &self,
+        kind: DatasetKind,
+        id: &str,
+        req: &UpdateDatasetRequest,
+    ) -> Result<DatasetInfo> {
+        let url = format!("{}/{}/{}", self.base_url, kind.path(), id);
+        self.execute(true, || self.http.put(&url).json(req)).await
+    }
+
+    pub async fn list_datasets(&self, kind: DatasetKind) -> Result<Vec<DatasetInfo>> {
+        let url = format!("{}/{}", self.base_url, kind.path());
+        self.execute(true, || self.http.get(&url)).await
+    }
+
+    /// 上传清单，数据集进入等待校验状态，由服务端在后台完成校验
+    pub async fn put_manifest(
+        &self,
+        kind: DatasetKind,
+        id: &str,
+        manifest: &Manifest,
+    ) -> Result<DatasetInfo> {
+        let url = format!("{}/{}/{}/manifest", self.base_url, kind.path(), id);
+        self.execute(true, || self.http.put(&url).json(manifest))
+            .await
+    }
+
+    async fn execute<T, F>(&self, idempotent: bool, request: F) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+        F: Fn() -> reqwest::RequestBuilder,
+    {
+        self.retry(idempotent, || send(request())).await
+    }
+
+    async fn retry<T, F, Fut>(&self, idempotent: bool, attempt_once: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match attempt_once().await {
+                Err(e)
+                    if attempt < self.retry.max_retries
+                        && (e.is_connect() || idempotent && e.is_transient()) =>
+                {
+                    tokio::time::sleep(self.retry.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+async fn send<T>(request: reqwest::RequestBuilder) -> Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        return Err(error(response).await);
+    }
+
+    let body: Response<T> = response.json().await?;
+    if body.code != code::SUCCESS {
+        return Err(Error::from_code(body.code, body.message));
+    }
+    body.data.ok_or(Error::EmptyData)
+}
+
+/// 非 2xx 响应的错误。服务端在 503、504 等响应中同样返回带 `code` 的响应体，
+/// 能解析时按 `code` 映射，否则只保留状态码
+async fn error(response: reqwest::Response) -> Error {
+    let status = response.status();
+    match response.json::<Response<serde::de::IgnoredAny>>().await {
+        Ok(body) if body.code != code::SUCCESS => Error::from_code(body.code, body.message),
+        _ => Error::Status(status),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        net::SocketAddr,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Request, StatusCode,
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    /// 启动一个本地 HTTP 服务，`handler` 的参数为请求序号（从 0 开始）与请求本身
+    async fn serve<F>(handler: F) -> SocketAddr
+    where
+        F: Fn(usize, Request<Body>) -> hyper::Response<Body> + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+        let counter = Arc::new(AtomicUsize::new(0));
+        let make_service = make_service_fn(move |_| {
+            let handler = handler.clone();
+            let counter = counter.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let n = counter.fetch_add(1, Ordering::SeqCst);
+                    let response = handler(n, req);
+                    async move { Ok::<_, Infallible>(response) }
+                }))
+            }
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        addr
+    }
+
+    fn json_response(value: serde_json::Value) -> hyper::Response<Body> {
+        hyper::Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(value.to_string()))
+            .unwrap()
+    }
+
+    fn client(addr: SocketAddr) -> ClientBuilder {
+        Client::builder(format!("http://{}/", addr)).retry(RetryPolicy {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+        })
+    }
+
+    #[tokio::test]
+    async fn create_with_auth_header() {
+        let addr = serve(|_, req| {
+            assert_eq!(req.uri().path(), "/algorithms");
+            assert_eq!(req.headers()[AUTHORIZATION], "Bearer secret");
+            json_response(json!({ "code": "000000", "data": "42" }))
+        })
+        .await;
+
+        let client = client(addr).bearer_auth("secret").build().unwrap();
+        let id = client
+            .create_algorithm(&CreateAlgorithmRequest {
+                name: "alg".to_string(),
+                location: "/a/b".to_string(),
+                image: 1,
+            })
+            .await
+            .unwrap();
+        assert_eq!(id, "42");
+    }
+
+    #[tokio::test]
+    async fn map_error_code() {
+        let addr = serve(|_, req| match req.uri().path() {
+            "/algorithms/1" => {
+                json_response(json!({ "code": "000005", "message": "algorithm not found" }))
+            }
+            _ => json_response(json!({ "code": "000001", "message": "duplicate name" })),
+        })
+        .await;
+
+        let client = client(addr).build().unwrap();
+        assert!(matches!(
+            client.get_algorithm("1").await,
+            Err(Error::NotFound(message)) if message == "algorithm not found"
+        ));
+        assert!(matches!(
+            client
+                .create_algorithm(&CreateAlgorithmRequest {
+                    name: "alg".to_string(),
+                    location: "/a/b".to_string(),
+                    image: 1,
+                })
+                .await,
+            Err(Error::DuplicateName)
+        ));
+    }
+
+    #[tokio::test]
+    async fn retry_transient_errors() {
+        let addr = serve(|n, req| {
+            assert_eq!(req.uri().query(), Some("labels=task%3Dcv"));
+            if n < 2 {
+                let mut response = hyper::Response::new(Body::empty());
+                *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                response
+            } else {
+                json_response(json!({ "code": "000000", "data": [] }))
+            }
+        })
+        .await;
+
+        let client = client(addr).build().unwrap();
+        let algorithms = client.list_algorithms(Some("task=cv")).await.unwrap();
+        assert!(algorithms.is_empty());
+    }
+
+    #[tokio::test]
+    async fn do_not_retry_create_on_server_error() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let requests = counter.clone();
+        let addr = serve(move |_, _| {
+            requests.fetch_add(1, Ordering::SeqCst);
+            let mut response = hyper::Response::new(Body::empty());
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            response
+        })
+        .await;
+
+        let client = client(addr).build().unwrap();
+        let result = client
+            .create_algorithm(&CreateAlgorithmRequest {
+                name: "alg".to_string(),
+                location: "/a/b".to_string(),
+                image: 1,
+            })
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::Status(StatusCode::INTERNAL_SERVER_ERROR))
+        ));
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn map_error_envelope_on_unavailable() {
+        let addr = serve(|n, _| {
+            let mut response =
+                json_response(json!({ "code": "000007", "message": "service unavailable" }));
+            *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            if n == 0 {
+                response
+            } else {
+                json_response(json!({ "code": "000005", "message": "algorithm not found" }))
+            }
+        })
+        .await;
+
+        let client = client(addr).retry(RetryPolicy::none()).build().unwrap();
+        assert!(matches!(
+            client.get_algorithm("1").await,
+            Err(Error::Unavailable(message)) if message == "service unavailable"
+        ));
+
+        // 服务不可用可以重试
+        let client = self::client(addr).build().unwrap();
+        assert!(matches!(
+            client.get_algorithm("1").await,
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn packages_and_datasets() {
+        let addr = serve(|_, req| match (req.method().as_str(), req.uri().path()) {
+            ("GET", "/algorithms/1/package") => hyper::Response::new(Body::from("hello")),
+            ("GET", "/algorithms/2/package") => {
+                json_response(json!({ "code": "000005", "message": "package not found" }))
+            }
+            ("PUT", "/testsets/3/manifest") => json_response(json!({
+                "code": "000000",
+                "data": {
+                    "id": "3",
+                    "name": "testset",
+                    "location": "file:///tmp/testsets",
+                    "verification": { "status": "pending" },
+                },
+            })),
+            ("GET", "/trainsets") => json_response(json!({ "code": "000000", "data": [] })),
+            (method, path) => panic!("unexpected request: {} {}", method, path),
+        })
+        .await;
+
+        let client = client(addr).build().unwrap();
+        assert_eq!(client.download_package("1").await.unwrap(), b"hello");
+        assert!(matches!(
+            client.download_package("2").await,
+            Err(Error::NotFound(_))
+        ));
+        let info = client
+            .put_manifest(DatasetKind::Testset, "3", &Manifest::default())
+            .await
+            .unwrap();
+        assert_eq!(info.verification.status, VerificationStatus::Pending);
+        assert!(client
+            .list_datasets(DatasetKind::Trainset)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
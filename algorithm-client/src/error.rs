@@ -0,0 +1,60 @@
+use crate::types::code;
+
+/// 客户端错误，服务端返回的 `code` 会被映射为对应的变体
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("duplicate name")]
+    DuplicateName,
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("not found: {0}")]
+    NotFound(String),
+    /// 数据库不可用、熔断器打开、服务过载或请求超时
+    #[error("service unavailable: {0}")]
+    Unavailable(String),
+    #[error("server error. code: {code}, message: {message}")]
+    Server { code: String, message: String },
+    #[error("unexpected status: {0}")]
+    Status(reqwest::StatusCode),
+    #[error("response has no data")]
+    EmptyData,
+    #[error("invalid base url: {0}")]
+    BaseUrl(String),
+    #[error("invalid header: {0}")]
+    InvalidHeader(String),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+}
+
+impl Error {
+    pub(crate) fn from_code(code: String, message: Option<String>) -> Self {
+        let message = message.unwrap_or_default();
+        match code.as_str() {
+            code::DUPLICATE_NAME => Error::DuplicateName,
+            code::INVALID_ARGUMENT => Error::InvalidArgument(message),
+            code::NOT_FOUND => Error::NotFound(message),
+            code::UNAVAILABLE => Error::Unavailable(message),
+            // 服务端内部错误，调用方无法据此处理，保留原始错误码
+            code::DATABASE | code::UNKNOWN | code::STORAGE => Error::Server { code, message },
+            _ => Error::Server { code, message },
+        }
+    }
+
+    pub(crate) fn is_connect(&self) -> bool {
+        matches!(self, Error::Http(e) if e.is_connect())
+    }
+
+    /// 连接失败、超时、服务不可用、5xx 与 429 被视为暂时性错误
+    pub(crate) fn is_transient(&self) -> bool {
+        match self {
+            Error::Http(e) => e.is_connect() || e.is_timeout(),
+            Error::Unavailable(_) => true,
+            Error::Status(status) => {
+                status.is_server_error() || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => false,
+        }
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,24 @@
+//! 算法服务的请求、响应类型，以及基于 `reqwest` 的异步客户端
+//!
+//! ```no_run
+//! # async fn run() -> algorithm_client::Result<()> {
+//! let client = algorithm_client::Client::builder("http://127.0.0.1:3000")
+//!     .bearer_auth("token")
+//!     .build()?;
+//! let algorithms = client.list_algorithms(Some("task=cv")).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod types;
+
+#[cfg(feature = "client")]
+mod client;
+#[cfg(feature = "client")]
+mod error;
+
+#[cfg(feature = "client")]
+pub use client::*;
+#[cfg(feature = "client")]
+pub use error::*;
+pub use types::*;
@@ -0,0 +1,218 @@
+//! 服务端与客户端共用的请求、响应类型
+
+use std::collections::BTreeMap;
+
+pub type Labels = BTreeMap<String, String>;
+
+/// 响应中 `code` 字段的取值
+///
+/// 不兼容的变更：服务端改为统一的 `Error` 之后，`000001` 只表示名称重复，创建算法时的非数据库错误
+/// 由 `000001` 改为 `000003`；没有 SQLSTATE 的数据库错误由 `000003` 改为 `000002`。
+/// 依赖旧取值区分这些错误的调用方需要同步修改，客户端将二者都映射为 `Error::Server`
+pub mod code {
+    pub const SUCCESS: &str = "000000";
+    pub const DUPLICATE_NAME: &str = "000001";
+    pub const DATABASE: &str = "000002";
+    pub const UNKNOWN: &str = "000003";
+    pub const INVALID_ARGUMENT: &str = "000004";
+    pub const NOT_FOUND: &str = "000005";
+    pub const STORAGE: &str = "000006";
+    pub const UNAVAILABLE: &str = "000007";
+    pub const PAYLOAD_TOO_LARGE: &str = "000008";
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct Response<T> {
+    pub code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<T>,
+}
+
+impl<T> Response<T> {
+    pub fn ok(data: T) -> Self {
+        Self {
+            code: code::SUCCESS.to_string(),
+            message: None,
+            data: Some(data),
+        }
+    }
+
+    pub fn error(code: &str, message: impl Into<String>) -> Self {
+        Self {
+            code: code.to_string(),
+            message: Some(message.into()),
+            data: None,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct CreateAlgorithmRequest {
+    pub name: String,
+    pub location: String,
+    pub image: u64,
+}
+
+pub type CreateAlgorithmResponse = String;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct AlgorithmInfo {
+    pub id: String,
+    pub name: String,
+    pub display_name: String,
+    pub location: String,
+    pub image: u64,
+    pub labels: Labels,
+    /// 算法包的 SHA-256，未上传算法包时为空
+    #[serde(default)]
+    pub checksum: Option<String>,
+    /// 算法包的字节数，未上传算法包时为空
+    #[serde(default)]
+    pub size: Option<u64>,
+}
+
+pub type GetAlgorithmResponse = AlgorithmInfo;
+
+/// 名称创建后不能修改
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+/// `location` 由上传算法包自动设置，不能直接修改
+pub struct UpdateAlgorithmRequest {
+    pub image: u64,
+}
+
+pub type UpdateAlgorithmResponse = AlgorithmInfo;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct ListAlgorithmsQuery {
+    /// 标签选择器，例如 `task=cv,framework!=tf`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<String>,
+}
+
+pub type ListAlgorithmsResponse = Vec<AlgorithmInfo>;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct AddLabelsRequest {
+    pub labels: Labels,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct RemoveLabelsRequest {
+    pub keys: Vec<String>,
+}
+
+/// 添加或删除标签后，返回算法当前的全部标签
+pub type LabelsResponse = Labels;
+
+/// 数据集清单，列出数据集中的每个文件
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct Manifest {
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ManifestFile {
+    /// 相对于数据集 `location` 的路径
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+    #[serde(default)]
+    pub records: u64,
+    /// 文件所属的划分，例如 `train`、`validation`
+    #[serde(default)]
+    pub split: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationStatus {
+    /// 尚未上传清单
+    Unverified,
+    /// 清单已上传，等待后台校验
+    Pending,
+    Verifying,
+    Verified,
+    /// 存在缺失或损坏的文件
+    Failed,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileProblem {
+    pub path: String,
+    pub problem: Problem,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum Problem {
+    Missing,
+    SizeMismatch { expected: u64, actual: u64 },
+    ChecksumMismatch { expected: String, actual: String },
+    Unreadable { message: String },
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Verification {
+    pub status: VerificationStatus,
+    #[serde(default)]
+    pub verified_at: Option<String>,
+    #[serde(default)]
+    pub problems: Vec<FileProblem>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct CreateDatasetRequest {
+    pub name: String,
+    pub location: String,
+}
+
+pub type CreateDatasetResponse = String;
+
+pub type UpdateDatasetRequest = CreateDatasetRequest;
+
+/// 清单的汇总信息，按划分统计文件数、字节数与记录数
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct ManifestSummary {
+    pub files: u64,
+    pub size: u64,
+    pub records: u64,
+    pub splits: BTreeMap<String, u64>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct DatasetInfo {
+    pub id: String,
+    pub name: String,
+    pub location: String,
+    #[serde(default)]
+    pub manifest: Option<ManifestSummary>,
+    pub verification: Verification,
+}
+
+pub type GetDatasetResponse = DatasetInfo;
+
+pub type ListDatasetsResponse = Vec<DatasetInfo>;
+
+/// 领域事件的 `event_type`
+pub mod event {
+    pub const ALGORITHM_CREATED: &str = "algorithm.created";
+    pub const ALGORITHM_UPDATED: &str = "algorithm.updated";
+}
+
+/// 通过 outbox 投递的领域事件
+///
+/// 事件至少投递一次，消费者应按 `id` 去重；同一个聚合的事件按 `id` 递增的顺序投递。
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Event<T> {
+    pub id: String,
+    pub aggregate_type: String,
+    pub aggregate_id: String,
+    pub event_type: String,
+    pub occurred_at: String,
+    pub payload: T,
+}
+
+/// `algorithm.created` 与 `algorithm.updated` 事件，内容为变更后的算法
+pub type AlgorithmEvent = Event<AlgorithmInfo>;
@@ -0,0 +1,23 @@
+# 000001
+duplicate-name = duplicate name
+# 000002
+database = database error
+# 000003
+unknown = unknown error
+# 000004
+invalid-argument = invalid argument: { $detail }
+# 000005
+not-found = { $resource ->
+    [algorithm] algorithm
+    [package] package
+    [image] image
+    [trainset] trainset
+    [testset] testset
+   *[other] { $resource }
+} not found
+# 000006
+storage = storage error
+# 000007
+unavailable = service temporarily unavailable, please retry later
+# 000008
+payload-too-large = payload larger than { $limit } bytes
@@ -0,0 +1,25 @@
+# 错误码对应的提示，消息 ID 与 `Error` 的变体一一对应
+
+# 000001
+duplicate-name = 名称已存在
+# 000002
+database = 数据库错误
+# 000003
+unknown = 未知错误
+# 000004
+invalid-argument = 参数错误：{ $detail }
+# 000005
+not-found = { $resource ->
+    [algorithm] 算法
+    [package] 算法包
+    [image] 镜像
+    [trainset] 训练集
+    [testset] 测试集
+   *[other] { $resource }
+}不存在
+# 000006
+storage = 存储错误
+# 000007
+unavailable = 服务暂时不可用，请稍后重试
+# 000008
+payload-too-large = 内容超过 { $limit } 字节的上限
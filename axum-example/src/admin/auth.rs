@@ -0,0 +1,86 @@
+//! HTTP Basic 认证：管理后台可以直接修改数据，任何能访问端口的人都不能随意提交表单
+//!
+//! 账号与密码通过 `ADMIN_USER`（默认 `admin`）与 `ADMIN_PASSWORD` 配置，未设置密码时管理后台不可用。
+
+use axum::{
+    extract::{Extension, FromRequest, RequestParts},
+    http::header,
+};
+
+use super::Failure;
+
+#[derive(Clone, Debug)]
+pub struct Credentials {
+    user: String,
+    password: String,
+}
+
+impl Credentials {
+    pub fn new<U: Into<String>, P: Into<String>>(user: U, password: P) -> Self {
+        Self {
+            user: user.into(),
+            password: password.into(),
+        }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        let password = std::env::var("ADMIN_PASSWORD")
+            .ok()
+            .filter(|password| !password.is_empty())?;
+        let user = std::env::var("ADMIN_USER").unwrap_or_else(|_| "admin".into());
+        Some(Self::new(user, password))
+    }
+
+    /// 校验 `Authorization: Basic <base64(user:password)>`
+    fn verify(&self, authorization: &str) -> bool {
+        let decoded = match authorization
+            .strip_prefix("Basic ")
+            .and_then(|encoded| base64::decode(encoded.trim()).ok())
+        {
+            Some(decoded) => decoded,
+            None => return false,
+        };
+        let expected = format!("{}:{}", self.user, self.password);
+        super::csrf::constant_time_eq(&decoded, expected.as_bytes())
+    }
+}
+
+/// 通过认证的请求，作为管理后台的中间件使用
+pub struct Authenticated;
+
+#[async_trait::async_trait]
+impl<B: Send> FromRequest<B> for Authenticated {
+    type Rejection = Failure;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let Extension(credentials) = Extension::<Option<Credentials>>::from_request(req)
+            .await
+            .map_err(|_| Failure::Disabled)?;
+        let credentials = credentials.ok_or(Failure::Disabled)?;
+        let authorized = req
+            .headers()
+            .and_then(|headers| headers.get(header::AUTHORIZATION))
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|authorization| credentials.verify(authorization));
+        if !authorized {
+            return Err(Failure::Unauthorized);
+        }
+        Ok(Authenticated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify() {
+        let credentials = Credentials::new("admin", "secret");
+        // admin:secret
+        assert!(credentials.verify("Basic YWRtaW46c2VjcmV0"));
+        // admin:wrong
+        assert!(!credentials.verify("Basic YWRtaW46d3Jvbmc="));
+        assert!(!credentials.verify("Bearer YWRtaW46c2VjcmV0"));
+        assert!(!credentials.verify("Basic not-base64"));
+    }
+}
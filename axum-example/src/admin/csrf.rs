@@ -0,0 +1,115 @@
+//! 双重提交 cookie：令牌同时保存在 cookie 与表单中，提交时两者必须一致
+//!
+//! 其他站点无法读取 `SameSite=Strict` 的 cookie，也就无法构造出带有正确令牌的表单。
+
+use std::convert::Infallible;
+
+use axum::{
+    extract::{FromRequest, RequestParts},
+    http::{header, HeaderValue},
+};
+use rand::RngCore;
+
+use super::Failure;
+
+const COOKIE: &str = "admin_csrf";
+
+pub struct Csrf {
+    token: String,
+    /// 请求中没有令牌，需要在响应中设置 cookie
+    fresh: bool,
+}
+
+impl Csrf {
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// 比较表单中的令牌与 cookie 中的令牌，cookie 中没有令牌时总是失败
+    pub fn verify(&self, submitted: &str) -> Result<(), Failure> {
+        if self.fresh || !constant_time_eq(self.token.as_bytes(), submitted.as_bytes()) {
+            return Err(Failure::Csrf);
+        }
+        Ok(())
+    }
+
+    pub fn set_cookie(&self) -> Option<HeaderValue> {
+        if !self.fresh {
+            return None;
+        }
+        let cookie = format!(
+            "{}={}; Path=/admin; HttpOnly; SameSite=Strict",
+            COOKIE, self.token
+        );
+        Some(HeaderValue::from_str(&cookie).unwrap())
+    }
+}
+
+#[async_trait::async_trait]
+impl<B: Send> FromRequest<B> for Csrf {
+    type Rejection = Infallible;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let token = req.headers().and_then(|headers| {
+            headers
+                .get_all(header::COOKIE)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .find_map(cookie)
+        });
+        Ok(match token {
+            Some(token) => Csrf {
+                token,
+                fresh: false,
+            },
+            None => {
+                let mut bytes = [0; 32];
+                rand::thread_rng().fill_bytes(&mut bytes);
+                Csrf {
+                    token: hex::encode(bytes),
+                    fresh: true,
+                }
+            }
+        })
+    }
+}
+
+fn cookie(header: &str) -> Option<String> {
+    header
+        .split(';')
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, value)| *name == COOKIE && value.len() == 64)
+        .map(|(_, value)| value.to_string())
+}
+
+pub(super) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify() {
+        let token = "ab".repeat(32);
+        assert_eq!(
+            cookie(&format!("theme=dark; admin_csrf={}", token)),
+            Some(token.clone())
+        );
+        assert_eq!(cookie("admin_csrf=short"), None);
+
+        let csrf = Csrf {
+            token: token.clone(),
+            fresh: false,
+        };
+        assert!(csrf.verify(&token).is_ok());
+        assert!(csrf.verify(&"cd".repeat(32)).is_err());
+        assert!(csrf.verify("").is_err());
+        assert!(csrf.set_cookie().is_none());
+
+        let fresh = Csrf { token, fresh: true };
+        assert!(fresh.verify(fresh.token()).is_err());
+        assert!(fresh.set_cookie().is_some());
+    }
+}
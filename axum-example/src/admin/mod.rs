@@ -0,0 +1,724 @@
+//! 管理后台，服务端渲染的 HTML 页面，与 JSON 接口共用业务逻辑与校验
+
+use std::convert::Infallible;
+
+use askama::Template;
+use axum::{
+    body::{Bytes, Full},
+    extract::{extractor_middleware, Extension, Form, Path, Query},
+    http::{self, header, HeaderValue, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    AddExtensionLayer, Router,
+};
+
+use crate::{
+    algorithm::{AlgorithmInfo, CreateAlgorithmRequest, UpdateAlgorithmRequest},
+    dataset::{CreateDatasetRequest, DatasetInfo, Kind, Testset, Trainset},
+    error::Error,
+    service::{AlgorithmService, DatasetService, ImageInfo, ImageRequest, ImageService},
+};
+
+mod auth;
+mod csrf;
+
+pub use auth::Credentials;
+use csrf::Csrf;
+
+type Page = http::Response<Full<Bytes>>;
+
+/// 所有页面都需要认证，`credentials` 为 `None` 时管理后台不可用
+pub fn router(credentials: Option<Credentials>) -> Router {
+    Router::new()
+        .route(
+            "/",
+            get(|| async { redirect("/admin/algorithms".to_string()) }),
+        )
+        .route("/algorithms", get(algorithms).post(create_algorithm))
+        .route("/algorithms/:id", get(algorithm).post(update_algorithm))
+        .route("/images", get(images).post(create_image))
+        .route("/images/:id", get(image).post(update_image))
+        .route(
+            "/trainsets",
+            get(datasets::<Trainset>).post(create_dataset::<Trainset>),
+        )
+        .route(
+            "/trainsets/:id",
+            get(dataset::<Trainset>).post(update_dataset::<Trainset>),
+        )
+        .route(
+            "/testsets",
+            get(datasets::<Testset>).post(create_dataset::<Testset>),
+        )
+        .route(
+            "/testsets/:id",
+            get(dataset::<Testset>).post(update_dataset::<Testset>),
+        )
+        .layer(extractor_middleware::<auth::Authenticated>())
+        .layer(AddExtensionLayer::new(credentials))
+}
+
+#[derive(serde::Deserialize, Default)]
+struct Search {
+    #[serde(default)]
+    q: String,
+    /// 标签选择器，与 JSON 接口的 `labels` 参数相同
+    #[serde(default)]
+    labels: String,
+}
+
+impl Search {
+    fn matches(&self, fields: &[&String]) -> bool {
+        let q = self.q.trim().to_lowercase();
+        q.is_empty() || fields.iter().any(|f| f.to_lowercase().contains(&q))
+    }
+}
+
+#[derive(serde::Deserialize, Default)]
+struct AlgorithmForm {
+    #[serde(default)]
+    csrf: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    location: String,
+    #[serde(default)]
+    image: String,
+}
+
+impl AlgorithmForm {
+    fn image(&self) -> Result<u64, Error> {
+        self.image
+            .trim()
+            .parse()
+            .map_err(|_| Error::InvalidArgument("image must be an image id".to_string()))
+    }
+}
+
+impl From<&AlgorithmInfo> for AlgorithmForm {
+    fn from(algorithm: &AlgorithmInfo) -> Self {
+        Self {
+            csrf: String::new(),
+            name: algorithm.display_name.clone(),
+            location: algorithm.location.clone(),
+            image: algorithm.image.to_string(),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/algorithms.html")]
+struct AlgorithmsPage<'a> {
+    csrf: &'a str,
+    search: Search,
+    algorithms: Vec<AlgorithmInfo>,
+    form: AlgorithmForm,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/algorithm.html")]
+struct AlgorithmPage<'a> {
+    csrf: &'a str,
+    algorithm: AlgorithmInfo,
+    form: AlgorithmForm,
+    error: Option<String>,
+}
+
+async fn algorithms(
+    csrf: Csrf,
+    Extension(service): Extension<AlgorithmService>,
+    Query(search): Query<Search>,
+) -> Result<Page, Failure> {
+    let page = algorithms_page(&csrf, &service, search, AlgorithmForm::default(), None).await?;
+    render(&csrf, StatusCode::OK, page)
+}
+
+async fn algorithms_page<'a>(
+    csrf: &'a Csrf,
+    service: &AlgorithmService,
+    search: Search,
+    form: AlgorithmForm,
+    error: Option<String>,
+) -> Result<AlgorithmsPage<'a>, Failure> {
+    let algorithms = service
+        .list(&search.labels)
+        .await?
+        .into_iter()
+        .filter(|a| search.matches(&[&a.id, &a.name, &a.display_name, &a.location]))
+        .collect();
+    Ok(AlgorithmsPage {
+        csrf: csrf.token(),
+        search,
+        algorithms,
+        form,
+        error,
+    })
+}
+
+async fn create_algorithm(
+    csrf: Csrf,
+    Extension(service): Extension<AlgorithmService>,
+    Form(form): Form<AlgorithmForm>,
+) -> Result<Page, Failure> {
+    csrf.verify(&form.csrf)?;
+    let result = match form.image() {
+        Ok(image) => {
+            service
+                .create(CreateAlgorithmRequest {
+                    name: form.name.clone(),
+                    location: form.location.clone(),
+                    image,
+                })
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(id) => Ok(redirect(format!("/admin/algorithms/{}", id))),
+        Err(e) if invalid(&e) => {
+            let page = algorithms_page(
+                &csrf,
+                &service,
+                Search::default(),
+                form,
+                Some(e.to_string()),
+            )
+            .await?;
+            render(&csrf, StatusCode::BAD_REQUEST, page)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn algorithm(
+    csrf: Csrf,
+    Extension(service): Extension<AlgorithmService>,
+    Path(id): Path<i64>,
+) -> Result<Page, Failure> {
+    let algorithm = service.get(id).await?;
+    let page = AlgorithmPage {
+        csrf: csrf.token(),
+        form: AlgorithmForm::from(&algorithm),
+        algorithm,
+        error: None,
+    };
+    render(&csrf, StatusCode::OK, page)
+}
+
+async fn update_algorithm(
+    csrf: Csrf,
+    Extension(service): Extension<AlgorithmService>,
+    Path(id): Path<i64>,
+    Form(form): Form<AlgorithmForm>,
+) -> Result<Page, Failure> {
+    csrf.verify(&form.csrf)?;
+    let result = match form.image() {
+        Ok(image) => service.update(id, UpdateAlgorithmRequest { image }).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(_) => Ok(redirect(format!("/admin/algorithms/{}", id))),
+        Err(e) if invalid(&e) => {
+            let page = AlgorithmPage {
+                csrf: csrf.token(),
+                algorithm: service.get(id).await?,
+                form,
+                error: Some(e.to_string()),
+            };
+            render(&csrf, StatusCode::BAD_REQUEST, page)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[derive(serde::Deserialize, Default)]
+struct ImageForm {
+    #[serde(default)]
+    csrf: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    image: String,
+}
+
+impl ImageForm {
+    fn request(&self) -> ImageRequest {
+        ImageRequest {
+            name: self.name.clone(),
+            image: self.image.clone(),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/images.html")]
+struct ImagesPage<'a> {
+    csrf: &'a str,
+    search: Search,
+    images: Vec<ImageInfo>,
+    form: ImageForm,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/image.html")]
+struct ImagePage<'a> {
+    csrf: &'a str,
+    image: ImageInfo,
+    form: ImageForm,
+    error: Option<String>,
+}
+
+async fn images(
+    csrf: Csrf,
+    Extension(service): Extension<ImageService>,
+    Query(search): Query<Search>,
+) -> Result<Page, Failure> {
+    let page = images_page(&csrf, &service, search, ImageForm::default(), None).await?;
+    render(&csrf, StatusCode::OK, page)
+}
+
+async fn images_page<'a>(
+    csrf: &'a Csrf,
+    service: &ImageService,
+    search: Search,
+    form: ImageForm,
+    error: Option<String>,
+) -> Result<ImagesPage<'a>, Failure> {
+    let images = service
+        .list()
+        .await?
+        .into_iter()
+        .filter(|i| search.matches(&[&i.id.to_string(), &i.name, &i.image]))
+        .collect();
+    Ok(ImagesPage {
+        csrf: csrf.token(),
+        search,
+        images,
+        form,
+        error,
+    })
+}
+
+async fn create_image(
+    csrf: Csrf,
+    Extension(service): Extension<ImageService>,
+    Form(form): Form<ImageForm>,
+) -> Result<Page, Failure> {
+    csrf.verify(&form.csrf)?;
+    match service.create(form.request()).await {
+        Ok(id) => Ok(redirect(format!("/admin/images/{}", id))),
+        Err(e) if invalid(&e) => {
+            let page = images_page(
+                &csrf,
+                &service,
+                Search::default(),
+                form,
+                Some(e.to_string()),
+            )
+            .await?;
+            render(&csrf, StatusCode::BAD_REQUEST, page)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn image(
+    csrf: Csrf,
+    Extension(service): Extension<ImageService>,
+    Path(id): Path<i64>,
+) -> Result<Page, Failure> {
+    let image = service.get(id).await?;
+    let page = ImagePage {
+        csrf: csrf.token(),
+        form: ImageForm {
+            csrf: String::new(),
+            name: image.name.clone(),
+            image: image.image.clone(),
+        },
+        image,
+        error: None,
+    };
+    render(&csrf, StatusCode::OK, page)
+}
+
+async fn update_image(
+    csrf: Csrf,
+    Extension(service): Extension<ImageService>,
+    Path(id): Path<i64>,
+    Form(form): Form<ImageForm>,
+) -> Result<Page, Failure> {
+    csrf.verify(&form.csrf)?;
+    match service.update(id, form.request()).await {
+        Ok(_) => Ok(redirect(format!("/admin/images/{}", id))),
+        Err(e) if invalid(&e) => {
+            let page = ImagePage {
+                csrf: csrf.token(),
+                image: service.get(id).await?,
+                form,
+                error: Some(e.to_string()),
+            };
+            render(&csrf, StatusCode::BAD_REQUEST, page)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[derive(serde::Deserialize, Default)]
+struct DatasetForm {
+    #[serde(default)]
+    csrf: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    location: String,
+}
+
+impl DatasetForm {
+    fn request(&self) -> CreateDatasetRequest {
+        CreateDatasetRequest {
+            name: self.name.clone(),
+            location: self.location.clone(),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/datasets.html")]
+struct DatasetsPage<'a> {
+    csrf: &'a str,
+    kind: &'static str,
+    search: Search,
+    datasets: Vec<DatasetInfo>,
+    form: DatasetForm,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/dataset.html")]
+struct DatasetPage<'a> {
+    csrf: &'a str,
+    kind: &'static str,
+    dataset: DatasetInfo,
+    form: DatasetForm,
+    error: Option<String>,
+}
+
+/// 页面路径中的数据集类型，例如 `trainsets`
+fn kind_path<K: Kind>() -> &'static str {
+    match K::KIND.table() {
+        "trainset" => "trainsets",
+        _ => "testsets",
+    }
+}
+
+async fn datasets<K: Kind>(
+    csrf: Csrf,
+    Extension(service): Extension<DatasetService>,
+    Query(search): Query<Search>,
+) -> Result<Page, Failure> {
+    let page = datasets_page::<K>(&csrf, &service, search, DatasetForm::default(), None).await?;
+    render(&csrf, StatusCode::OK, page)
+}
+
+async fn datasets_page<'a, K: Kind>(
+    csrf: &'a Csrf,
+    service: &DatasetService,
+    search: Search,
+    form: DatasetForm,
+    error: Option<String>,
+) -> Result<DatasetsPage<'a>, Failure> {
+    let datasets = service
+        .list(K::KIND)
+        .await?
+        .into_iter()
+        .filter(|d| search.matches(&[&d.id, &d.name, &d.location]))
+        .collect();
+    Ok(DatasetsPage {
+        csrf: csrf.token(),
+        kind: kind_path::<K>(),
+        search,
+        datasets,
+        form,
+        error,
+    })
+}
+
+async fn create_dataset<K: Kind>(
+    csrf: Csrf,
+    Extension(service): Extension<DatasetService>,
+    Form(form): Form<DatasetForm>,
+) -> Result<Page, Failure> {
+    csrf.verify(&form.csrf)?;
+    match service.create(K::KIND, form.request()).await {
+        Ok(id) => Ok(redirect(format!("/admin/{}/{}", kind_path::<K>(), id))),
+        Err(e) if invalid(&e) => {
+            let page = datasets_page::<K>(
+                &csrf,
+                &service,
+                Search::default(),
+                form,
+                Some(e.to_string()),
+            )
+            .await?;
+            render(&csrf, StatusCode::BAD_REQUEST, page)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn dataset<K: Kind>(
+    csrf: Csrf,
+    Extension(service): Extension<DatasetService>,
+    Path(id): Path<i64>,
+) -> Result<Page, Failure> {
+    let dataset = service.get(K::KIND, id).await?;
+    let page = DatasetPage {
+        csrf: csrf.token(),
+        kind: kind_path::<K>(),
+        form: DatasetForm {
+            csrf: String::new(),
+            name: dataset.name.clone(),
+            location: dataset.location.clone(),
+        },
+        dataset,
+        error: None,
+    };
+    render(&csrf, StatusCode::OK, page)
+}
+
+async fn update_dataset<K: Kind>(
+    csrf: Csrf,
+    Extension(service): Extension<DatasetService>,
+    Path(id): Path<i64>,
+    Form(form): Form<DatasetForm>,
+) -> Result<Page, Failure> {
+    csrf.verify(&form.csrf)?;
+    match service.update(K::KIND, id, form.request()).await {
+        Ok(_) => Ok(redirect(format!("/admin/{}/{}", kind_path::<K>(), id))),
+        Err(e) if invalid(&e) => {
+            let page = DatasetPage {
+                csrf: csrf.token(),
+                kind: kind_path::<K>(),
+                dataset: service.get(K::KIND, id).await?,
+                form,
+                error: Some(e.to_string()),
+            };
+            render(&csrf, StatusCode::BAD_REQUEST, page)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 可以由用户修正的错误，在表单中展示而不是跳转到错误页面
+fn invalid(e: &Error) -> bool {
+    matches!(e, Error::InvalidArgument(_) | Error::DuplicateName)
+}
+
+fn render<T: Template>(csrf: &Csrf, status: StatusCode, page: T) -> Result<Page, Failure> {
+    let mut response = http::Response::new(Full::from(page.render()?));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    if let Some(cookie) = csrf.set_cookie() {
+        response.headers_mut().insert(header::SET_COOKIE, cookie);
+    }
+    Ok(response)
+}
+
+/// 提交表单后重定向，避免刷新页面时重复提交
+fn redirect(location: String) -> Page {
+    let mut response = http::Response::new(Full::default());
+    *response.status_mut() = StatusCode::SEE_OTHER;
+    response
+        .headers_mut()
+        .insert(header::LOCATION, HeaderValue::from_str(&location).unwrap());
+    response
+}
+
+#[derive(Template)]
+#[template(path = "admin/error.html")]
+struct ErrorPage {
+    status: StatusCode,
+    message: String,
+}
+
+#[derive(Debug)]
+pub enum Failure {
+    /// 未设置管理后台的密码
+    Disabled,
+    Unauthorized,
+    Csrf,
+    Service(Error),
+    Render(askama::Error),
+}
+
+impl From<Error> for Failure {
+    fn from(e: Error) -> Self {
+        Failure::Service(e)
+    }
+}
+
+impl From<askama::Error> for Failure {
+    fn from(e: askama::Error) -> Self {
+        Failure::Render(e)
+    }
+}
+
+impl IntoResponse for Failure {
+    type Body = Full<Bytes>;
+    type BodyError = Infallible;
+
+    fn into_response(self) -> http::Response<Self::Body> {
+        let (status, message) = match self {
+            Failure::Disabled => (
+                StatusCode::NOT_FOUND,
+                "admin console is disabled, set ADMIN_PASSWORD to enable it".to_string(),
+            ),
+            Failure::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                "authentication required".to_string(),
+            ),
+            Failure::Csrf => (
+                StatusCode::FORBIDDEN,
+                "invalid CSRF token, please reload the page and try again".to_string(),
+            ),
+            Failure::Service(e @ Error::NotFound(_)) => (StatusCode::NOT_FOUND, e.to_string()),
+            Failure::Service(e) if invalid(&e) => (StatusCode::BAD_REQUEST, e.to_string()),
+            Failure::Service(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            Failure::Render(e) => {
+                println!("failed to render admin page: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "failed to render page".to_string(),
+                )
+            }
+        };
+        let page = ErrorPage { status, message };
+        let mut response = http::Response::new(Full::from(page.render().unwrap_or_default()));
+        *response.status_mut() = status;
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        if status == StatusCode::UNAUTHORIZED {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static("Basic realm=\"admin\", charset=\"UTF-8\""),
+            );
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use chrono::Local;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    /// admin:secret
+    const AUTHORIZATION: &str = "Basic YWRtaW46c2VjcmV0";
+
+    #[tokio::test]
+    async fn create_algorithm_with_csrf() {
+        std::env::set_var("ADMIN_PASSWORD", "secret");
+        let app = crate::app().await;
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/admin/algorithms")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response.headers().contains_key(header::WWW_AUTHENTICATE));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/admin/algorithms")
+                    .header(header::AUTHORIZATION, AUTHORIZATION)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let cookie = response.headers()[header::SET_COOKIE]
+            .to_str()
+            .unwrap()
+            .split(';')
+            .next()
+            .unwrap()
+            .to_string();
+        let token = cookie.trim_start_matches("admin_csrf=").to_string();
+
+        let name = format!("alg-{}", Local::now().timestamp_nanos());
+        let form = |csrf: &str, image: &str| {
+            format!(
+                "csrf={}&name={}&location=%2Fa%2Fb&image={}",
+                csrf, name, image
+            )
+        };
+        let post = |cookie: Option<&str>, body: String| {
+            let mut req = Request::post("/admin/algorithms")
+                .header(header::AUTHORIZATION, AUTHORIZATION)
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+            if let Some(cookie) = cookie {
+                req = req.header(header::COOKIE, cookie);
+            }
+            req.body(Body::from(body)).unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(post(None, form(&token, "1000")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = app
+            .clone()
+            .oneshot(post(Some(&cookie), form(&token, "not-a-number")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .clone()
+            .oneshot(post(Some(&cookie), form(&token, "1000")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let location = response.headers()[header::LOCATION]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let response = app
+            .oneshot(
+                Request::get(location)
+                    .header(header::AUTHORIZATION, AUTHORIZATION)
+                    .header(header::COOKIE, &cookie)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::SET_COOKIE).is_none());
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(&name));
+        assert!(body.contains(&token));
+    }
+}
@@ -1,26 +1,20 @@
-use std::{
-    borrow::{Borrow, BorrowMut, Cow},
-    cell::RefCell,
+use axum::{
+    body::Body,
+    extract::{BodyStream, Extension, Path, Query},
+    http::{self, header},
 };
 
-use axum::{extract::Extension, response::IntoResponse, Json};
-use sea_query::MySqlQueryBuilder;
-use serde_json::json;
-use sqlx::{MySql, Pool};
-
-#[derive(serde::Serialize, serde::Deserialize)]
-pub struct CreateAlgorithmRequest {
-    pub name: String,
-    pub location: String,
-    pub image: u64,
-}
-
-pub type CreateAlgorithmResponse = String;
+use crate::{
+    negotiate::{Accept, Encoded, Payload, Rejection},
+    response::Response,
+    service::AlgorithmService,
+};
 
-std::thread_local! {
-    static SNOWFLAKE_GENERATOR: RefCell<snowflake::SnowflakeIdGenerator> =
-        RefCell::new(snowflake::SnowflakeIdGenerator::new(0, std::thread::current().id().as_u64().get() as i32));
-}
+pub use algorithm_client::types::{
+    AddLabelsRequest, AlgorithmInfo, CreateAlgorithmRequest, CreateAlgorithmResponse,
+    GetAlgorithmResponse, LabelsResponse, ListAlgorithmsQuery, ListAlgorithmsResponse,
+    RemoveLabelsRequest, UpdateAlgorithmRequest, UpdateAlgorithmResponse,
+};
 
 #[derive(sea_query::Iden)]
 pub enum Algorithm {
@@ -33,70 +27,88 @@ pub enum Algorithm {
 }
 
 pub async fn create(
-    Extension(pool): Extension<Pool<MySql>>,
-    Json(req): Json<CreateAlgorithmRequest>,
-) -> impl IntoResponse {
-    let display_name = req.name.to_lowercase();
+    accept: Accept,
+    Extension(service): Extension<AlgorithmService>,
+    Payload(req): Payload<CreateAlgorithmRequest>,
+) -> Encoded<Response<CreateAlgorithmResponse>> {
+    accept.respond(service.create(req).await.map(|id| id.to_string()))
+}
+
+pub async fn get(
+    accept: Accept,
+    Extension(service): Extension<AlgorithmService>,
+    Path(id): Path<i64>,
+) -> Encoded<Response<GetAlgorithmResponse>> {
+    accept.respond(service.get(id).await)
+}
+
+pub async fn update(
+    accept: Accept,
+    Extension(service): Extension<AlgorithmService>,
+    Path(id): Path<i64>,
+    Payload(req): Payload<UpdateAlgorithmRequest>,
+) -> Encoded<Response<UpdateAlgorithmResponse>> {
+    accept.respond(service.update(id, req).await)
+}
+
+pub async fn list(
+    accept: Accept,
+    Extension(service): Extension<AlgorithmService>,
+    Query(query): Query<ListAlgorithmsQuery>,
+) -> Encoded<Response<ListAlgorithmsResponse>> {
+    let selector = query.labels.unwrap_or_default();
+    accept.respond(service.list(&selector).await)
+}
 
-    let id = SNOWFLAKE_GENERATOR.with(|gen| gen.borrow_mut().generate());
+pub async fn add_labels(
+    accept: Accept,
+    Extension(service): Extension<AlgorithmService>,
+    Path(id): Path<i64>,
+    Payload(req): Payload<AddLabelsRequest>,
+) -> Encoded<Response<LabelsResponse>> {
+    accept.respond(service.add_labels(id, req).await)
+}
 
-    let query = sea_query::Query::insert()
-        .into_table(Algorithm::Table)
-        .columns(vec![
-            Algorithm::ID,
-            Algorithm::Name,
-            Algorithm::DisplayName,
-            Algorithm::Location,
-            Algorithm::Image,
-        ])
-        .values(vec![
-            id.into(),
-            display_name.into(),
-            req.name.into(),
-            req.location.into(),
-            req.image.into(),
-        ])
-        .unwrap()
-        .to_string(MySqlQueryBuilder {});
+pub async fn remove_labels(
+    accept: Accept,
+    Extension(service): Extension<AlgorithmService>,
+    Path(id): Path<i64>,
+    Payload(req): Payload<RemoveLabelsRequest>,
+) -> Encoded<Response<LabelsResponse>> {
+    accept.respond(service.remove_labels(id, req).await)
+}
 
-    match sqlx::query(&query).execute(&pool).await {
-        Ok(_) => Result::<_, ()>::Ok(Json(json!({
-            "code": "000000",
-            "data": id.to_string(),
-        }))),
-        Err(sqlx::Error::Database(e)) => {
-            // code: Some("42S02"), number: 1146, message: "Table 'testing.algorithm' doesn't exist"
-            // code: Some("23000"), number: 1062, message: "Duplicate entry 'alg-0000001' for key 'algorithm.unique_name'"
-            let code = e.code();
-            let message = e.message();
-            println!(
-                "database error: {:?}. code: {:?}, message: {}",
-                e, code, message
-            );
-            match code {
-                Some(Cow::Borrowed("23000")) => Ok(Json(json! ({
-                    "code": "000001", // duplicate name
-                    "message": "duplicate name",
-                }))),
-                Some(code) => Ok(Json(json! ({
-                    "code": "000002",
-                    "message": "unknown error",
-                }))),
-                None => Ok(Json(json! ({
-                    "code": "000003",
-                    "message": "unknown error",
-                }))),
-            }
-        }
-        Err(e) => {
-            println!("other error: {:?}", e);
-            Ok(Json(json! ({
-                "code": "000001",
-                "message": "",
-            })))
-        }
-    }
+/// 上传算法包，请求体为算法包的原始内容，超过大小上限时返回 413
+pub async fn upload_package(
+    accept: Accept,
+    Extension(service): Extension<AlgorithmService>,
+    Path(id): Path<i64>,
+    body: BodyStream,
+) -> (http::StatusCode, Encoded<Response<GetAlgorithmResponse>>) {
+    let result = service.upload_package(id, body).await;
+    let status = match &result {
+        Ok(_) => http::StatusCode::OK,
+        Err(e) => e.status(),
+    };
+    (status, accept.respond(result))
+}
 
-    // println!("id: {}", id);
-    // id.to_string()
+/// 下载算法包，内容与记录的 SHA-256 不一致时连接会在传输结束前中断。
+/// 错误按 `Accept` 编码，只接受算法包本身的格式时以 JSON 返回
+pub async fn download_package(
+    accept: Result<Accept, Rejection>,
+    Extension(service): Extension<AlgorithmService>,
+    Path(id): Path<i64>,
+) -> Result<http::Response<Body>, Encoded<Response<()>>> {
+    let accept = accept.unwrap_or_else(|e| e.accept());
+    let package = service
+        .download_package(id)
+        .await
+        .map_err(|e| accept.respond(Err(e)))?;
+    Ok(http::Response::builder()
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CONTENT_LENGTH, package.size)
+        .header("x-checksum-sha256", package.checksum)
+        .body(Body::wrap_stream(package.body))
+        .unwrap())
 }
@@ -0,0 +1,72 @@
+use axum::extract::{Extension, Path};
+
+use crate::{
+    negotiate::{Accept, Encoded, Payload},
+    response::Response,
+    service::{DatasetKind, DatasetService},
+};
+
+pub use algorithm_client::types::{
+    CreateDatasetRequest, CreateDatasetResponse, DatasetInfo, FileProblem, GetDatasetResponse,
+    ListDatasetsResponse, Manifest, ManifestFile, ManifestSummary, Problem, UpdateDatasetRequest,
+    Verification, VerificationStatus,
+};
+
+/// 训练集与测试集共用同一组接口，通过类型参数区分
+pub trait Kind: Send + Sync + 'static {
+    const KIND: DatasetKind;
+}
+
+pub struct Trainset;
+
+impl Kind for Trainset {
+    const KIND: DatasetKind = DatasetKind::Trainset;
+}
+
+pub struct Testset;
+
+impl Kind for Testset {
+    const KIND: DatasetKind = DatasetKind::Testset;
+}
+
+pub async fn create<K: Kind>(
+    accept: Accept,
+    Extension(service): Extension<DatasetService>,
+    Payload(req): Payload<CreateDatasetRequest>,
+) -> Encoded<Response<CreateDatasetResponse>> {
+    accept.respond(service.create(K::KIND, req).await.map(|id| id.to_string()))
+}
+
+pub async fn get<K: Kind>(
+    accept: Accept,
+    Extension(service): Extension<DatasetService>,
+    Path(id): Path<i64>,
+) -> Encoded<Response<GetDatasetResponse>> {
+    accept.respond(service.get(K::KIND, id).await)
+}
+
+pub async fn update<K: Kind>(
+    accept: Accept,
+    Extension(service): Extension<DatasetService>,
+    Path(id): Path<i64>,
+    Payload(req): Payload<UpdateDatasetRequest>,
+) -> Encoded<Response<GetDatasetResponse>> {
+    accept.respond(service.update(K::KIND, id, req).await)
+}
+
+pub async fn list<K: Kind>(
+    accept: Accept,
+    Extension(service): Extension<DatasetService>,
+) -> Encoded<Response<ListDatasetsResponse>> {
+    accept.respond(service.list(K::KIND).await)
+}
+
+/// 上传清单后数据集进入等待校验状态，由后台任务完成校验
+pub async fn put_manifest<K: Kind>(
+    accept: Accept,
+    Extension(service): Extension<DatasetService>,
+    Path(id): Path<i64>,
+    Payload(manifest): Payload<Manifest>,
+) -> Encoded<Response<GetDatasetResponse>> {
+    accept.respond(service.put_manifest(K::KIND, id, manifest).await)
+}
@@ -1 +1,123 @@
-pub struct DuplicateKey {}
+use std::{borrow::Cow, convert::Infallible};
+
+use axum::{
+    body::{Bytes, Full},
+    http::{self, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+
+use crate::{
+    i18n::Locale,
+    response::{code, Response},
+    storage::StorageError,
+};
+
+/// 业务错误，HTTP 与 gRPC 两种接口共用同一套错误码
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("duplicate name")]
+    DuplicateName,
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("{0} not found")]
+    NotFound(&'static str),
+    #[error("unknown error")]
+    Database(#[source] sqlx::Error),
+    #[error("unknown error")]
+    Other(#[source] sqlx::Error),
+    #[error("storage error")]
+    Storage(#[source] StorageError),
+    /// 数据库连接失败、获取连接超时或熔断器打开
+    #[error("service unavailable")]
+    Unavailable,
+    /// 上传的内容超过大小上限，参数为上限的字节数
+    #[error("payload larger than {0} bytes")]
+    PayloadTooLarge(u64),
+}
+
+impl Error {
+    /// 响应中的错误码，与统一 `Error` 之前的取值有不兼容的变化，见 `response::code`
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::DuplicateName => code::DUPLICATE_NAME,
+            Error::Database(_) => code::DATABASE,
+            Error::Other(_) => code::UNKNOWN,
+            Error::InvalidArgument(_) => code::INVALID_ARGUMENT,
+            Error::NotFound(_) => code::NOT_FOUND,
+            Error::Storage(_) => code::STORAGE,
+            Error::Unavailable => code::UNAVAILABLE,
+            Error::PayloadTooLarge(_) => code::PAYLOAD_TOO_LARGE,
+        }
+    }
+
+    /// HTTP 状态码，其余业务错误与成功一样返回 200，以 `code` 区分
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Error::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            Error::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            _ => StatusCode::OK,
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            // code: Some("42S02"), number: 1146, message: "Table 'testing.algorithm' doesn't exist"
+            // code: Some("23000"), number: 1062, message: "Duplicate entry 'alg-0000001' for key 'algorithm.unique_name'"
+            sqlx::Error::Database(e) if e.code() == Some(Cow::Borrowed("23000")) => {
+                Error::DuplicateName
+            }
+            sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed => {
+                println!("database unavailable: {:?}", e);
+                crate::db::mark_unavailable();
+                Error::Unavailable
+            }
+            sqlx::Error::Database(_) => {
+                println!("database error: {:?}", e);
+                Error::Database(e)
+            }
+            e => {
+                println!("other error: {:?}", e);
+                Error::Other(e)
+            }
+        }
+    }
+}
+
+impl From<StorageError> for Error {
+    fn from(e: StorageError) -> Self {
+        match e {
+            StorageError::NotFound(_) => Error::NotFound("package"),
+            StorageError::TooLarge(limit) => Error::PayloadTooLarge(limit),
+            e => {
+                println!("storage error: {:?}", e);
+                Error::Storage(e)
+            }
+        }
+    }
+}
+
+impl From<crate::label::ParseError> for Error {
+    fn from(e: crate::label::ParseError) -> Self {
+        Error::InvalidArgument(e.to_string())
+    }
+}
+
+impl IntoResponse for Error {
+    type Body = Full<Bytes>;
+    type BodyError = Infallible;
+
+    fn into_response(self) -> http::Response<Self::Body> {
+        let status = self.status();
+        let body = Json(Response::<()>::error(
+            self.code(),
+            Locale::default().message(&self),
+        ));
+        (status, body).into_response()
+    }
+}
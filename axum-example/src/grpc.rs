@@ -0,0 +1,212 @@
+//! gRPC 接口，与 HTTP 接口共用 `AlgorithmService`
+
+use tonic::{Request, Response, Status};
+
+use crate::{algorithm, error::Error, service::AlgorithmService};
+
+pub mod pb {
+    tonic::include_proto!("algorithm");
+}
+
+use pb::algorithm_service_server::AlgorithmServiceServer;
+
+pub struct GrpcAlgorithmService {
+    service: AlgorithmService,
+}
+
+impl GrpcAlgorithmService {
+    pub fn new(service: AlgorithmService) -> Self {
+        Self { service }
+    }
+}
+
+pub fn server(service: AlgorithmService) -> AlgorithmServiceServer<GrpcAlgorithmService> {
+    AlgorithmServiceServer::new(GrpcAlgorithmService::new(service))
+}
+
+#[tonic::async_trait]
+impl pb::algorithm_service_server::AlgorithmService for GrpcAlgorithmService {
+    async fn create_algorithm(
+        &self,
+        request: Request<pb::CreateAlgorithmRequest>,
+    ) -> Result<Response<pb::CreateAlgorithmResponse>, Status> {
+        let req = request.into_inner();
+        let id = self
+            .service
+            .create(algorithm::CreateAlgorithmRequest {
+                name: req.name,
+                location: req.location,
+                image: req.image,
+            })
+            .await?;
+        Ok(Response::new(pb::CreateAlgorithmResponse {
+            id: id.to_string(),
+        }))
+    }
+
+    async fn get_algorithm(
+        &self,
+        request: Request<pb::GetAlgorithmRequest>,
+    ) -> Result<Response<pb::Algorithm>, Status> {
+        let id = request
+            .into_inner()
+            .id
+            .parse::<i64>()
+            .map_err(|e| Error::InvalidArgument(format!("invalid id: {}", e)))?;
+        let algorithm = self.service.get(id).await?;
+        Ok(Response::new(algorithm.into()))
+    }
+
+    async fn list_algorithms(
+        &self,
+        request: Request<pb::ListAlgorithmsRequest>,
+    ) -> Result<Response<pb::ListAlgorithmsResponse>, Status> {
+        let algorithms = self
+            .service
+            .list(&request.into_inner().label_selector)
+            .await?;
+        Ok(Response::new(pb::ListAlgorithmsResponse {
+            algorithms: algorithms.into_iter().map(Into::into).collect(),
+        }))
+    }
+}
+
+impl From<algorithm::AlgorithmInfo> for pb::Algorithm {
+    fn from(algorithm: algorithm::AlgorithmInfo) -> Self {
+        Self {
+            id: algorithm.id,
+            name: algorithm.name,
+            display_name: algorithm.display_name,
+            location: algorithm.location,
+            image: algorithm.image,
+            labels: algorithm.labels.into_iter().collect(),
+            checksum: algorithm.checksum.unwrap_or_default(),
+            size: algorithm.size.unwrap_or_default(),
+        }
+    }
+}
+
+/// 业务错误码通过 `x-error-code` 元数据返回，与 HTTP 响应中的 `code` 一致
+impl From<Error> for Status {
+    fn from(e: Error) -> Self {
+        let mut status = match &e {
+            Error::DuplicateName => Status::already_exists(e.to_string()),
+            Error::InvalidArgument(_) => Status::invalid_argument(e.to_string()),
+            Error::NotFound(_) => Status::not_found(e.to_string()),
+            Error::Unavailable => Status::unavailable(e.to_string()),
+            Error::PayloadTooLarge(_) => Status::resource_exhausted(e.to_string()),
+            Error::Database(_) | Error::Other(_) | Error::Storage(_) => {
+                Status::internal(e.to_string())
+            }
+        };
+        status
+            .metadata_mut()
+            .insert("x-error-code", e.code().parse().unwrap());
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use tonic::transport::{Endpoint, Server, Uri};
+
+    use super::pb::algorithm_service_client::AlgorithmServiceClient;
+    use super::*;
+
+    /// 通过内存中的双向管道连接客户端与服务端，不需要监听端口
+    async fn client() -> AlgorithmServiceClient<tonic::transport::Channel> {
+        let (client, server) = tokio::io::duplex(1024);
+
+        let service = crate::services(crate::database().await).algorithms;
+        tokio::spawn(async move {
+            Server::builder()
+                .add_service(super::server(service))
+                .serve_with_incoming(futures::stream::iter(vec![Ok::<_, std::io::Error>(server)]))
+                .await
+        });
+
+        let mut client = Some(client);
+        let channel = Endpoint::try_from("http://[::]:50051")
+            .unwrap()
+            .connect_with_connector(tower::service_fn(move |_: Uri| {
+                let client = client.take();
+                async move {
+                    client.ok_or_else(|| {
+                        std::io::Error::new(std::io::ErrorKind::Other, "client already taken")
+                    })
+                }
+            }))
+            .await
+            .unwrap();
+
+        AlgorithmServiceClient::new(channel)
+    }
+
+    #[tokio::test]
+    async fn create_get_and_list() {
+        let mut client = client().await;
+
+        let name = format!("alg-grpc-{}", chrono::Local::now().timestamp_nanos());
+        let created = client
+            .create_algorithm(pb::CreateAlgorithmRequest {
+                name: name.clone(),
+                location: "/aaaaa/bbbbbb/ccccc/ddddd".to_string(),
+                image: 1000,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        let algorithm = client
+            .get_algorithm(pb::GetAlgorithmRequest {
+                id: created.id.clone(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(algorithm.display_name, name);
+
+        let algorithms = client
+            .list_algorithms(pb::ListAlgorithmsRequest::default())
+            .await
+            .unwrap()
+            .into_inner()
+            .algorithms;
+        assert!(algorithms.iter().any(|a| a.id == created.id));
+
+        let status = client
+            .create_algorithm(pb::CreateAlgorithmRequest {
+                name,
+                location: "/aaaaa/bbbbbb/ccccc/ddddd".to_string(),
+                image: 1000,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::AlreadyExists);
+        assert_eq!(status.metadata().get("x-error-code").unwrap(), "000001");
+    }
+
+    #[tokio::test]
+    async fn invalid_arguments() {
+        let mut client = client().await;
+
+        let status = client
+            .get_algorithm(pb::GetAlgorithmRequest {
+                id: "not-a-number".to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        let status = client
+            .list_algorithms(pb::ListAlgorithmsRequest {
+                label_selector: "task in (cv".to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(status.metadata().get("x-error-code").unwrap(), "000004");
+    }
+}
@@ -0,0 +1,216 @@
+//! 错误提示的多语言支持，根据 `Accept-Language` 选择语言
+
+use std::convert::Infallible;
+
+use axum::{
+    extract::{FromRequest, RequestParts},
+    http::{header, HeaderMap},
+};
+use fluent_bundle::{FluentArgs, FluentBundle, FluentResource};
+
+use crate::error::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Locale {
+    ZhCn,
+    EnUs,
+}
+
+/// 大部分用户使用中文，未指定或不支持请求的语言时使用中文
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::ZhCn
+    }
+}
+
+impl Locale {
+    const ALL: [Locale; 2] = [Locale::ZhCn, Locale::EnUs];
+
+    fn tag(self) -> &'static str {
+        match self {
+            Locale::ZhCn => "zh-CN",
+            Locale::EnUs => "en-US",
+        }
+    }
+
+    fn catalog(self) -> &'static str {
+        match self {
+            Locale::ZhCn => include_str!("../locales/zh-CN/errors.ftl"),
+            Locale::EnUs => include_str!("../locales/en-US/errors.ftl"),
+        }
+    }
+
+    /// 按 q 值选择语言，只比较主语言，例如 `zh-TW` 与 `zh` 都对应中文
+    pub fn from_accept_language(accept_language: &str) -> Option<Self> {
+        let mut best: Option<(Locale, f32)> = None;
+        for range in accept_language.split(',') {
+            let mut params = range.split(';');
+            let tag = params
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase();
+            let q = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if q <= 0.0 {
+                continue;
+            }
+
+            let locale = match tag.split('-').next().unwrap_or_default() {
+                "zh" => Locale::ZhCn,
+                "en" => Locale::EnUs,
+                "*" => Locale::default(),
+                _ => continue,
+            };
+            if !matches!(best, Some((_, best)) if best >= q) {
+                best = Some((locale, q));
+            }
+        }
+        best.map(|(locale, _)| locale)
+    }
+
+    /// 从 `Accept-Language` 中选择语言，未设置或不支持时使用默认语言
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Locale::from_accept_language)
+            .unwrap_or_default()
+    }
+
+    /// 错误的提示信息，翻译缺失时使用 `Error` 的英文描述
+    pub fn message(self, error: &Error) -> String {
+        let mut args = FluentArgs::new();
+        let id = match error {
+            Error::DuplicateName => "duplicate-name",
+            Error::Database(_) => "database",
+            Error::Other(_) => "unknown",
+            Error::InvalidArgument(detail) => {
+                args.set("detail", detail.as_str());
+                "invalid-argument"
+            }
+            Error::NotFound(resource) => {
+                args.set("resource", *resource);
+                "not-found"
+            }
+            Error::Storage(_) => "storage",
+            Error::Unavailable => "unavailable",
+            Error::PayloadTooLarge(limit) => {
+                args.set("limit", *limit);
+                "payload-too-large"
+            }
+        };
+
+        BUNDLES.with(|bundles| {
+            let bundle = &bundles[self as usize];
+            match bundle.get_message(id).and_then(|message| message.value()) {
+                Some(pattern) => {
+                    let mut errors = vec![];
+                    let message = bundle.format_pattern(pattern, Some(&args), &mut errors);
+                    if !errors.is_empty() {
+                        println!("failed to format {} in {}: {:?}", id, self.tag(), errors);
+                    }
+                    message.into_owned()
+                }
+                None => error.to_string(),
+            }
+        })
+    }
+}
+
+std::thread_local! {
+    static BUNDLES: Vec<FluentBundle<FluentResource>> = Locale::ALL
+        .iter()
+        .map(|locale| {
+            let resource = FluentResource::try_new(locale.catalog().to_string())
+                .unwrap_or_else(|(_, errors)| panic!("invalid {} catalog: {:?}", locale.tag(), errors));
+            let mut bundle = FluentBundle::new(vec![locale.tag().parse().unwrap()]);
+            // 不在参数两侧插入 Unicode 方向隔离符
+            bundle.set_use_isolating(false);
+            bundle.add_resource(resource).unwrap();
+            bundle
+        })
+        .collect();
+}
+
+#[async_trait::async_trait]
+impl<B: Send> FromRequest<B> for Locale {
+    type Rejection = Infallible;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        Ok(req.headers().map(Locale::from_headers).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_language() {
+        assert_eq!(
+            Locale::from_accept_language("en-US,en;q=0.9,zh-CN;q=0.8"),
+            Some(Locale::EnUs)
+        );
+        assert_eq!(
+            Locale::from_accept_language("fr, zh-TW;q=0.5"),
+            Some(Locale::ZhCn)
+        );
+        assert_eq!(Locale::from_accept_language("fr"), None);
+        assert_eq!(Locale::from_accept_language("en;q=0"), None);
+    }
+
+    #[test]
+    fn every_error_is_translated() {
+        let errors = [
+            Error::DuplicateName,
+            Error::Database(sqlx::Error::RowNotFound),
+            Error::Other(sqlx::Error::PoolTimedOut),
+            Error::InvalidArgument("name must not be empty".to_string()),
+            Error::NotFound("algorithm"),
+            Error::Storage(crate::storage::StorageError::Backend("down".to_string())),
+            Error::Unavailable,
+            Error::PayloadTooLarge(1024),
+        ];
+        for locale in Locale::ALL {
+            BUNDLES.with(|bundles| {
+                for id in [
+                    "duplicate-name",
+                    "database",
+                    "unknown",
+                    "invalid-argument",
+                    "not-found",
+                    "storage",
+                    "unavailable",
+                    "payload-too-large",
+                ] {
+                    assert!(
+                        bundles[locale as usize].has_message(id),
+                        "{} {}",
+                        locale.tag(),
+                        id
+                    );
+                }
+            });
+            for error in &errors {
+                assert!(!locale.message(error).is_empty());
+            }
+        }
+
+        assert_eq!(Locale::ZhCn.message(&Error::DuplicateName), "名称已存在");
+        assert_eq!(
+            Locale::ZhCn.message(&Error::NotFound("algorithm")),
+            "算法不存在"
+        );
+        assert_eq!(
+            Locale::EnUs.message(&Error::NotFound("algorithm")),
+            "algorithm not found"
+        );
+        assert_eq!(
+            Locale::EnUs.message(&Error::InvalidArgument("bad".to_string())),
+            "invalid argument: bad"
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! 标签选择器，语义与 Kubernetes 的 label selector 保持一致:
+//!
+//! * `key=value` / `key==value`: 存在 key 且值等于 value
+//! * `key!=value`: 不存在 key，或值不等于 value
+//! * `key in (v1,v2)`: 存在 key 且值属于集合
+//! * `key notin (v1,v2)`: 不存在 key，或值不属于集合
+//! * `key`: 存在 key
+//! * `!key`: 不存在 key
+//!
+//! 多个条件之间使用 `,` 分隔，表示逻辑与。
+
+use std::fmt;
+
+pub use algorithm_client::Labels;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operator {
+    Equals,
+    NotEquals,
+    In,
+    NotIn,
+    Exists,
+    DoesNotExist,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    pub key: String,
+    pub operator: Operator,
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Selector {
+    pub requirements: Vec<Requirement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError(String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid label selector: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl std::str::FromStr for Selector {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let requirements = split_requirements(s)?
+            .into_iter()
+            .map(parse_requirement)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Selector { requirements })
+    }
+}
+
+impl Selector {
+    pub fn is_empty(&self) -> bool {
+        self.requirements.is_empty()
+    }
+
+    /// 判断一组标签是否满足选择器
+    pub fn matches(&self, labels: &Labels) -> bool {
+        self.requirements.iter().all(|r| r.matches(labels))
+    }
+
+    /// 生成 `algorithm` 表上的 WHERE 条件，以及按顺序绑定的参数
+    pub fn to_sql(&self) -> (String, Vec<String>) {
+        let mut binds = vec![];
+        let conditions: Vec<String> = self
+            .requirements
+            .iter()
+            .map(|r| r.to_sql(&mut binds))
+            .collect();
+        (conditions.join(" AND "), binds)
+    }
+}
+
+impl Requirement {
+    pub fn matches(&self, labels: &Labels) -> bool {
+        let value = labels.get(&self.key);
+        match self.operator {
+            Operator::Equals | Operator::In => matches!(value, Some(v) if self.values.contains(v)),
+            Operator::NotEquals | Operator::NotIn => {
+                !matches!(value, Some(v) if self.values.contains(v))
+            }
+            Operator::Exists => value.is_some(),
+            Operator::DoesNotExist => value.is_none(),
+        }
+    }
+
+    fn to_sql(&self, binds: &mut Vec<String>) -> String {
+        const SUBQUERY: &str = "SELECT 1 FROM `algorithm_label` \
+            WHERE `algorithm_label`.`algorithm_id` = `algorithm`.`id` AND `algorithm_label`.`key` = ?";
+
+        binds.push(self.key.clone());
+        let values = if self.values.is_empty() {
+            String::new()
+        } else {
+            binds.extend(self.values.iter().cloned());
+            let placeholders = vec!["?"; self.values.len()].join(", ");
+            format!(" AND `algorithm_label`.`value` IN ({})", placeholders)
+        };
+
+        match self.operator {
+            Operator::Equals | Operator::In | Operator::Exists => {
+                format!("EXISTS ({}{})", SUBQUERY, values)
+            }
+            Operator::NotEquals | Operator::NotIn | Operator::DoesNotExist => {
+                format!("NOT EXISTS ({}{})", SUBQUERY, values)
+            }
+        }
+    }
+}
+
+/// 按顶层的 `,` 切分，忽略 `in (...)` 括号内部的逗号
+fn split_requirements(s: &str) -> Result<Vec<&str>, ParseError> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Err(ParseError(format!("unbalanced ')' in {:?}", s))),
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(ParseError(format!("unbalanced '(' in {:?}", s)));
+    }
+    parts.push(&s[start..]);
+    Ok(parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect())
+}
+
+fn parse_requirement(s: &str) -> Result<Requirement, ParseError> {
+    if let Some(key) = s.strip_prefix('!') {
+        return Ok(Requirement {
+            key: validate_key(key.trim())?,
+            operator: Operator::DoesNotExist,
+            values: vec![],
+        });
+    }
+
+    for (token, operator) in [
+        ("!=", Operator::NotEquals),
+        ("==", Operator::Equals),
+        ("=", Operator::Equals),
+    ] {
+        if let Some((key, value)) = s.split_once(token) {
+            return Ok(Requirement {
+                key: validate_key(key.trim())?,
+                operator,
+                values: vec![validate_value(value.trim())?],
+            });
+        }
+    }
+
+    if let Some(open) = s.find('(') {
+        let mut words = s[..open].split_whitespace();
+        let key = words.next().unwrap_or_default();
+        let operator = match (words.next(), words.next()) {
+            (Some("in"), None) => Operator::In,
+            (Some("notin"), None) => Operator::NotIn,
+            _ => return Err(ParseError(format!("expected 'in' or 'notin' in {:?}", s))),
+        };
+        let values = s[open..]
+            .strip_prefix('(')
+            .and_then(|rest| rest.strip_suffix(')'))
+            .ok_or_else(|| ParseError(format!("malformed value set in {:?}", s)))?
+            .split(',')
+            .map(|v| validate_value(v.trim()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if values.iter().all(String::is_empty) {
+            return Err(ParseError(format!("empty value set in {:?}", s)));
+        }
+        return Ok(Requirement {
+            key: validate_key(key)?,
+            operator,
+            values,
+        });
+    }
+
+    Ok(Requirement {
+        key: validate_key(s)?,
+        operator: Operator::Exists,
+        values: vec![],
+    })
+}
+
+/// 键由可选的 DNS 前缀与名称组成，例如 `example.com/task`，名称最长 63 个字符
+pub fn validate_key(key: &str) -> Result<String, ParseError> {
+    let name = match key.rsplit_once('/') {
+        Some((prefix, name)) => {
+            if prefix.is_empty()
+                || prefix.len() > 253
+                || !prefix
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.')
+            {
+                return Err(ParseError(format!("invalid key prefix {:?}", prefix)));
+            }
+            name
+        }
+        None => key,
+    };
+    if name.is_empty() || !is_label_token(name) {
+        return Err(ParseError(format!("invalid key {:?}", key)));
+    }
+    Ok(key.to_string())
+}
+
+/// 值可以为空，非空时规则与键的名称部分一致
+pub fn validate_value(value: &str) -> Result<String, ParseError> {
+    if !value.is_empty() && !is_label_token(value) {
+        return Err(ParseError(format!("invalid value {:?}", value)));
+    }
+    Ok(value.to_string())
+}
+
+fn is_label_token(s: &str) -> bool {
+    s.len() <= 63
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && s.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && s.ends_with(|c: char| c.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> Labels {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_selector() {
+        let selector: Selector = "task=cv, framework!=tf,env in (dev, test),!deprecated,owner"
+            .parse()
+            .unwrap();
+        let operators: Vec<_> = selector
+            .requirements
+            .iter()
+            .map(|r| (r.key.as_str(), r.operator.clone(), r.values.len()))
+            .collect();
+        assert_eq!(
+            operators,
+            vec![
+                ("task", Operator::Equals, 1),
+                ("framework", Operator::NotEquals, 1),
+                ("env", Operator::In, 2),
+                ("deprecated", Operator::DoesNotExist, 0),
+                ("owner", Operator::Exists, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_invalid_selector() {
+        for s in ["task in cv", "env in (dev", "=cv", "task=c v", "a notin ()"] {
+            assert!(s.parse::<Selector>().is_err(), "{:?} should be rejected", s);
+        }
+    }
+
+    #[test]
+    fn match_labels() {
+        let selector: Selector = "task=cv,framework!=tf".parse().unwrap();
+        assert!(selector.matches(&labels(&[("task", "cv")])));
+        assert!(selector.matches(&labels(&[("task", "cv"), ("framework", "torch")])));
+        assert!(!selector.matches(&labels(&[("task", "cv"), ("framework", "tf")])));
+        assert!(!selector.matches(&labels(&[("framework", "torch")])));
+    }
+
+    #[test]
+    fn selector_to_sql() {
+        let selector: Selector = "task=cv,env notin (dev,test)".parse().unwrap();
+        let (sql, binds) = selector.to_sql();
+        assert!(sql.starts_with("EXISTS ("));
+        assert!(sql.contains(" AND NOT EXISTS ("));
+        assert!(sql.contains("IN (?, ?)"));
+        assert_eq!(binds, vec!["task", "cv", "env", "dev", "test"]);
+    }
+}
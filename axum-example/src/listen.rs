@@ -0,0 +1,440 @@
+//! HTTP 服务的监听方式：TCP 或 Unix 域套接字，可选 TLS 终止与 HTTP/2
+//!
+//! TLS 证书定期从磁盘重新读取，证书续期后无需重启服务。
+
+use std::{
+    fs,
+    io::{self, BufReader},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+use axum::Router;
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, UnixListener},
+    sync::mpsc,
+};
+use tokio_rustls::{
+    rustls::{
+        server::{ClientHello, ResolvesServerCert},
+        sign::{self, CertifiedKey},
+        Certificate, PrivateKey, ServerConfig,
+    },
+    TlsAcceptor,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ListenError {
+    #[error("invalid listen address {0:?}")]
+    Address(String),
+    #[error("invalid HTTP version {0:?}, expected one of auto, http1, h2")]
+    Protocol(String),
+    #[error("tls: {0}")]
+    Tls(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Hyper(#[from] hyper::Error),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Address {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl Address {
+    /// `0.0.0.0:3000` 或 `unix:/run/app.sock`
+    pub fn parse(address: &str) -> Result<Self, ListenError> {
+        match address.strip_prefix("unix:") {
+            Some(path) if !path.is_empty() => Ok(Address::Unix(PathBuf::from(path))),
+            Some(_) => Err(ListenError::Address(address.to_string())),
+            None => address
+                .parse()
+                .map(Address::Tcp)
+                .map_err(|_| ListenError::Address(address.to_string())),
+        }
+    }
+}
+
+/// 提供的 HTTP 版本
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// HTTP/1.1 与 HTTP/2 均可，明文时根据连接前言识别，TLS 时通过 ALPN 协商
+    Auto,
+    Http1,
+    /// 仅 HTTP/2，明文时即 h2c（prior knowledge）
+    Http2,
+}
+
+impl Protocol {
+    pub fn parse(protocol: &str) -> Result<Self, ListenError> {
+        match protocol {
+            "auto" => Ok(Protocol::Auto),
+            "http1" => Ok(Protocol::Http1),
+            "h2" | "h2c" | "http2" => Ok(Protocol::Http2),
+            _ => Err(ListenError::Protocol(protocol.to_string())),
+        }
+    }
+
+    fn alpn(self) -> Vec<Vec<u8>> {
+        match self {
+            Protocol::Auto => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+            Protocol::Http1 => vec![b"http/1.1".to_vec()],
+            Protocol::Http2 => vec![b"h2".to_vec()],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Tls {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// 检查证书是否更新的间隔
+    pub reload_interval: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub address: Address,
+    pub protocol: Protocol,
+    pub tls: Option<Tls>,
+}
+
+impl Config {
+    /// 从环境变量读取监听配置:
+    ///
+    /// * `LISTEN`: `0.0.0.0:3000`（默认）或 `unix:/run/app.sock`
+    /// * `HTTP_VERSION`: `auto`（默认）、`http1` 或 `h2`，明文时 `h2` 即 h2c
+    /// * `TLS_CERT` 与 `TLS_KEY`: PEM 格式的证书链与私钥，设置后启用 TLS
+    /// * `TLS_RELOAD_INTERVAL`: 重新读取证书的间隔秒数，默认 60
+    pub fn from_env() -> Self {
+        let address = std::env::var("LISTEN").unwrap_or_else(|_| "0.0.0.0:3000".into());
+        let address =
+            Address::parse(&address).unwrap_or_else(|e| panic!("unsupported LISTEN: {}", e));
+        let protocol = std::env::var("HTTP_VERSION").unwrap_or_else(|_| "auto".into());
+        let protocol = Protocol::parse(&protocol)
+            .unwrap_or_else(|e| panic!("unsupported HTTP_VERSION: {}", e));
+        let tls = match (std::env::var_os("TLS_CERT"), std::env::var_os("TLS_KEY")) {
+            (Some(cert), Some(key)) => Some(Tls {
+                cert: cert.into(),
+                key: key.into(),
+                reload_interval: Duration::from_secs(
+                    std::env::var("TLS_RELOAD_INTERVAL")
+                        .ok()
+                        .and_then(|secs| secs.parse().ok())
+                        .unwrap_or(60),
+                ),
+            }),
+            (None, None) => None,
+            _ => panic!("TLS_CERT and TLS_KEY must be set together"),
+        };
+        Self {
+            address,
+            protocol,
+            tls,
+        }
+    }
+}
+
+/// 已建立的连接，TCP、Unix 域套接字或其上的 TLS
+trait Io: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + 'static> Io for T {}
+
+type Incoming = BoxStream<'static, Box<dyn Io>>;
+
+/// 按配置监听并处理请求，直到服务出错
+pub async fn serve(config: Config, app: Router) -> Result<(), ListenError> {
+    let incoming = match &config.address {
+        Address::Tcp(addr) => tcp(TcpListener::bind(addr).await?),
+        Address::Unix(path) => unix(bind_unix(path)?),
+    };
+    let incoming = match &config.tls {
+        Some(tls) => {
+            let resolver = Arc::new(CertResolver::new(&tls.cert, &tls.key)?);
+            spawn_reload(resolver.clone(), tls.reload_interval);
+            let mut server = ServerConfig::builder()
+                .with_safe_defaults()
+                .with_no_client_auth()
+                .with_cert_resolver(resolver);
+            server.alpn_protocols = config.protocol.alpn();
+            handshake(incoming, TlsAcceptor::from(Arc::new(server)))
+        }
+        None => incoming,
+    };
+    tracing::debug!(
+        "listening on {:?} ({:?}, tls: {})",
+        config.address,
+        config.protocol,
+        config.tls.is_some()
+    );
+
+    let accept = hyper::server::accept::from_stream(incoming.map(Ok::<_, io::Error>));
+    let mut server = axum::Server::builder(accept);
+    match config.protocol {
+        Protocol::Auto => {}
+        Protocol::Http1 => server = server.http1_only(true),
+        Protocol::Http2 => server = server.http2_only(true),
+    }
+    server.serve(app.into_make_service()).await?;
+    Ok(())
+}
+
+fn tcp(listener: TcpListener) -> Incoming {
+    stream::unfold(listener, |listener| async move {
+        loop {
+            match listener.accept().await {
+                Ok((conn, _)) => {
+                    let _ = conn.set_nodelay(true);
+                    return Some((Box::new(conn) as Box<dyn Io>, listener));
+                }
+                Err(e) => accept_error(e).await,
+            }
+        }
+    })
+    .boxed()
+}
+
+/// 绑定 Unix 域套接字，上次运行遗留的套接字文件会被删除
+fn bind_unix(path: &Path) -> io::Result<UnixListener> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            fs::remove_file(path)?;
+        }
+    }
+    UnixListener::bind(path)
+}
+
+fn unix(listener: UnixListener) -> Incoming {
+    stream::unfold(listener, |listener| async move {
+        loop {
+            match listener.accept().await {
+                Ok((conn, _)) => return Some((Box::new(conn) as Box<dyn Io>, listener)),
+                Err(e) => accept_error(e).await,
+            }
+        }
+    })
+    .boxed()
+}
+
+/// 文件描述符耗尽等错误时稍后重试，不让整个服务退出
+async fn accept_error(e: io::Error) {
+    println!("[LISTEN] failed to accept connection. {}", e);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+}
+
+/// 并发地完成 TLS 握手，握手慢的连接不会阻塞其他连接
+fn handshake(mut incoming: Incoming, acceptor: TlsAcceptor) -> Incoming {
+    let (tx, rx) = mpsc::channel(64);
+    tokio::spawn(async move {
+        while let Some(conn) = incoming.next().await {
+            let acceptor = acceptor.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                match tokio::time::timeout(Duration::from_secs(10), acceptor.accept(conn)).await {
+                    Ok(Ok(conn)) => {
+                        let _ = tx.send(Box::new(conn) as Box<dyn Io>).await;
+                    }
+                    Ok(Err(e)) => println!("[LISTEN] TLS handshake failed. {}", e),
+                    Err(_) => println!("[LISTEN] TLS handshake timed out"),
+                }
+            });
+        }
+    });
+    stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|conn| (conn, rx))
+    })
+    .boxed()
+}
+
+/// 从磁盘读取证书，文件内容变化后替换，新的连接使用新的证书
+struct CertResolver {
+    cert: PathBuf,
+    key: PathBuf,
+    /// 当前证书与读取时的文件内容
+    current: RwLock<Arc<CertifiedKey>>,
+    loaded: Mutex<(Vec<u8>, Vec<u8>)>,
+}
+
+impl CertResolver {
+    fn new(cert: &Path, key: &Path) -> Result<Self, ListenError> {
+        let (cert_pem, key_pem) = (fs::read(cert)?, fs::read(key)?);
+        let current = certified_key(&cert_pem, &key_pem)?;
+        Ok(Self {
+            cert: cert.to_path_buf(),
+            key: key.to_path_buf(),
+            current: RwLock::new(Arc::new(current)),
+            loaded: Mutex::new((cert_pem, key_pem)),
+        })
+    }
+
+    /// 证书或私钥变化时重新加载，返回是否替换了证书；新证书无效时保留原证书
+    fn reload(&self) -> Result<bool, ListenError> {
+        let (cert_pem, key_pem) = (fs::read(&self.cert)?, fs::read(&self.key)?);
+        let mut loaded = self.loaded.lock().unwrap();
+        if loaded.0 == cert_pem && loaded.1 == key_pem {
+            return Ok(false);
+        }
+        let current = certified_key(&cert_pem, &key_pem)?;
+        *self.current.write().unwrap() = Arc::new(current);
+        *loaded = (cert_pem, key_pem);
+        Ok(true)
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+fn certified_key(cert_pem: &[u8], key_pem: &[u8]) -> Result<CertifiedKey, ListenError> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(cert_pem))?;
+    if certs.is_empty() {
+        return Err(ListenError::Tls("no certificate found".into()));
+    }
+    let key = rustls_pemfile::read_all(&mut BufReader::new(key_pem))?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(key),
+            _ => None,
+        })
+        .ok_or_else(|| ListenError::Tls("no private key found".into()))?;
+    let key =
+        sign::any_supported_type(&PrivateKey(key)).map_err(|e| ListenError::Tls(e.to_string()))?;
+    Ok(CertifiedKey::new(
+        certs.into_iter().map(Certificate).collect(),
+        key,
+    ))
+}
+
+fn spawn_reload(resolver: Arc<CertResolver>, interval: Duration) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            match resolver.reload() {
+                Ok(true) => println!("[LISTEN] reloaded TLS certificate {:?}", resolver.cert),
+                Ok(false) => {}
+                Err(e) => println!(
+                    "[LISTEN] failed to reload TLS certificate {:?}, keep using the old one. {}",
+                    resolver.cert, e
+                ),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request, routing::get};
+    use hyper::Version;
+    use tokio::net::UnixStream;
+
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Address::parse("0.0.0.0:3000").unwrap(),
+            Address::Tcp(([0, 0, 0, 0], 3000).into())
+        );
+        assert_eq!(
+            Address::parse("unix:/run/app.sock").unwrap(),
+            Address::Unix("/run/app.sock".into())
+        );
+        assert!(Address::parse("unix:").is_err());
+        assert!(Address::parse("localhost").is_err());
+
+        assert_eq!(Protocol::parse("h2c").unwrap(), Protocol::Http2);
+        assert_eq!(Protocol::parse("http1").unwrap(), Protocol::Http1);
+        assert!(Protocol::parse("http3").is_err());
+    }
+
+    fn self_signed(dir: &Path, name: &str) -> (PathBuf, PathBuf) {
+        let cert = rcgen::generate_simple_self_signed(vec![name.to_string()]).unwrap();
+        let (cert_path, key_path) = (dir.join("tls.crt"), dir.join("tls.key"));
+        fs::write(&cert_path, cert.serialize_pem().unwrap()).unwrap();
+        fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+        (cert_path, key_path)
+    }
+
+    #[test]
+    fn reload_certificate() {
+        let dir = std::env::temp_dir().join(format!("listen-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let (cert, key) = self_signed(&dir, "a.example.com");
+        let resolver = CertResolver::new(&cert, &key).unwrap();
+        let first = resolver.current.read().unwrap().clone();
+        assert!(!resolver.reload().unwrap());
+
+        // 新证书无效时保留原证书
+        fs::write(&cert, "not a certificate").unwrap();
+        assert!(resolver.reload().is_err());
+        assert!(Arc::ptr_eq(&first, &resolver.current.read().unwrap()));
+
+        self_signed(&dir, "b.example.com");
+        assert!(resolver.reload().unwrap());
+        let second = resolver.current.read().unwrap().clone();
+        assert_ne!(first.cert, second.cert);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn serve_h2c_on_unix_socket() {
+        let path = std::env::temp_dir().join(format!("listen-{}.sock", std::process::id()));
+        let config = Config {
+            address: Address::Unix(path.clone()),
+            protocol: Protocol::Http2,
+            tls: None,
+        };
+        let app = Router::new().route("/", get(|| async { "Hello, World!" }));
+        tokio::spawn(serve(config, app));
+
+        let mut conn = None;
+        for _ in 0..50 {
+            match UnixStream::connect(&path).await {
+                Ok(c) => {
+                    conn = Some(c);
+                    break;
+                }
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        }
+        let (mut sender, connection) = hyper::client::conn::Builder::new()
+            .http2_only(true)
+            .handshake(conn.unwrap())
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let response = sender
+            .send_request(
+                Request::get("http://localhost/")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.version(), Version::HTTP_2);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"Hello, World!");
+
+        let _ = fs::remove_file(&path);
+    }
+}
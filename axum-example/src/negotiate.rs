@@ -0,0 +1,468 @@
+//! 根据 `Content-Type` 与 `Accept` 在 JSON、MessagePack 与 CBOR 之间选择请求体与响应体的编码
+
+use std::convert::Infallible;
+
+use axum::{
+    body::{Bytes, Full, HttpBody},
+    extract::{FromRequest, RequestParts},
+    http::{self, header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+    BoxError,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    error::Error,
+    i18n::Locale,
+    response::{code, Response},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::MessagePack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+        }
+    }
+
+    /// 解析 `Content-Type`，忽略参数，例如 `application/json; charset=utf-8`
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        Self::from_media_type(&essence.to_ascii_lowercase())
+    }
+
+    /// 按 `Accept` 中的 q 值选择编码，q 值相同时取靠前的一个，均不支持时返回 `None`
+    pub fn from_accept(accept: &str) -> Option<Self> {
+        let mut best: Option<(Format, f32)> = None;
+        for range in accept.split(',') {
+            let mut params = range.split(';');
+            let media_type = params
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase();
+            let q = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if q <= 0.0 {
+                continue;
+            }
+
+            let format = match media_type.as_str() {
+                "*/*" | "application/*" => Some(Format::Json),
+                media_type => Self::from_media_type(media_type),
+            };
+            if let Some(format) = format {
+                if !matches!(best, Some((_, best)) if best >= q) {
+                    best = Some((format, q));
+                }
+            }
+        }
+        best.map(|(format, _)| format)
+    }
+
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "application/json" => Some(Format::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Format::MessagePack)
+            }
+            "application/cbor" => Some(Format::Cbor),
+            t if t.starts_with("application/") && t.ends_with("+json") => Some(Format::Json),
+            t if t.starts_with("application/") && t.ends_with("+cbor") => Some(Format::Cbor),
+            _ => None,
+        }
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Format::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            // 以 map 而不是数组编码结构体，使 `skip_serializing_if` 与字段顺序无关
+            Format::MessagePack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+            Format::Cbor => serde_cbor::to_vec(value).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, body: &[u8]) -> Result<T, String> {
+        match self {
+            Format::Json => serde_json::from_slice(body).map_err(|e| e.to_string()),
+            Format::MessagePack => rmp_serde::from_slice(body).map_err(|e| e.to_string()),
+            Format::Cbor => serde_cbor::from_slice(body).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// 响应体的编码与错误提示的语言
+#[derive(Clone, Copy, Debug)]
+pub struct Accept {
+    pub format: Format,
+    pub locale: Locale,
+}
+
+impl Accept {
+    /// 将业务结果包装为 `Response<T>`，成功与失败都使用协商出的编码，错误提示使用协商出的语言
+    pub fn respond<T>(self, result: Result<T, Error>) -> Encoded<Response<T>> {
+        let response = match result {
+            Ok(data) => Response::ok(data),
+            Err(e) => Response::error(e.code(), self.locale.message(&e)),
+        };
+        Encoded(self.format, response)
+    }
+
+    /// 从 `Accept` 中选择响应的编码，未设置 `Accept` 时使用 JSON；从 `Accept-Language` 中选择语言
+    pub fn from_headers(headers: Option<&HeaderMap>) -> Result<Self, Rejection> {
+        let locale = headers.map(Locale::from_headers).unwrap_or_default();
+        let accept = headers.map(accept).unwrap_or_default();
+        if accept.is_empty() {
+            return Ok(Accept {
+                format: Format::Json,
+                locale,
+            });
+        }
+        match Format::from_accept(&accept) {
+            Some(format) => Ok(Accept { format, locale }),
+            // 没有可接受的编码时，错误本身只能以 JSON 返回
+            None => Err(Accept {
+                format: Format::Json,
+                locale,
+            }
+            .reject(Reason::NotAcceptable(accept))),
+        }
+    }
+
+    fn reject(self, reason: Reason) -> Rejection {
+        Rejection {
+            accept: self,
+            reason,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<B: Send> FromRequest<B> for Accept {
+    type Rejection = Rejection;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        Accept::from_headers(req.headers())
+    }
+}
+
+fn accept(headers: &HeaderMap) -> String {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// 按 `Content-Type` 解码请求体，用法与 `Json<T>` 相同
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Payload<T>(pub T);
+
+#[async_trait::async_trait]
+impl<T, B> FromRequest<B> for Payload<T>
+where
+    T: DeserializeOwned,
+    B: HttpBody + Send,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = Rejection;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        // 解码失败时按请求的编码与语言返回错误
+        let accept = Accept::from_headers(req.headers()).unwrap_or_else(|e| e.accept());
+        let content_type = req
+            .headers()
+            .and_then(|headers| headers.get(header::CONTENT_TYPE))
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let format = Format::from_content_type(&content_type)
+            .ok_or_else(|| accept.reject(Reason::UnsupportedMediaType(content_type)))?;
+
+        let body = req
+            .take_body()
+            .ok_or_else(|| accept.reject(Reason::BodyAlreadyExtracted))?;
+        let body = hyper::body::to_bytes(body).await.map_err(|e| {
+            let e: BoxError = e.into();
+            accept.reject(Reason::InvalidBody(e.to_string()))
+        })?;
+        format
+            .decode(&body)
+            .map(Payload)
+            .map_err(|e| accept.reject(Reason::InvalidBody(e)))
+    }
+}
+
+/// 以指定的编码序列化响应体
+#[derive(Debug, Clone, Copy)]
+pub struct Encoded<T>(pub Format, pub T);
+
+impl<T: Serialize> IntoResponse for Encoded<T> {
+    type Body = Full<Bytes>;
+    type BodyError = Infallible;
+
+    fn into_response(self) -> http::Response<Self::Body> {
+        let Encoded(format, value) = self;
+        match format.encode(&value) {
+            Ok(body) => {
+                let mut response = http::Response::new(Full::from(body));
+                response.headers_mut().insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(format.content_type()),
+                );
+                response
+            }
+            Err(e) => {
+                println!("failed to encode response as {:?}: {}", format, e);
+                let mut response = http::Response::new(Full::from(e));
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                response
+            }
+        }
+    }
+}
+
+/// 无法协商编码或解码请求体，与业务错误一样以 `Response<T>` 返回，使用请求的编码与语言
+#[derive(Debug)]
+pub struct Rejection {
+    accept: Accept,
+    reason: Reason,
+}
+
+#[derive(Debug)]
+pub enum Reason {
+    UnsupportedMediaType(String),
+    NotAcceptable(String),
+    InvalidBody(String),
+    BodyAlreadyExtracted,
+}
+
+impl Rejection {
+    /// 返回错误时使用的编码与语言
+    pub fn accept(&self) -> Accept {
+        self.accept
+    }
+}
+
+impl IntoResponse for Rejection {
+    type Body = Full<Bytes>;
+    type BodyError = Infallible;
+
+    fn into_response(self) -> http::Response<Self::Body> {
+        let (status, error) = match self.reason {
+            Reason::UnsupportedMediaType(content_type) => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                Error::InvalidArgument(format!(
+                    "unsupported content type {:?}, expected one of application/json, application/msgpack, application/cbor",
+                    content_type
+                )),
+            ),
+            Reason::NotAcceptable(accept) => (
+                StatusCode::NOT_ACCEPTABLE,
+                Error::InvalidArgument(format!(
+                    "none of {:?} is acceptable, expected one of application/json, application/msgpack, application/cbor",
+                    accept
+                )),
+            ),
+            Reason::InvalidBody(e) => (
+                StatusCode::BAD_REQUEST,
+                Error::InvalidArgument(format!("failed to decode request body: {}", e)),
+            ),
+            Reason::BodyAlreadyExtracted => {
+                let body = Response::<()>::error(code::UNKNOWN, "request body already extracted");
+                let mut response = Encoded(self.accept.format, body).into_response();
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                return response;
+            }
+        };
+        let mut response = self.accept.respond::<()>(Err(error)).into_response();
+        *response.status_mut() = status;
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request, routing::post, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::algorithm::{AlgorithmInfo, CreateAlgorithmRequest};
+
+    #[test]
+    fn negotiate() {
+        assert_eq!(
+            Format::from_content_type("application/json; charset=utf-8"),
+            Some(Format::Json)
+        );
+        assert_eq!(
+            Format::from_content_type("Application/X-MsgPack"),
+            Some(Format::MessagePack)
+        );
+        assert_eq!(Format::from_content_type("text/plain"), None);
+
+        assert_eq!(Format::from_accept("*/*"), Some(Format::Json));
+        assert_eq!(
+            Format::from_accept("application/json;q=0.5, application/cbor"),
+            Some(Format::Cbor)
+        );
+        assert_eq!(
+            Format::from_accept("application/msgpack, application/cbor"),
+            Some(Format::MessagePack)
+        );
+        assert_eq!(Format::from_accept("text/html, application/cbor;q=0"), None);
+    }
+
+    #[test]
+    fn round_trip() {
+        let info = AlgorithmInfo {
+            id: "1".to_string(),
+            name: "alg".to_string(),
+            display_name: "alg".to_string(),
+            location: "/a/b".to_string(),
+            image: 1000,
+            labels: [("task".to_string(), "cv".to_string())]
+                .into_iter()
+                .collect(),
+            checksum: None,
+            size: None,
+        };
+        for format in [Format::Json, Format::MessagePack, Format::Cbor] {
+            let body = format.encode(&Response::ok(info.clone())).unwrap();
+            let decoded: Response<AlgorithmInfo> = format.decode(&body).unwrap();
+            let decoded = decoded.data.unwrap();
+            assert_eq!(decoded.labels, info.labels, "{:?}", format);
+            assert_eq!(decoded.checksum, None, "{:?}", format);
+
+            let body = format
+                .encode(&Response::<()>::error("000005", "algorithm not found"))
+                .unwrap();
+            let decoded: Response<()> = format.decode(&body).unwrap();
+            assert_eq!(decoded.code, "000005", "{:?}", format);
+            assert!(decoded.data.is_none(), "{:?}", format);
+        }
+    }
+
+    #[tokio::test]
+    async fn extract_and_respond() {
+        let app = Router::new()
+            .route(
+                "/",
+                post(
+                    |accept: Accept, Payload(req): Payload<CreateAlgorithmRequest>| async move {
+                        accept.respond(Ok(req.name))
+                    },
+                ),
+            )
+            .route(
+                "/error",
+                post(
+                    |accept: Accept| async move { accept.respond::<()>(Err(Error::DuplicateName)) },
+                ),
+            );
+        let req = CreateAlgorithmRequest {
+            name: "alg".to_string(),
+            location: "/a/b".to_string(),
+            image: 1000,
+        };
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post("/")
+                    .header(header::CONTENT_TYPE, "application/msgpack")
+                    .header(header::ACCEPT, "application/cbor")
+                    .body(Body::from(rmp_serde::to_vec_named(&req).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/cbor");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: Response<String> = serde_cbor::from_slice(&body).unwrap();
+        assert_eq!(body.data.unwrap(), "alg");
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post("/")
+                    .header(header::CONTENT_TYPE, "text/plain")
+                    .body(Body::from("alg"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: Response<()> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.code, "000004");
+        assert!(body.message.unwrap().starts_with("参数错误"));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post("/")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .header(header::ACCEPT, "application/msgpack")
+                    .header(header::ACCEPT_LANGUAGE, "en")
+                    .body(Body::from("{"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/msgpack"
+        );
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: Response<()> = rmp_serde::from_slice(&body).unwrap();
+        assert_eq!(body.code, "000004");
+        assert!(body.message.unwrap().starts_with("invalid argument"));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post("/")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .header(header::ACCEPT, "text/html")
+                    .body(Body::from(serde_json::to_vec(&req).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+
+        for (accept_language, message) in [("en-US,en;q=0.9", "duplicate name"), ("", "名称已存在")]
+        {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::post("/error")
+                        .header(header::ACCEPT_LANGUAGE, accept_language)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            let body: Response<()> = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body.code, "000001");
+            assert_eq!(body.message.unwrap(), message);
+        }
+    }
+}
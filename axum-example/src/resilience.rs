@@ -0,0 +1,584 @@
+//! 请求超时、数据库熔断与过载保护
+//!
+//! MySQL 无响应时，请求会一直等待获取连接并不断堆积。熔断器在连续多次数据库不可用后打开，
+//! 打开期间直接返回 503；并发请求数超过上限时同样直接返回 503，不再排队等待。
+
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::{Bytes, Full},
+    extract::Extension,
+    http::{self, header, HeaderValue, StatusCode},
+    response::IntoResponse,
+    BoxError, Json,
+};
+use futures::future::{self, BoxFuture};
+use serde_json::{json, Value};
+use tokio::sync::Semaphore;
+use tower::{Layer, Service};
+
+use crate::{
+    db::{self, Database},
+    error::Error,
+    i18n::Locale,
+    negotiate::{Accept, Format},
+};
+
+tokio::task_local! {
+    /// 当前请求协商出的编码与语言，中间件拒绝请求时按它返回错误
+    static ACCEPT: Accept;
+}
+
+/// 超时的请求数
+static TIMED_OUT: AtomicU64 = AtomicU64::new(0);
+/// 因并发数超过上限被拒绝的请求数
+static SHED: AtomicU64 = AtomicU64::new(0);
+
+/// 被中间件直接拒绝的原因
+#[derive(thiserror::Error, Debug)]
+pub enum Rejected {
+    #[error("too many concurrent requests")]
+    Overloaded,
+    #[error("circuit breaker is open")]
+    BreakerOpen,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl BreakerState {
+    pub const ALL: [BreakerState; 3] = [
+        BreakerState::Closed,
+        BreakerState::Open,
+        BreakerState::HalfOpen,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BreakerState::Closed => "closed",
+            BreakerState::Open => "open",
+            BreakerState::HalfOpen => "half_open",
+        }
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    Closed {
+        failures: u32,
+    },
+    /// `until` 之前拒绝所有请求，之后放行一个探测请求并把 `until` 后移，
+    /// 探测请求成功则关闭，失败则继续保持打开
+    Open {
+        until: Instant,
+        probing: bool,
+    },
+}
+
+/// 数据库熔断器，连续 `threshold` 个请求遇到数据库不可用后打开，`cooldown` 后半开探测
+#[derive(Clone, Debug)]
+pub struct Breaker {
+    inner: Arc<BreakerInner>,
+}
+
+#[derive(Debug)]
+struct BreakerInner {
+    state: Mutex<State>,
+    threshold: u32,
+    cooldown: Duration,
+    trips: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl Default for Breaker {
+    fn default() -> Self {
+        Self::new(5, Duration::from_secs(30))
+    }
+}
+
+impl Breaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            inner: Arc::new(BreakerInner {
+                state: Mutex::new(State::Closed { failures: 0 }),
+                threshold,
+                cooldown,
+                trips: AtomicU64::new(0),
+                rejected: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// 是否放行请求
+    pub fn allow(&self) -> bool {
+        let mut state = self.inner.state.lock().unwrap();
+        match &mut *state {
+            State::Closed { .. } => true,
+            State::Open { until, probing } => {
+                let now = Instant::now();
+                if now < *until {
+                    self.inner.rejected.fetch_add(1, Ordering::Relaxed);
+                    return false;
+                }
+                *until = now + self.inner.cooldown;
+                *probing = true;
+                true
+            }
+        }
+    }
+
+    /// 请求内数据库可用
+    pub fn success(&self) {
+        let mut state = self.inner.state.lock().unwrap();
+        if let State::Open { .. } = *state {
+            println!("[BREAKER] database recovered, closing circuit breaker");
+        }
+        *state = State::Closed { failures: 0 };
+    }
+
+    /// 请求内数据库不可用
+    pub fn failure(&self) {
+        let mut state = self.inner.state.lock().unwrap();
+        let until = Instant::now() + self.inner.cooldown;
+        match &mut *state {
+            State::Closed { failures } => {
+                *failures += 1;
+                if *failures >= self.inner.threshold {
+                    println!(
+                        "[BREAKER] database failed {} times in a row, opening circuit breaker",
+                        failures
+                    );
+                    self.inner.trips.fetch_add(1, Ordering::Relaxed);
+                    *state = State::Open {
+                        until,
+                        probing: false,
+                    };
+                }
+            }
+            State::Open { probing, .. } => {
+                if *probing {
+                    *state = State::Open {
+                        until,
+                        probing: false,
+                    };
+                }
+            }
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        match *self.inner.state.lock().unwrap() {
+            State::Closed { .. } => BreakerState::Closed,
+            State::Open { probing: false, .. } => BreakerState::Open,
+            State::Open { probing: true, .. } => BreakerState::HalfOpen,
+        }
+    }
+
+    /// 熔断器打开的次数
+    pub fn trips(&self) -> u64 {
+        self.inner.trips.load(Ordering::Relaxed)
+    }
+
+    /// 熔断器打开期间被拒绝的请求数
+    pub fn rejected(&self) -> u64 {
+        self.inner.rejected.load(Ordering::Relaxed)
+    }
+}
+
+/// 熔断器打开时直接拒绝请求
+#[derive(Clone, Debug)]
+pub struct BreakerLayer {
+    breaker: Breaker,
+}
+
+impl BreakerLayer {
+    pub fn new(breaker: Breaker) -> Self {
+        Self { breaker }
+    }
+}
+
+impl<S> Layer<S> for BreakerLayer {
+    type Service = BreakerService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BreakerService {
+            inner,
+            breaker: self.breaker.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BreakerService<S> {
+    inner: S,
+    breaker: Breaker,
+}
+
+impl<S, R> Service<R> for BreakerService<S>
+where
+    S: Service<R>,
+    S::Response: Send + 'static,
+    S::Error: Into<BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<S::Response, BoxError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        if !self.breaker.allow() {
+            return Box::pin(future::ready(Err(Rejected::BreakerOpen.into())));
+        }
+        let response = self.inner.call(req);
+        Box::pin(async move { response.await.map_err(Into::into) })
+    }
+}
+
+/// 同时处理的请求数达到 `max` 后直接拒绝新的请求，所有被包装的路由共用同一个上限
+#[derive(Clone, Debug)]
+pub struct ShedLayer {
+    permits: Arc<Semaphore>,
+}
+
+impl ShedLayer {
+    pub fn new(max: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max)),
+        }
+    }
+}
+
+impl<S> Layer<S> for ShedLayer {
+    type Service = Shed<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Shed {
+            inner,
+            permits: self.permits.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Shed<S> {
+    inner: S,
+    permits: Arc<Semaphore>,
+}
+
+impl<S, R> Service<R> for Shed<S>
+where
+    S: Service<R>,
+    S::Response: Send + 'static,
+    S::Error: Into<BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<S::Response, BoxError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let permit = match self.permits.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                SHED.fetch_add(1, Ordering::Relaxed);
+                return Box::pin(future::ready(Err(Rejected::Overloaded.into())));
+            }
+        };
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            let response = response.await.map_err(Into::into);
+            drop(permit);
+            response
+        })
+    }
+}
+
+/// 在请求处理期间记录协商出的编码与语言，需要位于所有 `HandleErrorLayer` 之外
+#[derive(Clone, Copy, Debug)]
+pub struct NegotiateLayer;
+
+impl<S> Layer<S> for NegotiateLayer {
+    type Service = Negotiate<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Negotiate { inner }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Negotiate<S> {
+    inner: S,
+}
+
+impl<S, B> Service<http::Request<B>> for Negotiate<S>
+where
+    S: Service<http::Request<B>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<S::Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        // 没有可接受的编码时，中间件的错误以 JSON 返回
+        let accept = Accept::from_headers(Some(req.headers())).unwrap_or_else(|e| e.accept());
+        Box::pin(ACCEPT.scope(accept, self.inner.call(req)))
+    }
+}
+
+/// 超时与中间件的拒绝对应的状态码，超时返回 504，过载与熔断返回 503
+///
+/// 超时的请求访问过数据库时计入熔断器的失败次数，MySQL 无响应时请求不会返回任何数据库错误。
+fn rejected_status(error: &BoxError) -> StatusCode {
+    if error.is::<tower::timeout::error::Elapsed>() {
+        TIMED_OUT.fetch_add(1, Ordering::Relaxed);
+        db::mark_timed_out();
+        StatusCode::GATEWAY_TIMEOUT
+    } else if error.is::<Rejected>() {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        println!("unhandled middleware error: {}", error);
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// 将超时与中间件的拒绝转换为响应，响应体按请求协商出的编码与语言返回
+pub fn handle_error(error: BoxError) -> http::Response<Full<Bytes>> {
+    let status = rejected_status(&error);
+    let accept = ACCEPT.try_with(|accept| *accept).unwrap_or(Accept {
+        format: Format::Json,
+        locale: Locale::default(),
+    });
+    let mut response = accept
+        .respond::<()>(Err(Error::Unavailable))
+        .into_response();
+    *response.status_mut() = status;
+    response
+}
+
+/// gRPC 服务的 `handle_error`，超时返回 DEADLINE_EXCEEDED，过载与熔断返回 UNAVAILABLE
+pub fn grpc_status(error: BoxError) -> tonic::Status {
+    let error = match error.downcast::<tonic::Status>() {
+        Ok(status) => return *status,
+        Err(error) => error,
+    };
+    let message = error.to_string();
+    match rejected_status(&error) {
+        StatusCode::GATEWAY_TIMEOUT => tonic::Status::deadline_exceeded(message),
+        StatusCode::SERVICE_UNAVAILABLE => tonic::Status::unavailable(message),
+        _ => tonic::Status::internal(message),
+    }
+}
+
+/// 熔断器关闭且主库可用时就绪
+pub async fn readyz(Extension(db): Extension<Database>) -> (StatusCode, Json<Value>) {
+    let breaker = db.breaker().state();
+    let database = breaker != BreakerState::Open && db.ping(Duration::from_secs(1)).await;
+    let ready = breaker == BreakerState::Closed && database;
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(json!({
+            "ready": ready,
+            "breaker": breaker.as_str(),
+            "database": database,
+        })),
+    )
+}
+
+/// Prometheus 文本格式的指标
+pub async fn metrics(Extension(db): Extension<Database>) -> http::Response<Full<Bytes>> {
+    let breaker = db.breaker();
+    let state = breaker.state();
+    let mut body = String::new();
+    let _ = writeln!(body, "# TYPE http_requests_timed_out_total counter");
+    let _ = writeln!(
+        body,
+        "http_requests_timed_out_total {}",
+        TIMED_OUT.load(Ordering::Relaxed)
+    );
+    let _ = writeln!(body, "# TYPE http_requests_shed_total counter");
+    let _ = writeln!(
+        body,
+        "http_requests_shed_total {}",
+        SHED.load(Ordering::Relaxed)
+    );
+    let _ = writeln!(body, "# TYPE db_circuit_breaker_state gauge");
+    for s in BreakerState::ALL {
+        let _ = writeln!(
+            body,
+            "db_circuit_breaker_state{{state=\"{}\"}} {}",
+            s.as_str(),
+            (s == state) as u8
+        );
+    }
+    let _ = writeln!(body, "# TYPE db_circuit_breaker_trips_total counter");
+    let _ = writeln!(body, "db_circuit_breaker_trips_total {}", breaker.trips());
+    let _ = writeln!(body, "# TYPE db_circuit_breaker_rejected_total counter");
+    let _ = writeln!(
+        body,
+        "db_circuit_breaker_rejected_total {}",
+        breaker.rejected()
+    );
+    let mut response = http::Response::new(Full::from(body));
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; version=0.0.4"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use axum::{body::Body, error_handling::HandleErrorLayer, http::Request, routing::get, Router};
+    use tower::{service_fn, ServiceBuilder, ServiceExt};
+
+    use super::*;
+    use crate::response::Response;
+
+    #[test]
+    fn breaker() {
+        let breaker = Breaker::new(3, Duration::from_millis(50));
+        for _ in 0..2 {
+            assert!(breaker.allow());
+            breaker.failure();
+        }
+        // 成功后重新计数
+        breaker.success();
+        for _ in 0..3 {
+            assert!(breaker.allow());
+            breaker.failure();
+        }
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert_eq!(breaker.trips(), 1);
+        assert!(!breaker.allow());
+        assert_eq!(breaker.rejected(), 1);
+
+        // 冷却后只放行一个探测请求，探测失败继续打开
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breaker.allow());
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert!(!breaker.allow());
+        breaker.failure();
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.allow());
+
+        // 探测成功后关闭
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breaker.allow());
+        breaker.success();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(breaker.allow());
+        assert_eq!(breaker.trips(), 1);
+    }
+
+    #[tokio::test]
+    async fn shed_and_break() {
+        let slow = service_fn(|_: ()| async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok::<_, Infallible>(())
+        });
+        let shed = ShedLayer::new(1).layer(slow);
+        let first = tokio::spawn(shed.clone().oneshot(()));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let e = shed.clone().oneshot(()).await.unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<Rejected>(),
+            Some(Rejected::Overloaded)
+        ));
+        first.await.unwrap().unwrap();
+        shed.oneshot(()).await.unwrap();
+
+        let breaker = Breaker::new(1, Duration::from_secs(60));
+        let service = BreakerLayer::new(breaker.clone())
+            .layer(service_fn(|_: ()| async { Ok::<_, Infallible>(()) }));
+        service.clone().oneshot(()).await.unwrap();
+        breaker.failure();
+        let e = service.oneshot(()).await.unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<Rejected>(),
+            Some(Rejected::BreakerOpen)
+        ));
+    }
+
+    #[tokio::test]
+    async fn negotiate_rejections() {
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(handle_error))
+                    .layer(ShedLayer::new(0))
+                    .into_inner(),
+            )
+            .layer(NegotiateLayer);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/")
+                    .header(header::ACCEPT, "application/cbor")
+                    .header(header::ACCEPT_LANGUAGE, "en-US")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/cbor");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: Response<()> = serde_cbor::from_slice(&body).unwrap();
+        assert_eq!(body.code, "000007");
+        assert_eq!(
+            body.message.unwrap(),
+            "service temporarily unavailable, please retry later"
+        );
+
+        let response = app
+            .oneshot(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: Response<()> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.message.unwrap(), "服务暂时不可用，请稍后重试");
+    }
+
+    #[test]
+    fn grpc_rejections() {
+        let status = grpc_status(Rejected::Overloaded.into());
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+        let status = grpc_status(tonic::Status::not_found("algorithm").into());
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+}
@@ -1,6 +1 @@
-#[derive(serde::Deserialize)]
-pub struct Response<T> {
-    pub code: String,
-    pub message: Option<String>,
-    pub data: Option<T>,
-}
+pub use algorithm_client::{code, Response};
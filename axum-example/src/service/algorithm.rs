@@ -0,0 +1,317 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::body::Bytes;
+use futures::Stream;
+use sea_query::MySqlQueryBuilder;
+use sqlx::{mysql::MySqlRow, MySql, Row, Transaction};
+
+use crate::{
+    algorithm::{
+        AddLabelsRequest, Algorithm, AlgorithmInfo, CreateAlgorithmRequest, RemoveLabelsRequest,
+        UpdateAlgorithmRequest,
+    },
+    db::Database,
+    error::Error,
+    label::{self, Labels, Selector},
+    outbox::{self, event},
+    storage::{self, BlobStore, ByteStream, Spooled},
+};
+
+const ALGORITHM_COLUMNS: &str =
+    "`id`, `name`, `display_name`, `location`, `image`, `checksum`, `size`";
+
+/// 算法包默认的大小上限，1 GiB
+pub const DEFAULT_MAX_PACKAGE_SIZE: u64 = 1 << 30;
+
+#[derive(Clone)]
+pub struct AlgorithmService {
+    db: Database,
+    store: Arc<dyn BlobStore>,
+    max_package_size: u64,
+}
+
+/// 下载中的算法包，`body` 在读取结束时校验 SHA-256
+pub struct Package {
+    pub checksum: String,
+    pub size: u64,
+    pub body: ByteStream,
+}
+
+impl AlgorithmService {
+    pub fn new(db: Database, store: Arc<dyn BlobStore>) -> Self {
+        Self {
+            db,
+            store,
+            max_package_size: DEFAULT_MAX_PACKAGE_SIZE,
+        }
+    }
+
+    /// 上传的算法包超过 `max` 字节时返回 `Error::PayloadTooLarge`
+    pub fn with_max_package_size(mut self, max: u64) -> Self {
+        self.max_package_size = max;
+        self
+    }
+
+    /// 创建算法，返回新算法的 ID。`location` 可以为空，上传算法包时会自动设置
+    pub async fn create(&self, req: CreateAlgorithmRequest) -> Result<i64, Error> {
+        super::validate_text("name", &req.name)?;
+        if !req.location.is_empty() {
+            super::validate_text("location", &req.location)?;
+        }
+        let display_name = req.name.to_lowercase();
+
+        let id = super::next_id();
+
+        let query = sea_query::Query::insert()
+            .into_table(Algorithm::Table)
+            .columns(vec![
+                Algorithm::ID,
+                Algorithm::Name,
+                Algorithm::DisplayName,
+                Algorithm::Location,
+                Algorithm::Image,
+            ])
+            .values(vec![
+                id.into(),
+                display_name.into(),
+                req.name.into(),
+                req.location.into(),
+                req.image.into(),
+            ])
+            .unwrap()
+            .to_string(MySqlQueryBuilder {});
+
+        let mut tx = self.db.writer().begin().await?;
+        sqlx::query(&query).execute(&mut tx).await?;
+        record_event(&mut tx, id, event::ALGORITHM_CREATED).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    pub async fn get(&self, id: i64) -> Result<AlgorithmInfo, Error> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM `algorithm` WHERE `id` = ?",
+            ALGORITHM_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(self.db.reader())
+        .await?
+        .ok_or(Error::NotFound("algorithm"))?;
+
+        let labels = fetch_labels(self.db.reader(), &[id]).await?.remove(&id);
+        Ok(algorithm_info(row, labels.unwrap_or_default()))
+    }
+
+    /// 修改算法的镜像。名称创建后不能修改，`location` 与 `checksum`、`size` 一起由上传算法包设置
+    pub async fn update(
+        &self,
+        id: i64,
+        req: UpdateAlgorithmRequest,
+    ) -> Result<AlgorithmInfo, Error> {
+        let mut tx = self.db.writer().begin().await?;
+        lock_algorithm(&mut tx, id).await?;
+        sqlx::query("UPDATE `algorithm` SET `image` = ? WHERE `id` = ?")
+            .bind(req.image as i64)
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        let algorithm = record_event(&mut tx, id, event::ALGORITHM_UPDATED).await?;
+        tx.commit().await?;
+        Ok(algorithm)
+    }
+
+    /// 按标签选择器列出算法，选择器为空时返回全部算法
+    pub async fn list(&self, selector: &str) -> Result<Vec<AlgorithmInfo>, Error> {
+        let selector = selector.parse::<Selector>()?;
+
+        let mut sql = format!("SELECT {} FROM `algorithm`", ALGORITHM_COLUMNS);
+        let (condition, binds) = selector.to_sql();
+        if !selector.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&condition);
+        }
+        sql.push_str(" ORDER BY `id`");
+
+        let rows = binds
+            .into_iter()
+            .fold(sqlx::query(&sql), |query, bind| query.bind(bind))
+            .fetch_all(self.db.reader())
+            .await?;
+
+        let ids: Vec<i64> = rows.iter().map(|row| row.get("id")).collect();
+        let mut labels = fetch_labels(self.db.reader(), &ids).await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let id: i64 = row.get("id");
+                let labels = labels.remove(&id).unwrap_or_default();
+                algorithm_info(row, labels)
+            })
+            .collect())
+    }
+
+    /// 在同一个事务中添加标签，已存在的键会被覆盖，返回算法当前的全部标签
+    pub async fn add_labels(&self, id: i64, req: AddLabelsRequest) -> Result<Labels, Error> {
+        for (key, value) in &req.labels {
+            label::validate_key(key)?;
+            label::validate_value(value)?;
+        }
+
+        let mut tx = self.db.writer().begin().await?;
+        lock_algorithm(&mut tx, id).await?;
+        for (key, value) in &req.labels {
+            sqlx::query(
+                "INSERT INTO `algorithm_label` (`algorithm_id`, `key`, `value`) VALUES (?, ?, ?) \
+                 ON DUPLICATE KEY UPDATE `value` = VALUES(`value`)",
+            )
+            .bind(id)
+            .bind(key)
+            .bind(value)
+            .execute(&mut tx)
+            .await?;
+        }
+        let algorithm = record_event(&mut tx, id, event::ALGORITHM_UPDATED).await?;
+        tx.commit().await?;
+        Ok(algorithm.labels)
+    }
+
+    /// 上传算法包，计算 SHA-256 与大小后写入存储，并自动更新算法的 `location`。
+    /// 算法包按 SHA-256 存放，并发上传同一个算法时各自写入不同的对象，行锁保证最终记录的
+    /// `location`、`checksum` 与 `size` 来自同一次上传
+    pub async fn upload_package<S, E>(&self, id: i64, body: S) -> Result<AlgorithmInfo, Error>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Send,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.get(id).await?;
+
+        let spooled = Spooled::from_stream(Box::pin(body), self.max_package_size).await?;
+        let location = self
+            .store
+            .put(&package_key(id, &spooled.checksum), &spooled.path)
+            .await?;
+
+        let mut tx = self.db.writer().begin().await?;
+        lock_algorithm(&mut tx, id).await?;
+        sqlx::query(
+            "UPDATE `algorithm` SET `location` = ?, `checksum` = ?, `size` = ? WHERE `id` = ?",
+        )
+        .bind(&location)
+        .bind(&spooled.checksum)
+        .bind(spooled.size as i64)
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+        let algorithm = record_event(&mut tx, id, event::ALGORITHM_UPDATED).await?;
+        tx.commit().await?;
+        Ok(algorithm)
+    }
+
+    pub async fn download_package(&self, id: i64) -> Result<Package, Error> {
+        let algorithm = self.get(id).await?;
+        let (checksum, size) = match (algorithm.checksum, algorithm.size) {
+            (Some(checksum), Some(size)) => (checksum, size),
+            _ => return Err(Error::NotFound("package")),
+        };
+        let body = self.store.get(&package_key(id, &checksum)).await?;
+        Ok(Package {
+            body: storage::verify(body, checksum.clone()),
+            checksum,
+            size,
+        })
+    }
+
+    /// 在同一个事务中删除标签，不存在的键会被忽略，返回算法当前的全部标签
+    pub async fn remove_labels(&self, id: i64, req: RemoveLabelsRequest) -> Result<Labels, Error> {
+        let mut tx = self.db.writer().begin().await?;
+        lock_algorithm(&mut tx, id).await?;
+        for key in &req.keys {
+            sqlx::query("DELETE FROM `algorithm_label` WHERE `algorithm_id` = ? AND `key` = ?")
+                .bind(id)
+                .bind(key)
+                .execute(&mut tx)
+                .await?;
+        }
+        let algorithm = record_event(&mut tx, id, event::ALGORITHM_UPDATED).await?;
+        tx.commit().await?;
+        Ok(algorithm.labels)
+    }
+}
+
+fn algorithm_info(row: MySqlRow, labels: Labels) -> AlgorithmInfo {
+    let id: i64 = row.get("id");
+    let image: i64 = row.get("image");
+    let size: Option<i64> = row.get("size");
+    AlgorithmInfo {
+        id: id.to_string(),
+        name: row.get("name"),
+        display_name: row.get("display_name"),
+        location: row.get("location"),
+        image: image as u64,
+        labels,
+        checksum: row.get("checksum"),
+        size: size.map(|size| size as u64),
+    }
+}
+
+/// 按内容寻址的键，记录中的 `checksum` 决定读取哪个对象
+fn package_key(id: i64, checksum: &str) -> String {
+    format!("algorithms/{}/packages/{}", id, checksum)
+}
+
+/// 锁定算法所在的行，避免并发修改标签时与删除算法交错
+async fn lock_algorithm(tx: &mut Transaction<'_, MySql>, id: i64) -> Result<(), Error> {
+    sqlx::query("SELECT `id` FROM `algorithm` WHERE `id` = ? FOR UPDATE")
+        .bind(id)
+        .fetch_optional(tx)
+        .await?
+        .map(|_| ())
+        .ok_or(Error::NotFound("algorithm"))
+}
+
+/// 在事务中读取变更后的算法并写入 outbox，返回变更后的算法
+async fn record_event(
+    tx: &mut Transaction<'_, MySql>,
+    id: i64,
+    event_type: &str,
+) -> Result<AlgorithmInfo, Error> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM `algorithm` WHERE `id` = ?",
+        ALGORITHM_COLUMNS
+    ))
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await?;
+    let labels = fetch_labels(&mut *tx, &[id]).await?.remove(&id);
+    let algorithm = algorithm_info(row, labels.unwrap_or_default());
+    outbox::record(tx, "algorithm", id, event_type, &algorithm).await?;
+    Ok(algorithm)
+}
+
+async fn fetch_labels<'e, E>(executor: E, ids: &[i64]) -> Result<HashMap<i64, Labels>, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    let mut labels: HashMap<i64, Labels> = HashMap::new();
+    if ids.is_empty() {
+        return Ok(labels);
+    }
+
+    let sql = format!(
+        "SELECT `algorithm_id`, `key`, `value` FROM `algorithm_label` WHERE `algorithm_id` IN ({})",
+        vec!["?"; ids.len()].join(", ")
+    );
+    let rows = ids
+        .iter()
+        .fold(sqlx::query(&sql), |query, id| query.bind(id))
+        .fetch_all(executor)
+        .await?;
+    for row in rows {
+        labels
+            .entry(row.get("algorithm_id"))
+            .or_default()
+            .insert(row.get("key"), row.get("value"));
+    }
+    Ok(labels)
+}
@@ -0,0 +1,546 @@
+use std::{collections::HashSet, path::Component, time::Duration};
+
+use futures::StreamExt;
+use sha2::{Digest, Sha256};
+use sqlx::{mysql::MySqlRow, Row};
+
+use crate::{
+    dataset::{
+        CreateDatasetRequest, DatasetInfo, FileProblem, Manifest, ManifestSummary, Problem,
+        UpdateDatasetRequest, Verification, VerificationStatus,
+    },
+    db::Database,
+    error::Error,
+    storage::{self, BlobStore, StorageError},
+};
+
+/// 已校验的数据集超过该时间后重新校验
+const REVERIFY_AFTER: Duration = Duration::from_secs(24 * 3600);
+
+const DATASET_COLUMNS: &str =
+    "`id`, `name`, `location`, `manifest`, `verification_status`, `verification_problems`, `verified_at`";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DatasetKind {
+    Trainset,
+    Testset,
+}
+
+impl DatasetKind {
+    pub const ALL: [DatasetKind; 2] = [DatasetKind::Trainset, DatasetKind::Testset];
+
+    pub fn table(self) -> &'static str {
+        match self {
+            DatasetKind::Trainset => "trainset",
+            DatasetKind::Testset => "testset",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct DatasetService {
+    db: Database,
+}
+
+impl DatasetService {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    /// 创建数据集，`location` 必须是 `file://` 或 `s3://` 开头的位置
+    pub async fn create(&self, kind: DatasetKind, req: CreateDatasetRequest) -> Result<i64, Error> {
+        validate_dataset(&req)?;
+
+        let id = super::next_id();
+        sqlx::query(&format!(
+            "INSERT INTO `{}` (`id`, `name`, `location`) VALUES (?, ?, ?)",
+            kind.table()
+        ))
+        .bind(id)
+        .bind(req.name)
+        .bind(req.location)
+        .execute(self.db.writer())
+        .await?;
+        Ok(id)
+    }
+
+    pub async fn get(&self, kind: DatasetKind, id: i64) -> Result<DatasetInfo, Error> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM `{}` WHERE `id` = ?",
+            DATASET_COLUMNS,
+            kind.table()
+        ))
+        .bind(id)
+        .fetch_optional(self.db.reader())
+        .await?
+        .ok_or(Error::NotFound(kind.table()))?;
+        dataset_info(row)
+    }
+
+    pub async fn list(&self, kind: DatasetKind) -> Result<Vec<DatasetInfo>, Error> {
+        sqlx::query(&format!(
+            "SELECT {} FROM `{}` ORDER BY `id`",
+            DATASET_COLUMNS,
+            kind.table()
+        ))
+        .fetch_all(self.db.reader())
+        .await?
+        .into_iter()
+        .map(dataset_info)
+        .collect()
+    }
+
+    /// 修改名称与位置，位置变化后已上传的清单需要重新校验
+    pub async fn update(
+        &self,
+        kind: DatasetKind,
+        id: i64,
+        req: UpdateDatasetRequest,
+    ) -> Result<DatasetInfo, Error> {
+        validate_dataset(&req)?;
+
+        // MySQL 按顺序赋值，比较 `location` 时仍是修改前的值
+        sqlx::query(&format!(
+            "UPDATE `{}` SET `verification_status` = IF(`manifest` IS NOT NULL AND `location` <> ?, ?, `verification_status`), \
+             `name` = ?, `location` = ? WHERE `id` = ?",
+            kind.table()
+        ))
+        .bind(&req.location)
+        .bind(status_str(VerificationStatus::Pending))
+        .bind(req.name)
+        .bind(&req.location)
+        .bind(id)
+        .execute(self.db.writer())
+        .await?;
+        self.get(kind, id).await
+    }
+
+    /// 上传清单，覆盖已有的清单，并等待后台重新校验
+    pub async fn put_manifest(
+        &self,
+        kind: DatasetKind,
+        id: i64,
+        mut manifest: Manifest,
+    ) -> Result<DatasetInfo, Error> {
+        validate_manifest(&mut manifest)?;
+
+        sqlx::query(&format!(
+            "UPDATE `{}` SET `manifest` = ?, `verification_status` = ?, \
+             `verification_problems` = NULL, `verified_at` = NULL WHERE `id` = ?",
+            kind.table()
+        ))
+        .bind(serde_json::to_string(&manifest).unwrap())
+        .bind(status_str(VerificationStatus::Pending))
+        .bind(id)
+        .execute(self.db.writer())
+        .await?;
+        self.get(kind, id).await
+    }
+
+    /// 校验所有等待校验的数据集，返回本轮校验的数据集数量
+    ///
+    /// 校验任务只访问主库，避免从库的复制延迟导致重复认领或读到旧的清单
+    pub async fn verify_pending(&self) -> Result<usize, Error> {
+        let mut verified = 0;
+        for kind in DatasetKind::ALL {
+            sqlx::query(&format!(
+                "UPDATE `{}` SET `verification_status` = ? \
+                 WHERE `verification_status` IN (?, ?) AND `verified_at` < DATE_SUB(NOW(), INTERVAL ? SECOND)",
+                kind.table()
+            ))
+            .bind(status_str(VerificationStatus::Pending))
+            .bind(status_str(VerificationStatus::Verified))
+            .bind(status_str(VerificationStatus::Failed))
+            .bind(REVERIFY_AFTER.as_secs())
+            .execute(self.db.writer())
+            .await?;
+
+            let ids: Vec<i64> = sqlx::query(&format!(
+                "SELECT `id` FROM `{}` WHERE `verification_status` = ?",
+                kind.table()
+            ))
+            .bind(status_str(VerificationStatus::Pending))
+            .fetch_all(self.db.writer())
+            .await?
+            .iter()
+            .map(|row| row.get("id"))
+            .collect();
+
+            for id in ids {
+                if self.verify(kind, id).await? {
+                    verified += 1;
+                }
+            }
+        }
+        Ok(verified)
+    }
+
+    /// 将中断的校验重新标记为等待校验，在启动校验任务时调用
+    pub async fn reset_interrupted(&self) -> Result<(), Error> {
+        for kind in DatasetKind::ALL {
+            sqlx::query(&format!(
+                "UPDATE `{}` SET `verification_status` = ? WHERE `verification_status` = ?",
+                kind.table()
+            ))
+            .bind(status_str(VerificationStatus::Pending))
+            .bind(status_str(VerificationStatus::Verifying))
+            .execute(self.db.writer())
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// 认领并校验一个数据集，已被其他校验任务认领时返回 `false`
+    async fn verify(&self, kind: DatasetKind, id: i64) -> Result<bool, Error> {
+        let claimed = sqlx::query(&format!(
+            "UPDATE `{}` SET `verification_status` = ? WHERE `id` = ? AND `verification_status` = ?",
+            kind.table()
+        ))
+        .bind(status_str(VerificationStatus::Verifying))
+        .bind(id)
+        .bind(status_str(VerificationStatus::Pending))
+        .execute(self.db.writer())
+        .await?;
+        if claimed.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        // 出错时放回等待校验，否则会一直停留在校验中，直到重启
+        if let Err(e) = self.verify_claimed(kind, id).await {
+            sqlx::query(&format!(
+                "UPDATE `{}` SET `verification_status` = ? WHERE `id` = ? AND `verification_status` = ?",
+                kind.table()
+            ))
+            .bind(status_str(VerificationStatus::Pending))
+            .bind(id)
+            .bind(status_str(VerificationStatus::Verifying))
+            .execute(self.db.writer())
+            .await?;
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    async fn verify_claimed(&self, kind: DatasetKind, id: i64) -> Result<(), Error> {
+        let row = sqlx::query(&format!(
+            "SELECT `location`, `manifest` FROM `{}` WHERE `id` = ?",
+            kind.table()
+        ))
+        .bind(id)
+        .fetch_one(self.db.writer())
+        .await?;
+        let location: String = row.get("location");
+        let manifest: Manifest = parse_manifest(row.get("manifest"))?.unwrap_or_default();
+
+        let problems = match storage::open(&location) {
+            Ok((store, prefix)) => verify_manifest(store.as_ref(), &prefix, &manifest).await,
+            Err(e) => manifest
+                .files
+                .iter()
+                .map(|file| FileProblem {
+                    path: file.path.clone(),
+                    problem: Problem::Unreadable {
+                        message: e.to_string(),
+                    },
+                })
+                .collect(),
+        };
+        let status = if problems.is_empty() {
+            VerificationStatus::Verified
+        } else {
+            VerificationStatus::Failed
+        };
+
+        // 校验期间重新上传了清单时状态已变为等待校验，丢弃本次结果
+        sqlx::query(&format!(
+            "UPDATE `{}` SET `verification_status` = ?, `verification_problems` = ?, `verified_at` = NOW() \
+             WHERE `id` = ? AND `verification_status` = ?",
+            kind.table()
+        ))
+        .bind(status_str(status))
+        .bind(serde_json::to_string(&problems).unwrap())
+        .bind(id)
+        .bind(status_str(VerificationStatus::Verifying))
+        .execute(self.db.writer())
+        .await?;
+        Ok(())
+    }
+}
+
+/// 启动后台校验任务，每隔 `interval` 校验一次等待校验的数据集
+pub fn spawn_verifier(service: DatasetService, interval: Duration) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        if let Err(e) = service.reset_interrupted().await {
+            println!(
+                "[VERIFIER] failed to reset interrupted verifications. {}",
+                e
+            );
+        }
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match service.verify_pending().await {
+                Ok(0) => {}
+                Ok(n) => println!("[VERIFIER] verified {} datasets", n),
+                Err(e) => println!("[VERIFIER] {}", e),
+            }
+        }
+    })
+}
+
+/// 逐个读取清单中的文件，比较大小与 SHA-256，返回缺失或损坏的文件
+pub async fn verify_manifest(
+    store: &dyn BlobStore,
+    prefix: &str,
+    manifest: &Manifest,
+) -> Vec<FileProblem> {
+    let mut problems = vec![];
+    for file in &manifest.files {
+        let key = if prefix.is_empty() {
+            file.path.clone()
+        } else {
+            format!("{}/{}", prefix, file.path)
+        };
+
+        let problem = match store.get(&key).await {
+            Err(StorageError::NotFound(_)) => Some(Problem::Missing),
+            Err(e) => Some(Problem::Unreadable {
+                message: e.to_string(),
+            }),
+            Ok(mut body) => {
+                let mut hasher = Sha256::new();
+                let mut size = 0;
+                let mut error = None;
+                while let Some(chunk) = body.next().await {
+                    match chunk {
+                        Ok(chunk) => {
+                            hasher.update(&chunk);
+                            size += chunk.len() as u64;
+                        }
+                        Err(e) => {
+                            error = Some(e);
+                            break;
+                        }
+                    }
+                }
+                let actual = hex::encode(hasher.finalize());
+                match error {
+                    Some(e) => Some(Problem::Unreadable {
+                        message: e.to_string(),
+                    }),
+                    None if size != file.size => Some(Problem::SizeMismatch {
+                        expected: file.size,
+                        actual: size,
+                    }),
+                    None if actual != file.sha256 => Some(Problem::ChecksumMismatch {
+                        expected: file.sha256.clone(),
+                        actual,
+                    }),
+                    None => None,
+                }
+            }
+        };
+
+        if let Some(problem) = problem {
+            problems.push(FileProblem {
+                path: file.path.clone(),
+                problem,
+            });
+        }
+    }
+    problems
+}
+
+fn validate_dataset(req: &CreateDatasetRequest) -> Result<(), Error> {
+    super::validate_text("name", &req.name)?;
+    super::validate_text("location", &req.location)?;
+    if !req.location.starts_with("file://") && !req.location.starts_with("s3://") {
+        return Err(Error::InvalidArgument(format!(
+            "unsupported location: {}",
+            req.location
+        )));
+    }
+    Ok(())
+}
+
+/// 校验清单格式，SHA-256 统一转换为小写
+pub fn validate_manifest(manifest: &mut Manifest) -> Result<(), Error> {
+    if manifest.files.is_empty() {
+        return Err(Error::InvalidArgument("manifest has no files".to_string()));
+    }
+
+    let mut paths = HashSet::new();
+    for file in &mut manifest.files {
+        let relative = !file.path.is_empty()
+            && std::path::Path::new(&file.path)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)));
+        if !relative {
+            return Err(Error::InvalidArgument(format!(
+                "invalid file path: {:?}",
+                file.path
+            )));
+        }
+        if !paths.insert(file.path.clone()) {
+            return Err(Error::InvalidArgument(format!(
+                "duplicate file path: {:?}",
+                file.path
+            )));
+        }
+        if file.sha256.len() != 64 || !file.sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Error::InvalidArgument(format!(
+                "invalid sha256 of {:?}",
+                file.path
+            )));
+        }
+        file.sha256.make_ascii_lowercase();
+    }
+    Ok(())
+}
+
+fn summarize(manifest: &Manifest) -> ManifestSummary {
+    let mut summary = ManifestSummary::default();
+    for file in &manifest.files {
+        summary.files += 1;
+        summary.size += file.size;
+        summary.records += file.records;
+        if let Some(split) = &file.split {
+            *summary.splits.entry(split.clone()).or_default() += file.records;
+        }
+    }
+    summary
+}
+
+fn dataset_info(row: MySqlRow) -> Result<DatasetInfo, Error> {
+    let id: i64 = row.get("id");
+    let manifest = parse_manifest(row.get("manifest"))?;
+    let problems: Option<String> = row.get("verification_problems");
+    let verified_at: Option<chrono::NaiveDateTime> = row.get("verified_at");
+    let status: String = row.get("verification_status");
+
+    Ok(DatasetInfo {
+        id: id.to_string(),
+        name: row.get("name"),
+        location: row.get("location"),
+        manifest: manifest.as_ref().map(summarize),
+        verification: Verification {
+            status: parse_status(&status),
+            verified_at: verified_at.map(|t| t.to_string()),
+            problems: problems
+                .and_then(|problems| serde_json::from_str(&problems).ok())
+                .unwrap_or_default(),
+        },
+    })
+}
+
+fn parse_manifest(manifest: Option<String>) -> Result<Option<Manifest>, Error> {
+    manifest
+        .map(|manifest| serde_json::from_str(&manifest))
+        .transpose()
+        .map_err(|e| Error::InvalidArgument(format!("invalid manifest: {}", e)))
+}
+
+fn status_str(status: VerificationStatus) -> &'static str {
+    match status {
+        VerificationStatus::Unverified => "unverified",
+        VerificationStatus::Pending => "pending",
+        VerificationStatus::Verifying => "verifying",
+        VerificationStatus::Verified => "verified",
+        VerificationStatus::Failed => "failed",
+    }
+}
+
+fn parse_status(status: &str) -> VerificationStatus {
+    match status {
+        "pending" => VerificationStatus::Pending,
+        "verifying" => VerificationStatus::Verifying,
+        "verified" => VerificationStatus::Verified,
+        "failed" => VerificationStatus::Failed,
+        _ => VerificationStatus::Unverified,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dataset::ManifestFile, storage::LocalStore};
+
+    use super::*;
+
+    fn file(path: &str, content: &[u8]) -> ManifestFile {
+        ManifestFile {
+            path: path.to_string(),
+            size: content.len() as u64,
+            sha256: hex::encode(Sha256::digest(content)),
+            records: 1,
+            split: Some("train".to_string()),
+        }
+    }
+
+    #[test]
+    fn validate() {
+        let mut manifest = Manifest {
+            files: vec![file("a/0.txt", b"0")],
+        };
+        manifest.files[0].sha256.make_ascii_uppercase();
+        validate_manifest(&mut manifest).unwrap();
+        assert_eq!(manifest.files[0].sha256, hex::encode(Sha256::digest(b"0")));
+
+        for path in ["", "/etc/passwd", "a/../../b", "./a"] {
+            let mut manifest = Manifest {
+                files: vec![file(path, b"0")],
+            };
+            assert!(validate_manifest(&mut manifest).is_err(), "{:?}", path);
+        }
+
+        let mut manifest = Manifest {
+            files: vec![file("a", b"0"), file("a", b"1")],
+        };
+        assert!(validate_manifest(&mut manifest).is_err());
+    }
+
+    #[tokio::test]
+    async fn verify_local_files() {
+        let root = std::env::temp_dir().join(format!("dataset-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("data")).unwrap();
+        std::fs::write(root.join("data/ok.txt"), b"ok").unwrap();
+        std::fs::write(root.join("data/short.txt"), b"s").unwrap();
+        std::fs::write(root.join("data/corrupted.txt"), b"bad").unwrap();
+
+        let mut corrupted = file("corrupted.txt", b"bad");
+        corrupted.sha256 = hex::encode(Sha256::digest(b"dab"));
+        let manifest = Manifest {
+            files: vec![
+                file("ok.txt", b"ok"),
+                file("short.txt", b"short"),
+                corrupted,
+                file("missing.txt", b"missing"),
+            ],
+        };
+
+        let store = LocalStore::new(&root);
+        let problems = verify_manifest(&store, "data", &manifest).await;
+        let problems: Vec<_> = problems
+            .iter()
+            .map(|p| (p.path.as_str(), &p.problem))
+            .collect();
+        assert_eq!(problems.len(), 3);
+        assert!(matches!(
+            problems[0],
+            (
+                "short.txt",
+                Problem::SizeMismatch {
+                    expected: 5,
+                    actual: 1
+                }
+            )
+        ));
+        assert!(matches!(
+            problems[1],
+            ("corrupted.txt", Problem::ChecksumMismatch { .. })
+        ));
+        assert!(matches!(problems[2], ("missing.txt", Problem::Missing)));
+
+        assert_eq!(summarize(&manifest).splits["train"], 4);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
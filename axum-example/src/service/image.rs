@@ -0,0 +1,87 @@
+use sqlx::{mysql::MySqlRow, Row};
+
+use crate::{db::Database, error::Error};
+
+#[derive(Clone, Debug)]
+pub struct ImageInfo {
+    pub id: i64,
+    pub name: String,
+    pub image: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct ImageRequest {
+    pub name: String,
+    /// 镜像地址，例如 `registry.example.com/algorithm/torch:1.10`
+    pub image: String,
+}
+
+#[derive(Clone)]
+pub struct ImageService {
+    db: Database,
+}
+
+impl ImageService {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(&self, req: ImageRequest) -> Result<i64, Error> {
+        validate_image(&req)?;
+
+        let id = super::next_id();
+        sqlx::query("INSERT INTO `image` (`id`, `name`, `image`) VALUES (?, ?, ?)")
+            .bind(id)
+            .bind(req.name)
+            .bind(req.image)
+            .execute(self.db.writer())
+            .await?;
+        Ok(id)
+    }
+
+    pub async fn get(&self, id: i64) -> Result<ImageInfo, Error> {
+        sqlx::query("SELECT `id`, `name`, `image` FROM `image` WHERE `id` = ?")
+            .bind(id)
+            .fetch_optional(self.db.reader())
+            .await?
+            .map(image_info)
+            .ok_or(Error::NotFound("image"))
+    }
+
+    pub async fn list(&self) -> Result<Vec<ImageInfo>, Error> {
+        Ok(
+            sqlx::query("SELECT `id`, `name`, `image` FROM `image` ORDER BY `id`")
+                .fetch_all(self.db.reader())
+                .await?
+                .into_iter()
+                .map(image_info)
+                .collect(),
+        )
+    }
+
+    pub async fn update(&self, id: i64, req: ImageRequest) -> Result<ImageInfo, Error> {
+        validate_image(&req)?;
+
+        sqlx::query("UPDATE `image` SET `name` = ?, `image` = ? WHERE `id` = ?")
+            .bind(req.name)
+            .bind(req.image)
+            .bind(id)
+            .execute(self.db.writer())
+            .await?;
+        // 内容未变化时影响的行数也为 0，通过重新读取判断记录是否存在
+        self.get(id).await
+    }
+}
+
+fn validate_image(req: &ImageRequest) -> Result<(), Error> {
+    super::validate_text("name", &req.name)?;
+    super::validate_text("image", &req.image)
+}
+
+fn image_info(row: MySqlRow) -> ImageInfo {
+    ImageInfo {
+        id: row.get("id"),
+        name: row.get("name"),
+        image: row.get("image"),
+    }
+}
@@ -0,0 +1,39 @@
+//! 业务逻辑，HTTP 与 gRPC 接口共用
+
+use std::cell::RefCell;
+
+mod algorithm;
+mod dataset;
+mod image;
+
+pub use algorithm::*;
+pub use dataset::*;
+pub use image::*;
+
+use crate::error::Error;
+
+std::thread_local! {
+    static SNOWFLAKE_GENERATOR: RefCell<snowflake::SnowflakeIdGenerator> =
+        RefCell::new(snowflake::SnowflakeIdGenerator::new(0, std::thread::current().id().as_u64().get() as i32));
+}
+
+fn next_id() -> i64 {
+    SNOWFLAKE_GENERATOR.with(|gen| gen.borrow_mut().generate())
+}
+
+/// 名称、位置等文本字段不能为空，且不能超过数据库列的长度
+fn validate_text(field: &str, value: &str) -> Result<(), Error> {
+    if value.trim().is_empty() {
+        return Err(Error::InvalidArgument(format!(
+            "{} must not be empty",
+            field
+        )));
+    }
+    if value.chars().count() > 255 {
+        return Err(Error::InvalidArgument(format!(
+            "{} must be at most 255 characters",
+            field
+        )));
+    }
+    Ok(())
+}
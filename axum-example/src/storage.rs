@@ -0,0 +1,374 @@
+//! 算法包的存储后端，支持本地文件系统与 S3 兼容的对象存储（例如 COS）
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::body::Bytes;
+use futures::{ready, stream::BoxStream, Stream, StreamExt, TryStreamExt};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+
+pub type ByteStream = BoxStream<'static, io::Result<Bytes>>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum StorageError {
+    #[error("object {0} not found")]
+    NotFound(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("object storage: {0}")]
+    Backend(String),
+    /// 上传的内容超过大小上限
+    #[error("larger than {0} bytes")]
+    TooLarge(u64),
+}
+
+#[async_trait::async_trait]
+pub trait BlobStore: Send + Sync {
+    /// 上传本地文件，返回对象的位置，例如 `file:///data/packages/...` 或 `s3://bucket/...`
+    async fn put(&self, key: &str, file: &Path) -> Result<String, StorageError>;
+
+    async fn get(&self, key: &str) -> Result<ByteStream, StorageError>;
+}
+
+/// 根据 `ALGORITHM_STORE` 选择存储算法包的后端，未设置时默认为 `file://data/packages`
+pub fn from_env() -> Arc<dyn BlobStore> {
+    let store = std::env::var("ALGORITHM_STORE").unwrap_or_else(|_| "file://data/packages".into());
+    match open(&store) {
+        Ok((store, _)) => store,
+        Err(e) => panic!("unsupported ALGORITHM_STORE: {}", e),
+    }
+}
+
+/// 按位置打开存储后端，返回后端与位置中的键前缀:
+///
+/// * `file:///data/packages`: 本地文件系统
+/// * `s3://bucket/prefix`: S3 兼容的对象存储，通过 `S3_ENDPOINT`、`S3_REGION`、
+///   `ACCESS_KEY_ID` 与 `SECRET_ACCESS_KEY` 配置
+pub fn open(location: &str) -> Result<(Arc<dyn BlobStore>, String), StorageError> {
+    match location.split_once("://") {
+        Some(("file", root)) => Ok((Arc::new(LocalStore::new(root)), String::new())),
+        Some(("s3", path)) => {
+            let (bucket, prefix) = path.split_once('/').unwrap_or((path, ""));
+            Ok((
+                Arc::new(S3Store::from_env(bucket)?),
+                prefix.trim_end_matches('/').to_string(),
+            ))
+        }
+        _ => Err(StorageError::Backend(format!(
+            "unsupported location: {}",
+            location
+        ))),
+    }
+}
+
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+#[async_trait::async_trait]
+impl BlobStore for LocalStore {
+    async fn put(&self, key: &str, file: &Path) -> Result<String, StorageError> {
+        let path = self.root.join(key);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // 先写入临时文件再重命名，并发写入同一个键时读取方不会看到写了一半的文件
+        let tmp = path.with_extension(format!(
+            "tmp-{}-{}",
+            std::process::id(),
+            chrono::Local::now().timestamp_nanos()
+        ));
+        tokio::fs::copy(file, &tmp).await?;
+        if let Err(e) = tokio::fs::rename(&tmp, &path).await {
+            let _ = tokio::fs::remove_file(&tmp).await;
+            return Err(e.into());
+        }
+        Ok(format!("file://{}", path.display()))
+    }
+
+    async fn get(&self, key: &str) -> Result<ByteStream, StorageError> {
+        let file = match tokio::fs::File::open(self.root.join(key)).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(StorageError::NotFound(key.to_string()))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(tokio_util::io::ReaderStream::new(file).boxed())
+    }
+}
+
+pub struct S3Store {
+    client: aws_sdk_s3::Client,
+    bucket: String,
+}
+
+impl S3Store {
+    /// 缺少配置时返回错误，数据集的位置由用户指定，不能因此使校验任务崩溃
+    pub fn from_env(bucket: &str) -> Result<Self, StorageError> {
+        let var = |name: &str| {
+            std::env::var(name).map_err(|_| StorageError::Backend(format!("{} not set", name)))
+        };
+        let region = var("S3_REGION")?;
+        let endpoint = var("S3_ENDPOINT")?;
+        let access_key_id = var("ACCESS_KEY_ID")?;
+        let secret_access_key = var("SECRET_ACCESS_KEY")?;
+
+        let endpoint = endpoint
+            .parse()
+            .map_err(|_| StorageError::Backend(format!("invalid S3_ENDPOINT: {}", endpoint)))?;
+        let config = aws_sdk_s3::Config::builder()
+            .region(aws_sdk_s3::Region::new(region))
+            .endpoint_resolver(aws_sdk_s3::Endpoint::immutable(endpoint))
+            .credentials_provider(aws_sdk_s3::Credentials::new(
+                &access_key_id,
+                &secret_access_key,
+                None,
+                None,
+                "algorithm-store",
+            ))
+            .build();
+
+        Ok(Self {
+            client: aws_sdk_s3::Client::from_conf(config),
+            bucket: bucket.to_string(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl BlobStore for S3Store {
+    async fn put(&self, key: &str, file: &Path) -> Result<String, StorageError> {
+        let body = aws_sdk_s3::ByteStream::from_path(file)
+            .await
+            .map_err(|e| StorageError::Backend(e.to_string()))?;
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| StorageError::Backend(e.to_string()))?;
+        Ok(format!("s3://{}/{}", self.bucket, key))
+    }
+
+    async fn get(&self, key: &str) -> Result<ByteStream, StorageError> {
+        let output = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| match e {
+                aws_sdk_s3::SdkError::ServiceError { err, .. } if err.is_no_such_key() => {
+                    StorageError::NotFound(key.to_string())
+                }
+                e => StorageError::Backend(e.to_string()),
+            })?;
+        Ok(output
+            .body
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            .boxed())
+    }
+}
+
+/// 上传前先写入临时文件，同时计算 SHA-256 与大小
+pub struct Spooled {
+    pub path: PathBuf,
+    pub checksum: String,
+    pub size: u64,
+}
+
+impl Spooled {
+    /// 内容超过 `limit` 字节时停止读取并返回 `StorageError::TooLarge`
+    pub async fn from_stream<S, E>(mut stream: S, limit: u64) -> Result<Self, StorageError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let path = std::env::temp_dir().join(format!(
+            "algorithm-package-{}-{}",
+            std::process::id(),
+            chrono::Local::now().timestamp_nanos()
+        ));
+        let mut file = tokio::fs::File::create(&path).await?;
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+
+        let result: Result<(), StorageError> = async {
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk.map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+                size += chunk.len() as u64;
+                if size > limit {
+                    return Err(StorageError::TooLarge(limit));
+                }
+                hasher.update(&chunk);
+                file.write_all(&chunk).await?;
+            }
+            Ok(file.flush().await?)
+        }
+        .await;
+
+        let spooled = Self {
+            path,
+            checksum: hex::encode(hasher.finalize()),
+            size,
+        };
+        result.map(|_| spooled)
+    }
+}
+
+impl Drop for Spooled {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// 边读取边校验 SHA-256，最后一块数据在校验通过后才返回。不一致时返回错误而不是最后一块数据，
+/// 使客户端收到的字节数少于 `Content-Length`，下载中断而不是得到完整但损坏的内容
+pub fn verify(stream: ByteStream, checksum: String) -> ByteStream {
+    Verify {
+        inner: stream,
+        hasher: Some(Sha256::new()),
+        pending: None,
+        checksum,
+    }
+    .boxed()
+}
+
+struct Verify {
+    inner: ByteStream,
+    /// 读取结束后为 `None`
+    hasher: Option<Sha256>,
+    /// 已读取但尚未返回的一块数据
+    pending: Option<Bytes>,
+    checksum: String,
+}
+
+impl Stream for Verify {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            let hasher = match this.hasher.as_mut() {
+                Some(hasher) => hasher,
+                None => return Poll::Ready(this.pending.take().map(Ok)),
+            };
+            match ready!(this.inner.poll_next_unpin(cx)) {
+                Some(Ok(chunk)) => {
+                    hasher.update(&chunk);
+                    if let Some(previous) = this.pending.replace(chunk) {
+                        return Poll::Ready(Some(Ok(previous)));
+                    }
+                }
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => {
+                    let actual = hex::encode(this.hasher.take().unwrap().finalize());
+                    if actual == this.checksum {
+                        return Poll::Ready(this.pending.take().map(Ok));
+                    }
+                    println!(
+                        "checksum mismatch. expected: {}, actual: {}",
+                        this.checksum, actual
+                    );
+                    this.pending = None;
+                    return Poll::Ready(Some(Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "checksum mismatch",
+                    ))));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunks(data: &[&'static [u8]]) -> ByteStream {
+        futures::stream::iter(
+            data.iter()
+                .map(|chunk| Ok(Bytes::from_static(chunk)))
+                .collect::<Vec<_>>(),
+        )
+        .boxed()
+    }
+
+    #[tokio::test]
+    async fn spool_and_verify() {
+        let spooled = Spooled::from_stream(chunks(&[b"hello ", b"world"]), 11)
+            .await
+            .unwrap();
+        assert_eq!(spooled.size, 11);
+        assert!(matches!(
+            Spooled::from_stream(chunks(&[b"hello ", b"world"]), 10).await,
+            Err(StorageError::TooLarge(10))
+        ));
+        assert_eq!(
+            spooled.checksum,
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+
+        let root = std::env::temp_dir().join(format!("algorithm-store-{}", std::process::id()));
+        let store = LocalStore::new(&root);
+        let location = store
+            .put("algorithms/1/package", &spooled.path)
+            .await
+            .unwrap();
+        assert!(location.starts_with("file://"));
+
+        let body: Vec<Bytes> = verify(
+            store.get("algorithms/1/package").await.unwrap(),
+            spooled.checksum.clone(),
+        )
+        .try_collect()
+        .await
+        .unwrap();
+        assert_eq!(body.concat(), b"hello world");
+
+        // 最后一块数据被扣留，出错前只返回校验通过前的数据
+        let mut corrupted = verify(chunks(&[b"hello ", b"WORLD"]), spooled.checksum.clone());
+        assert_eq!(&corrupted.next().await.unwrap().unwrap()[..], b"hello ");
+        let e = corrupted.next().await.unwrap().unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(corrupted.next().await.is_none());
+
+        assert!(matches!(
+            store.get("algorithms/2/package").await,
+            Err(StorageError::NotFound(_))
+        ));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn open_locations() {
+        let (_, prefix) = open("file:///tmp/datasets").unwrap();
+        assert_eq!(prefix, "");
+        assert!(matches!(
+            open("ftp://host/datasets"),
+            Err(StorageError::Backend(_))
+        ));
+        if std::env::var("S3_REGION").is_err() {
+            assert!(matches!(
+                open("s3://bucket/datasets/"),
+                Err(StorageError::Backend(_))
+            ));
+        }
+    }
+}
@@ -2,7 +2,21 @@ use std::time::Duration;
 
 use crawler::Crawler;
 use reqwest::blocking::{Client, ClientBuilder};
-use select::predicate::Name;
+use select::{
+    node::Node,
+    predicate::{Class, Name},
+};
+use serde::Serialize;
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    iterator::Signals,
+};
+
+const USER_AGENT: &str = "gushiwen/0.1";
+
+const CHECKPOINT_DIR: &str = "gushiwen.checkpoint";
+
+const POEMS_FILE: &str = "gushiwen.jsonl";
 
 #[derive(Clone)]
 struct Fetcher {
@@ -16,7 +30,7 @@ struct FetcherBuilder {
 impl Fetcher {
     fn builder() -> FetcherBuilder {
         FetcherBuilder {
-            builder: Client::builder(),
+            builder: Client::builder().user_agent(USER_AGENT),
         }
     }
 }
@@ -41,11 +55,115 @@ impl FetcherBuilder {
 }
 
 impl crawler::Fetcher for Fetcher {
-    fn fetch<U: AsRef<str>>(&self, u: U) -> crawler::Result<String, crawler::FetchError> {
-        Ok(self.client.get(u.as_ref()).send()?.text()?)
+    fn fetch<U: AsRef<str>>(
+        &self,
+        u: U,
+    ) -> crawler::Result<crawler::Response, crawler::FetchError> {
+        let response = self.client.get(u.as_ref()).send()?;
+        if let Some(e) = crawler::FetchError::from_status(response.status(), response.headers()) {
+            return Err(e);
+        }
+        let url = response.url().to_string();
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let body = if crawler::Response::is_gzip(&url, &headers) {
+            crawler::Response::gunzip(&response.bytes()?)?
+        } else {
+            response.text()?
+        };
+        Ok(crawler::Response {
+            url: Some(url),
+            status,
+            headers,
+            body,
+        })
     }
 }
 
+#[derive(Serialize)]
+struct Poem {
+    /// From the id of the poem text, the same on every page showing the poem.
+    id: String,
+    url: String,
+    title: String,
+    dynasty: String,
+    author: String,
+    text: String,
+}
+
+impl Poem {
+    /// A poem is a `div.sons` block:
+    ///
+    /// ```html
+    /// <div class="sons"><div class="cont">
+    ///   <p><a><b>静夜思</b></a></p>
+    ///   <p class="source"><a>李白</a><a>〔唐代〕</a></p>
+    ///   <div class="contson" id="contson8a3e2c2a1a5f">床前明月光，疑是地上霜。<br>...</div>
+    /// </div></div>
+    /// ```
+    fn parse(url: &str, sons: Node) -> Option<Self> {
+        let contson = sons.find(Class("contson")).next()?;
+        let title = sons
+            .find(Name("h1"))
+            .chain(sons.find(Name("b")))
+            .map(|title| title.text().trim().to_string())
+            .find(|title| !title.is_empty())?;
+        let mut dynasty = String::new();
+        let mut author = String::new();
+        for a in sons.find(Class("source")).next()?.find(Name("a")) {
+            let text = a.text();
+            let text = text.trim();
+            if text.starts_with('〔') {
+                dynasty = text.trim_matches(|c| c == '〔' || c == '〕').to_string();
+            } else if !text.is_empty() {
+                author = text.to_string();
+            }
+        }
+        let text = poem_text(contson);
+        if text.is_empty() {
+            return None;
+        }
+        let id = contson
+            .attr("id")
+            .and_then(|id| id.strip_prefix("contson"))
+            .map_or_else(|| format!("{}#{}", url, title), str::to_string);
+        Some(Poem {
+            id,
+            url: url.to_string(),
+            title,
+            dynasty,
+            author,
+            text,
+        })
+    }
+}
+
+/// One line per `<br>` or paragraph, trimmed.
+fn poem_text(contson: Node) -> String {
+    fn walk(node: Node, text: &mut String) {
+        for child in node.children() {
+            match child.name() {
+                None => text.push_str(child.as_text().unwrap_or_default()),
+                Some("br") => text.push('\n'),
+                Some(name) => {
+                    walk(child, text);
+                    if name == "p" || name == "div" {
+                        text.push('\n');
+                    }
+                }
+            }
+        }
+    }
+    let mut text = String::new();
+    walk(contson, &mut text);
+    let lines: Vec<_> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    lines.join("\n")
+}
+
 #[derive(Clone)]
 struct Extractor;
 
@@ -54,23 +172,22 @@ impl crawler::Extractor for Extractor {
         &self,
         u: U,
         document: &str,
-    ) -> crawler::Result<Option<Vec<String>>, crawler::ExtractError> {
-        // println!("document: {}", document);
+    ) -> crawler::Result<crawler::Extraction, crawler::ExtractError> {
         let document: select::document::Document = document.into();
-        let u = url::url::URL::parse(u.as_ref()).unwrap();
-        let mut urls = vec![];
+        let base = url::url::URL::parse(u.as_ref()).unwrap();
+        let mut extraction = crawler::Extraction::new();
         for e in document.find(Name("a")) {
             if let Some(href) = e.attr("href") {
-                let u = u.parse_reference(href).unwrap();
-                urls.push(u.to_string());
-                // println!("{} -> {}", href, u);
+                let u = base.parse_reference(href).unwrap();
+                extraction.links.push(u.to_string());
             }
         }
-        if urls.is_empty() {
-            Ok(None)
-        } else {
-            Ok(Some(urls))
+        for sons in document.find(Class("sons")) {
+            if let Some(poem) = Poem::parse(u.as_ref(), sons) {
+                extraction.push_item(&poem)?;
+            }
         }
+        Ok(extraction)
     }
 }
 
@@ -82,13 +199,56 @@ fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
 
     let extractor = Extractor;
 
-    let crawler = crawler::MultiThreadsCrawler::new(10);
+    let checkpoint = crawler::Checkpoint::new(CHECKPOINT_DIR);
+    // appended to and deduplicated across resumed runs
+    let poems = crawler::Sink::jsonl(POEMS_FILE)?.dedupe_by("id")?;
+    let crawler = crawler::MultiThreadsCrawler::new(10)
+        .with_pipeline(crawler::Pipeline::new().then(poems))
+        .with_options(crawler::CrawlOptions {
+            scope: crawler::Scope::http().and(crawler::Scope::SameDomain).and(
+                crawler::Scope::deny([r"\.(jpg|jpeg|png|gif|mp3|zip|rar)$"])?,
+            ),
+            max_depth: Some(3),
+            max_pages: Some(10_000),
+            max_duration: Some(Duration::from_secs(3600)),
+            max_bytes: Some(1 << 30),
+            max_per_host: Some(2),
+            host_delay: Some(Duration::from_millis(200)),
+            user_agent: Some(USER_AGENT.to_string()),
+            checkpoint: Some(checkpoint.clone()),
+            discovery: crawler::Discovery::all(),
+            ..Default::default()
+        });
 
-    let urls = crawler.crawl("https://gushiwen.com/", fetcher, extractor)?;
+    let pages = if checkpoint.exists() {
+        crawler.crawl_resume(checkpoint, fetcher, extractor)?
+    } else {
+        crawler.crawl("https://gushiwen.com/", fetcher, extractor)?
+    };
 
-    while let Ok(url) = urls.recv() {
-        println!("{:?}", url);
+    // Ctrl-C saves a checkpoint, the next run resumes from it
+    let shutdown = pages.shutdown_handle();
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    std::thread::spawn(move || {
+        for _ in signals.forever() {
+            shutdown.shutdown();
+        }
+    });
+
+    while let Ok(page) = pages.recv() {
+        match page {
+            Ok(page) => println!(
+                "[{}] {} ({} bytes, {} poems in {:?})",
+                page.status,
+                page.final_url,
+                page.body.len(),
+                page.items.len(),
+                page.elapsed
+            ),
+            Err(e) => println!("{}", e),
+        }
     }
+    pages.summary();
 
     Ok(())
 }
@@ -0,0 +1,597 @@
+use std::{
+    collections::VecDeque,
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Instant, SystemTime},
+};
+
+use futures::{
+    stream::{self, BoxStream, FuturesUnordered, StreamExt},
+    FutureExt, Stream,
+};
+use tokio::task::JoinHandle;
+
+use crate::{
+    frontier::Frontier, page::Visit, robots::AsyncRobotsCache, Checkpoint, CrawlOptions, Discovery,
+    Error, ExtractError, Extraction, FetchError, Page, Pipeline, Request, Response, Result,
+    RetryPolicy, Shutdown, StopReason, Summary,
+};
+
+#[async_trait::async_trait]
+pub trait AsyncFetcher: Send + Sync {
+    async fn fetch(&self, u: &str) -> Result<Response, FetchError>;
+}
+
+#[async_trait::async_trait]
+pub trait AsyncExtractor: Send + Sync {
+    /// `u` is where the document was fetched from, after redirects.
+    async fn extract(&self, u: &str, document: &str) -> Result<Extraction, ExtractError>;
+}
+
+#[async_trait::async_trait]
+impl<T: AsyncFetcher + ?Sized> AsyncFetcher for Arc<T> {
+    async fn fetch(&self, u: &str) -> Result<Response, FetchError> {
+        (**self).fetch(u).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: AsyncExtractor + ?Sized> AsyncExtractor for Arc<T> {
+    async fn extract(&self, u: &str, document: &str) -> Result<Extraction, ExtractError> {
+        (**self).extract(u, document).await
+    }
+}
+
+#[async_trait::async_trait]
+impl AsyncFetcher for reqwest::Client {
+    async fn fetch(&self, u: &str) -> Result<Response, FetchError> {
+        let response = self.get(u).send().await?;
+        if let Some(e) = FetchError::from_status(response.status(), response.headers()) {
+            return Err(e);
+        }
+        let url = response.url().to_string();
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let body = if Response::is_gzip(&url, &headers) {
+            Response::gunzip(&response.bytes().await?)?
+        } else {
+            response.text().await?
+        };
+        Ok(Response {
+            url: Some(url),
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+/// Crawls on the Tokio runtime, fetching at most `concurrency` pages at a time.
+///
+/// Like `MultiThreadsCrawler`, the returned stream yields every crawled page once it went
+/// through the pipeline, plus fetch, extract and pipeline errors, and ends when there is
+/// nothing left to crawl or a limit of `CrawlOptions` is hit.
+pub struct AsyncCrawler {
+    concurrency: usize,
+    options: CrawlOptions,
+    pipeline: Pipeline,
+}
+
+/// The stream of a running crawl, its summary is available once the stream has ended.
+pub struct AsyncCrawl {
+    pages: BoxStream<'static, Result<Page>>,
+    summary: Arc<Mutex<Option<Summary>>>,
+    shutdown: Shutdown,
+}
+
+impl AsyncCrawl {
+    pub fn shutdown_handle(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    pub fn summary(&self) -> Option<Summary> {
+        self.summary.lock().unwrap().clone()
+    }
+}
+
+impl Stream for AsyncCrawl {
+    type Item = Result<Page>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.pages.poll_next_unpin(cx)
+    }
+}
+
+type Visited = (Request, Result<Visit>);
+
+struct State<F, E> {
+    fetcher: Arc<F>,
+    extractor: Arc<E>,
+    robots: Option<Arc<AsyncRobotsCache>>,
+    retry: RetryPolicy,
+    discovery: Discovery,
+    pipeline: Arc<Pipeline>,
+    concurrency: usize,
+    frontier: Frontier,
+    inflight: FuturesUnordered<JoinHandle<Visited>>,
+    ready: VecDeque<Result<Page>>,
+    summary: Arc<Mutex<Option<Summary>>>,
+    shutdown: Shutdown,
+    /// Set by `finish`, after which the stream ends.
+    finished: bool,
+}
+
+impl AsyncCrawler {
+    pub fn new(concurrency: usize) -> Self {
+        Self {
+            concurrency: concurrency.max(1),
+            options: CrawlOptions::default(),
+            pipeline: Pipeline::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: CrawlOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Processors run on the tasks that fetch the pages, so they should not block for long.
+    pub fn with_pipeline(mut self, pipeline: Pipeline) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    /// Must be polled within a Tokio runtime, each page is fetched and extracted on its own task.
+    pub fn crawl<U, F, E>(&self, entrance: U, fetcher: F, extractor: E) -> Result<AsyncCrawl>
+    where
+        U: Into<String>,
+        F: AsyncFetcher + 'static,
+        E: AsyncExtractor + 'static,
+    {
+        let frontier = Frontier::new(self.options.clone(), entrance.into())?;
+        Ok(self.run(frontier, fetcher, extractor))
+    }
+
+    /// Continues the crawl saved in `checkpoint.dir`, crawling the pages that were in flight
+    /// again, and keeps saving it there.
+    pub fn crawl_resume<F, E>(
+        &self,
+        checkpoint: Checkpoint,
+        fetcher: F,
+        extractor: E,
+    ) -> Result<AsyncCrawl>
+    where
+        F: AsyncFetcher + 'static,
+        E: AsyncExtractor + 'static,
+    {
+        let dir = checkpoint.dir.clone();
+        let options = CrawlOptions {
+            checkpoint: Some(checkpoint),
+            ..self.options.clone()
+        };
+        let frontier = Frontier::resume(options, &dir)?;
+        Ok(self.run(frontier, fetcher, extractor))
+    }
+
+    fn run<F, E>(&self, mut frontier: Frontier, fetcher: F, extractor: E) -> AsyncCrawl
+    where
+        F: AsyncFetcher + 'static,
+        E: AsyncExtractor + 'static,
+    {
+        let summary = Arc::new(Mutex::new(None));
+        let shutdown = Shutdown::default();
+        let robots = (!self.options.ignore_robots).then(|| {
+            Arc::new(AsyncRobotsCache::new(
+                self.options.user_agent.as_deref(),
+                self.options.retry.clone(),
+            ))
+        });
+        if let Some(robots) = robots.clone() {
+            frontier = frontier.with_crawl_delay(move |url| robots.crawl_delay(url));
+        }
+        let state = State {
+            fetcher: Arc::new(fetcher),
+            extractor: Arc::new(extractor),
+            robots,
+            retry: self.options.retry.clone(),
+            discovery: self.options.discovery,
+            pipeline: Arc::new(self.pipeline.clone()),
+            concurrency: self.concurrency,
+            frontier,
+            inflight: FuturesUnordered::new(),
+            ready: VecDeque::new(),
+            summary: summary.clone(),
+            shutdown: shutdown.clone(),
+            finished: false,
+        };
+        let pages = stream::unfold(state, |mut state| async move {
+            if state.finished {
+                return None;
+            }
+            loop {
+                if let Some(result) = state.ready.pop_front() {
+                    return Some((result, state));
+                }
+                if state.shutdown.is_requested() {
+                    state.frontier.stop(StopReason::Shutdown);
+                    state.finish();
+                    return None;
+                }
+                if let Some(robots) = &state.robots {
+                    state.frontier.seed_sitemaps(robots.take_sitemaps());
+                }
+                state.spawn_pending();
+                if state.frontier.is_done() {
+                    state.finish();
+                    return None;
+                }
+                let wake_at = state.frontier.wake_at();
+                let shutdown = state.shutdown.clone();
+                let visited = tokio::select! {
+                    visited = state.inflight.next(), if !state.inflight.is_empty() => visited,
+                    // a deadline, a host done waiting for its delay or a checkpoint
+                    _ = async {
+                        match wake_at {
+                            Some(wake_at) => tokio::time::sleep_until(wake_at.into()).await,
+                            None => futures::future::pending().await,
+                        }
+                    } => None,
+                    _ = shutdown.requested() => None,
+                };
+                state.frontier.checkpoint_if_due();
+                if state.frontier.is_expired() {
+                    state.frontier.stop(StopReason::MaxDuration);
+                    state.finish();
+                    return None;
+                }
+                match visited {
+                    Some(Ok(visited)) => state.forward(visited),
+                    // tasks catch their own panics, so this only happens if the runtime is
+                    // shutting down
+                    Some(Err(e)) => {
+                        state.finish();
+                        let e = Error::Other(anyhow::anyhow!("crawl task failed. {}", e));
+                        return Some((Err(e), state));
+                    }
+                    None => {}
+                }
+            }
+        })
+        .boxed();
+        AsyncCrawl {
+            pages,
+            summary,
+            shutdown,
+        }
+    }
+}
+
+impl<F, E> State<F, E>
+where
+    F: AsyncFetcher + 'static,
+    E: AsyncExtractor + 'static,
+{
+    fn spawn_pending(&mut self) {
+        while self.inflight.len() < self.concurrency {
+            let request = match self.frontier.next() {
+                Some(request) => request,
+                None => return,
+            };
+            let fetcher = self.fetcher.clone();
+            let extractor = self.extractor.clone();
+            let robots = self.robots.clone();
+            let retry = self.retry.clone();
+            let pipeline = self.pipeline.clone();
+            let discovery = self.discovery;
+            self.inflight.push(tokio::spawn(async move {
+                let visited = AssertUnwindSafe(async {
+                    if let Some(robots) = robots {
+                        if !robots.allows(&request.url, &*fetcher).await {
+                            return Err(Error::Fetch(FetchError::Disallowed));
+                        }
+                    }
+                    let fetched_at = SystemTime::now();
+                    let started = Instant::now();
+                    let response = retry.fetch_async(&*fetcher, &request.url).await?;
+                    let elapsed = started.elapsed();
+                    // relative links are relative to where the redirects ended
+                    let base = response.url.as_deref().unwrap_or(&request.url);
+                    let extraction = match discovery.links(base, &response.body) {
+                        Some(links) => Extraction::links(links),
+                        None => {
+                            let mut extraction = extractor.extract(base, &response.body).await?;
+                            extraction
+                                .links
+                                .extend(discovery.feeds(base, &response.body));
+                            extraction
+                        }
+                    };
+                    let bytes = response.body.len();
+                    let page = Page::new(
+                        request.clone(),
+                        response,
+                        extraction.items,
+                        fetched_at,
+                        elapsed,
+                    );
+                    Ok(Visit {
+                        bytes,
+                        links: extraction.links,
+                        page: pipeline.process(page),
+                    })
+                })
+                .catch_unwind()
+                .await
+                .unwrap_or_else(|_| {
+                    Err(Error::Other(anyhow::anyhow!(
+                        "panicked while crawling {}",
+                        request.url
+                    )))
+                });
+                (request, visited)
+            }));
+        }
+    }
+
+    fn forward(&mut self, (request, visited): Visited) {
+        match visited {
+            Ok(visit) => {
+                self.frontier.complete(&request, visit.bytes, visit.links);
+                match visit.page {
+                    Ok(Some(page)) => self.ready.push_back(Ok(page)),
+                    Ok(None) => {}
+                    Err(e) => {
+                        self.frontier.process_failed();
+                        self.ready.push_back(Err(e));
+                    }
+                }
+            }
+            Err(e) => {
+                self.frontier.fail(&request, &e);
+                self.ready.push_back(Err(e));
+            }
+        }
+    }
+
+    /// Pages still in flight are abandoned, the checkpoint crawls them again on resume.
+    fn finish(&mut self) {
+        for task in self.inflight.iter() {
+            task.abort();
+        }
+        self.frontier.finish();
+        let summary = self.frontier.summary();
+        println!("{}", summary);
+        *self.summary.lock().unwrap() = Some(summary);
+        self.finished = true;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashSet,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use futures::StreamExt;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Fetcher {
+        running: AtomicUsize,
+        max_running: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl AsyncFetcher for Fetcher {
+        async fn fetch(&self, u: &str) -> Result<Response, FetchError> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+
+            let page = |children: &[&str]| {
+                let mut urls = vec!["page0", "page1", "page2"];
+                urls.extend_from_slice(children);
+                Ok(serde_json::to_string(&urls).unwrap().into())
+            };
+            match u {
+                "home-page" => page(&[]),
+                "page0" => page(&["page0-0", "page0-1", "page0-2"]),
+                "page1" => page(&["page1-0", "page1-1", "page1-2"]),
+                "page2" => page(&["page2-0", "page2-1", "page2-2"]),
+                "broken" => Err(FetchError::NotFound),
+                _ => Ok("[]".into()),
+            }
+        }
+    }
+
+    struct Extractor;
+
+    #[async_trait::async_trait]
+    impl AsyncExtractor for Extractor {
+        async fn extract(&self, _u: &str, document: &str) -> Result<Extraction, ExtractError> {
+            let urls: Vec<String> = serde_json::from_str(document).unwrap();
+            Ok(Extraction::links(urls))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn crawl() {
+        let fetcher = Arc::new(Fetcher::default());
+        let results: Vec<_> = AsyncCrawler::new(2)
+            .crawl("home-page", fetcher.clone(), Extractor)
+            .unwrap()
+            .collect()
+            .await;
+
+        let pages: Vec<_> = results.into_iter().map(|r| r.unwrap()).collect();
+        let unique: HashSet<_> = pages.iter().map(|page| page.url.as_str()).collect();
+        assert_eq!(pages.len(), 13);
+        assert_eq!(unique.len(), 13);
+        assert!(unique.contains("home-page"));
+        let page = pages.iter().find(|page| page.url == "page1-2").unwrap();
+        assert_eq!((page.depth, page.referrer.as_deref()), (2, Some("page1")));
+        assert_eq!((page.status, page.final_url.as_str()), (200, "page1-2"));
+        assert!(page.elapsed >= Duration::from_millis(10));
+        assert_eq!(fetcher.max_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn limits() {
+        let options = CrawlOptions {
+            max_duration: Some(Duration::from_millis(25)),
+            ..Default::default()
+        };
+        let mut crawl = AsyncCrawler::new(1)
+            .with_options(options)
+            .crawl("home-page", Fetcher::default(), Extractor)
+            .unwrap();
+        assert!(crawl.summary().is_none());
+        while crawl.next().await.is_some() {}
+        let summary = crawl.summary().unwrap();
+        assert!(summary.pages < 13);
+        assert_eq!(summary.stopped, Some(StopReason::MaxDuration));
+
+        let options = CrawlOptions {
+            max_depth: Some(1),
+            max_bytes: Some(1 << 20),
+            ..Default::default()
+        };
+        let mut crawl = AsyncCrawler::new(4)
+            .with_options(options)
+            .crawl("home-page", Fetcher::default(), Extractor)
+            .unwrap();
+        let mut urls = vec![];
+        while let Some(page) = crawl.next().await {
+            let page = page.unwrap();
+            assert!(page.depth <= 1);
+            urls.push(page.url);
+        }
+        assert_eq!(urls.len(), 4);
+        let summary = crawl.summary().unwrap();
+        assert_eq!(summary.pages, 4);
+        assert_eq!(summary.stopped, None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn politeness() {
+        let options = CrawlOptions {
+            max_per_host: Some(1),
+            host_delay: Some(Duration::from_millis(5)),
+            ..Default::default()
+        };
+        let fetcher = Arc::new(Fetcher::default());
+        let started = std::time::Instant::now();
+        let results: Vec<_> = AsyncCrawler::new(4)
+            .with_options(options)
+            .crawl("home-page", fetcher.clone(), Extractor)
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(results.len(), 13);
+        // every URL here has the same (empty) host
+        assert_eq!(fetcher.max_running.load(Ordering::SeqCst), 1);
+        assert!(started.elapsed() >= Duration::from_millis(12 * 5));
+    }
+
+    #[tokio::test]
+    async fn shutdown_and_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = Checkpoint::new(dir.path());
+        let crawler = AsyncCrawler::new(2).with_options(CrawlOptions {
+            checkpoint: Some(checkpoint.clone()),
+            ..Default::default()
+        });
+        let mut crawl = crawler
+            .crawl("home-page", Fetcher::default(), Extractor)
+            .unwrap();
+        let mut urls = vec![crawl.next().await.unwrap().unwrap().url];
+        crawl.shutdown_handle().shutdown();
+        while let Some(page) = crawl.next().await {
+            urls.push(page.unwrap().url);
+        }
+        let summary = crawl.summary().unwrap();
+        assert_eq!(summary.stopped, Some(StopReason::Shutdown));
+        assert!(summary.pages < 13);
+
+        let mut crawl = crawler
+            .crawl_resume(checkpoint, Fetcher::default(), Extractor)
+            .unwrap();
+        while let Some(page) = crawl.next().await {
+            urls.push(page.unwrap().url);
+        }
+        let summary = crawl.summary().unwrap();
+        assert_eq!((summary.pages, summary.stopped), (13, None));
+        let unique: HashSet<_> = urls.iter().collect();
+        assert_eq!((urls.len(), unique.len()), (13, 13));
+        assert!(!Checkpoint::new(dir.path()).exists());
+    }
+
+    #[tokio::test]
+    async fn report_errors() {
+        struct Broken;
+
+        #[async_trait::async_trait]
+        impl AsyncExtractor for Broken {
+            async fn extract(&self, _u: &str, _document: &str) -> Result<Extraction, ExtractError> {
+                Ok(Extraction::links(vec!["broken"]))
+            }
+        }
+
+        let results: Vec<_> = AsyncCrawler::new(4)
+            .crawl("home-page", Fetcher::default(), Broken)
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap().url, "home-page");
+        assert!(matches!(
+            results[1],
+            Err(Error::Fetch(FetchError::NotFound))
+        ));
+    }
+
+    #[tokio::test]
+    async fn robots() {
+        struct Site;
+
+        #[async_trait::async_trait]
+        impl AsyncFetcher for Site {
+            async fn fetch(&self, u: &str) -> Result<Response, FetchError> {
+                match u {
+                    "https://a.com/robots.txt" => {
+                        Ok("User-agent: gushiwen\nDisallow: /*.jpg$".into())
+                    }
+                    "https://a.com/" => {
+                        Ok(r#"["https://a.com/a.jpg", "https://b.com/a.jpg"]"#.into())
+                    }
+                    "https://b.com/a.jpg" => Ok("[]".into()),
+                    _ => Err(FetchError::NotFound),
+                }
+            }
+        }
+
+        let options = CrawlOptions {
+            user_agent: Some("gushiwen/0.1".to_string()),
+            ..Default::default()
+        };
+        let mut crawl = AsyncCrawler::new(2)
+            .with_options(options)
+            .crawl("https://a.com/", Site, Extractor)
+            .unwrap();
+        let mut blocked = vec![];
+        while let Some(result) = crawl.next().await {
+            if let Err(e) = result {
+                blocked.push(e.to_string());
+            }
+        }
+        assert_eq!(blocked, vec!["fetch: disallowed by robots.txt"]);
+        let summary = crawl.summary().unwrap();
+        assert_eq!((summary.pages, summary.errors, summary.blocked), (2, 0, 1));
+    }
+}
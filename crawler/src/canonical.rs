@@ -0,0 +1,231 @@
+use crate::uri::Url;
+
+/// Rewrites URLs so that the different spellings of one page are crawled once.
+///
+/// ```
+/// use crawler::Canonicalizer;
+///
+/// let canonical = Canonicalizer::default();
+/// assert_eq!(
+///     canonical.canonicalize("HTTPS://WWW.Gushiwen.cn:443/a/./b/../%7euser?utm_source=x&id=2&a=1#top"),
+///     "https://www.gushiwen.cn/a/~user?a=1&id=2",
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Canonicalizer {
+    /// Query parameters removed from every URL, a trailing `*` matches any suffix.
+    pub drop_params: Vec<String>,
+}
+
+/// Parameters of analytics and ad click tracking, which never change the page.
+const TRACKING_PARAMS: &[&str] = &[
+    "utm_*", "gclid", "dclid", "fbclid", "msclkid", "yclid", "mc_cid", "mc_eid", "_ga", "_gl",
+];
+
+impl Default for Canonicalizer {
+    fn default() -> Self {
+        Self {
+            drop_params: TRACKING_PARAMS.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+impl Canonicalizer {
+    /// Keeps every query parameter.
+    pub fn keep_params() -> Self {
+        Self {
+            drop_params: vec![],
+        }
+    }
+
+    /// Lowercases the scheme and host, strips default ports and the fragment, normalizes
+    /// percent-encoding and dot segments, sorts the query by key and drops `drop_params`. URLs that
+    /// are not absolute only lose their fragment.
+    pub fn canonicalize(&self, url: &str) -> String {
+        let mut uri = match Url::parse(url) {
+            Ok(uri) => uri,
+            Err(_) => return url.split('#').next().unwrap_or_default().to_string(),
+        };
+        // the parser already lowercases, strips default ports, resolves dot segments and
+        // encodes what must be
+        let query = uri.query().map(normalize_percent);
+        uri.set_query(None);
+        uri.set_fragment(None);
+        let path = normalize_percent(uri.path());
+        let mut canonical = uri.as_str()[..uri.as_str().len() - uri.path().len()].to_string();
+        if uri.cannot_be_a_base() {
+            canonical.push_str(&path);
+        } else {
+            // decoding may have revealed more
+            canonical.push_str(&remove_dot_segments(&path));
+        }
+
+        if let Some(query) = query {
+            let mut params: Vec<_> = query
+                .split('&')
+                .filter(|param| !param.is_empty())
+                .filter(|param| {
+                    let key = param.split('=').next().unwrap_or_default();
+                    !self
+                        .drop_params
+                        .iter()
+                        .any(|drop| match drop.strip_suffix('*') {
+                            Some(prefix) => key.starts_with(prefix),
+                            None => key == drop,
+                        })
+                })
+                .collect();
+            // by key only: the order of a repeated key can matter, e.g. `a=2&a=1`
+            params.sort_by_key(|param| param.split('=').next().unwrap_or_default());
+            if !params.is_empty() {
+                canonical.push('?');
+                canonical.push_str(&params.join("&"));
+            }
+        }
+        canonical
+    }
+}
+
+fn is_unreserved(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"-._~".contains(&b)
+}
+
+/// Decodes percent-encoded unreserved characters and uppercases the hex digits of the rest.
+fn normalize_percent(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut normalized = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| {
+            let hex = std::str::from_utf8(hex).ok()?;
+            u8::from_str_radix(hex, 16).ok()
+        });
+        match hex {
+            Some(b) if bytes[i] == b'%' => {
+                if is_unreserved(b) {
+                    normalized.push(b);
+                } else {
+                    normalized.extend_from_slice(format!("%{:02X}", b).as_bytes());
+                }
+                i += 3;
+            }
+            _ => {
+                normalized.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    // only ASCII was replaced, so this is still UTF-8
+    String::from_utf8(normalized).unwrap_or_else(|_| s.to_string())
+}
+
+/// RFC 3986 section 5.2.4.
+fn remove_dot_segments(path: &str) -> String {
+    let absolute = path.starts_with('/');
+    let segments: Vec<_> = path.split('/').collect();
+    let last = segments.len() - 1;
+    let mut output = vec![];
+    for (i, &segment) in segments.iter().enumerate().skip(absolute as usize) {
+        match segment {
+            "." | ".." => {
+                if segment == ".." {
+                    output.pop();
+                }
+                // `a/b/..` is the directory `a/`
+                if i == last {
+                    output.push("");
+                }
+            }
+            segment => output.push(segment),
+        }
+    }
+    let path = output.join("/");
+    if absolute {
+        format!("/{}", path)
+    } else {
+        path
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn canonicalize() {
+        let canonical = Canonicalizer::default();
+        let same = [
+            "https://www.gushiwen.cn/shiwen/a.aspx?id=1&page=2",
+            "HTTPS://WWW.GUSHIWEN.CN/shiwen/a.aspx?page=2&id=1#x",
+            "https://www.gushiwen.cn:443/shiwen/./a.aspx?id=1&page=2#y",
+            "https://www.gushiwen.cn/mingju/../shiwen/a.aspx?id=1&&page=2",
+            "https://www.gushiwen.cn/%73hiwen/a.aspx?utm_source=x&id=1&fbclid=y&page=2",
+        ];
+        for url in same {
+            assert_eq!(canonical.canonicalize(url), same[0], "{}", url);
+        }
+
+        assert_eq!(canonical.canonicalize("http://a.com"), "http://a.com/");
+        assert_eq!(
+            canonical.canonicalize("http://a.com:8080/"),
+            "http://a.com:8080/"
+        );
+        assert_eq!(canonical.canonicalize("http://a.com:/"), "http://a.com/");
+        assert_eq!(
+            canonical.canonicalize("https://a.com:80/"),
+            "https://a.com:80/"
+        );
+        assert_eq!(
+            canonical.canonicalize("http://a.com/a%2fb%e4"),
+            "http://a.com/a%2Fb%E4"
+        );
+        for url in [
+            "http://a.com/诗?q=李白",
+            "http://a.com/%e8%af%97?q=%E6%9D%8E%E7%99%BD",
+        ] {
+            assert_eq!(
+                canonical.canonicalize(url),
+                "http://a.com/%E8%AF%97?q=%E6%9D%8E%E7%99%BD"
+            );
+        }
+        assert_eq!(
+            canonical.canonicalize("http://a.com/?utm_x=1"),
+            "http://a.com/"
+        );
+        assert_eq!(
+            canonical.canonicalize("http://a.com/100%"),
+            "http://a.com/100%"
+        );
+        assert_eq!(canonical.canonicalize("MAILTO:Me@A.com"), "mailto:Me@A.com");
+        assert_eq!(canonical.canonicalize("page0#top"), "page0");
+
+        assert_eq!(
+            canonical.canonicalize("http://a.com/?b=1&a=2&a=1"),
+            "http://a.com/?a=2&a=1&b=1"
+        );
+
+        let keep = Canonicalizer::keep_params();
+        assert_eq!(
+            keep.canonicalize("http://a.com/?utm_x=1&gclid=2"),
+            "http://a.com/?gclid=2&utm_x=1"
+        );
+        let custom = Canonicalizer {
+            drop_params: vec!["sid".to_string()],
+        };
+        assert_eq!(
+            custom.canonicalize("http://a.com/?sid=1&utm_x=1"),
+            "http://a.com/?utm_x=1"
+        );
+    }
+
+    #[test]
+    fn dot_segments() {
+        assert_eq!(remove_dot_segments("/a/b/c/./../../g"), "/a/g");
+        assert_eq!(remove_dot_segments("/a/b/.."), "/a/");
+        assert_eq!(remove_dot_segments("/a/./b/"), "/a/b/");
+        assert_eq!(remove_dot_segments("/../a"), "/a");
+        assert_eq!(remove_dot_segments("/.."), "/");
+        assert_eq!(remove_dot_segments("/"), "/");
+        assert_eq!(remove_dot_segments(""), "");
+    }
+}
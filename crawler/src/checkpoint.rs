@@ -0,0 +1,127 @@
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Request, Result};
+
+/// Where and how often a crawl saves its progress, so that it can be resumed with
+/// `crawl_resume` after it was killed. A crawl that runs out of links removes it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub dir: PathBuf,
+    pub interval: Duration,
+}
+
+impl Checkpoint {
+    /// Every minute.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            interval: Duration::from_secs(60),
+        }
+    }
+
+    /// A crawl has been saved into `dir`.
+    pub fn exists(&self) -> bool {
+        self.dir.join(FRONTIER).is_file()
+    }
+
+    /// Removes the saved crawl, the frontier first so that `exists` is false even if the
+    /// seen-set stays behind.
+    pub(crate) fn clear(&self) -> Result<()> {
+        for file in [FRONTIER, crate::seen::SEEN] {
+            match fs::remove_file(self.dir.join(file)) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(anyhow::Error::from(e).into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The frontier of a crawl at the time of a checkpoint, the seen-set is saved next to it.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Saved {
+    pub entrance: String,
+    /// Requests in flight are crawled again first.
+    pub inflight: Vec<Request>,
+    pub queued: Vec<Request>,
+    pub dispatched: usize,
+    pub pages: usize,
+    pub errors: usize,
+    pub blocked: usize,
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+const FRONTIER: &str = "frontier.json";
+
+/// Writes the file `name` of the checkpoint in `dir` through `write`, replacing the old one
+/// only once the new one is on disk, so that a crash leaves one or the other but never half
+/// a file.
+pub(crate) fn save_file<F>(dir: &Path, name: &str, write: F) -> Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+{
+    let tmp = dir.join(format!("{}.tmp", name));
+    let save = || {
+        let mut file = BufWriter::new(File::create(&tmp)?);
+        write(&mut file)?;
+        file.into_inner()?.sync_all()?;
+        fs::rename(&tmp, dir.join(name))?;
+        // the rename itself is durable once the directory is synced
+        #[cfg(unix)]
+        File::open(dir)?.sync_all()?;
+        Ok(())
+    };
+    save().map_err(|e: std::io::Error| anyhow::Error::from(e).into())
+}
+
+impl Saved {
+    /// Replaces the previous checkpoint in `dir` only once the new one is complete.
+    pub(crate) fn save(&self, dir: &Path) -> Result<()> {
+        save_file(dir, FRONTIER, |file| {
+            serde_json::to_writer(&mut *file, self).map_err(std::io::Error::from)
+        })
+    }
+
+    pub(crate) fn load(dir: &Path) -> Result<Self> {
+        let json = fs::read(dir.join(FRONTIER)).map_err(anyhow::Error::from)?;
+        Ok(serde_json::from_slice(&json).map_err(anyhow::Error::from)?)
+    }
+}
+
+/// Stops a running crawl gracefully: nothing more is fetched, the pages in flight are left
+/// to be crawled again on resume and a last checkpoint is saved.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    requested: Arc<AtomicBool>,
+    notify: Arc<tokio::sync::Notify>,
+}
+
+impl Shutdown {
+    pub fn shutdown(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        self.notify.notify_one();
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    pub(crate) async fn requested(&self) {
+        if !self.is_requested() {
+            self.notify.notified().await
+        }
+    }
+}
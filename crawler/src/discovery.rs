@@ -0,0 +1,405 @@
+use std::io::Read;
+
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use scraper::{Html, Selector};
+
+use crate::{uri, FetchError};
+
+/// Finding pages through sitemaps and feeds, on top of following links.
+///
+/// Sitemaps are seeded from the `Sitemap:` lines of every robots.txt, and from the
+/// `/sitemap.xml` of the entrance if its robots.txt has none. Feeds are the RSS and Atom `<link rel="alternate">`s of the
+/// pages crawled. Sitemaps and feeds are crawled like pages, except that the crawler takes
+/// their links itself, newest `lastmod` or entry date first, instead of the extractor. Sitemap
+/// seeds are at depth 0, so a sitemap index counts as one level of `max_depth`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Discovery {
+    pub sitemaps: bool,
+    pub feeds: bool,
+}
+
+impl Discovery {
+    pub fn all() -> Self {
+        Self {
+            sitemaps: true,
+            feeds: true,
+        }
+    }
+
+    /// The sitemap at the well-known path of the host of `entrance`.
+    pub(crate) fn well_known(&self, entrance: &str) -> Option<String> {
+        if !self.sitemaps {
+            return None;
+        }
+        uri::resolve(entrance, "/sitemap.xml")
+    }
+
+    /// The links of `document` if it is a sitemap or feed, newest first. `None` for any
+    /// other document, which is left to the extractor.
+    pub(crate) fn links(&self, url: &str, document: &str) -> Option<Vec<String>> {
+        if !self.sitemaps && !self.feeds {
+            return None;
+        }
+        // a cheap look at the start before parsing, most documents are HTML
+        let head = match document.char_indices().nth(1024) {
+            Some((i, _)) => &document[..i],
+            None => document,
+        };
+        let kind = ["<urlset", "<sitemapindex", "<rss", "<rdf:RDF", "<feed"]
+            .iter()
+            .position(|tag| head.contains(tag))?;
+        if (kind < 2 && !self.sitemaps) || (kind >= 2 && !self.feeds) {
+            return None;
+        }
+        let document = roxmltree::Document::parse(document).ok()?;
+        let root = document.root_element();
+        let mut entries = match root.tag_name().name() {
+            "urlset" | "sitemapindex" => sitemap_entries(root),
+            "rss" | "RDF" | "feed" => feed_entries(root),
+            _ => return None,
+        };
+        // newest first, undated last, in document order otherwise
+        entries.sort_by_key(|(_, date)| std::cmp::Reverse(date.unwrap_or(i64::MIN)));
+        Some(
+            entries
+                .into_iter()
+                .filter_map(|(link, _)| uri::resolve(url, &link))
+                .collect(),
+        )
+    }
+
+    /// The feeds an HTML page links to.
+    pub(crate) fn feeds(&self, url: &str, document: &str) -> Vec<String> {
+        const TYPES: [&str; 2] = ["application/rss+xml", "application/atom+xml"];
+        if !self.feeds || !TYPES.iter().any(|t| document.contains(t)) {
+            return vec![];
+        }
+        let selector = Selector::parse("link[rel~=alternate][href]").unwrap();
+        Html::parse_document(document)
+            .select(&selector)
+            .filter(|link| {
+                let kind = link.value().attr("type").unwrap_or_default();
+                TYPES.iter().any(|t| kind.eq_ignore_ascii_case(t))
+            })
+            .filter_map(|link| uri::resolve(url, link.value().attr("href")?))
+            .collect()
+    }
+}
+
+type Entry = (String, Option<i64>);
+
+fn child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|n| n.tag_name().name() == name)
+}
+
+fn child_text(node: roxmltree::Node, name: &str) -> Option<String> {
+    Some(child(node, name)?.text()?.trim().to_string()).filter(|text| !text.is_empty())
+}
+
+/// `<url>`s of a urlset and `<sitemap>`s of an index.
+fn sitemap_entries(root: roxmltree::Node) -> Vec<Entry> {
+    root.children()
+        .filter(|n| matches!(n.tag_name().name(), "url" | "sitemap"))
+        .filter_map(|entry| {
+            let lastmod = child_text(entry, "lastmod").and_then(|d| parse_w3c_date(&d));
+            Some((child_text(entry, "loc")?, lastmod))
+        })
+        .collect()
+}
+
+/// `<item>`s of RSS 0.9x, 1.0 and 2.0, `<entry>`s of Atom.
+fn feed_entries(root: roxmltree::Node) -> Vec<Entry> {
+    root.descendants()
+        .filter(|n| matches!(n.tag_name().name(), "item" | "entry"))
+        .filter_map(|entry| {
+            let link = match entry.tag_name().name() {
+                "item" => child_text(entry, "link").or_else(|| {
+                    let guid = child(entry, "guid")?;
+                    (guid.attribute("isPermaLink") != Some("false"))
+                        .then(|| child_text(entry, "guid"))?
+                })?,
+                _ => entry
+                    .children()
+                    .filter(|n| n.tag_name().name() == "link")
+                    .find(|n| matches!(n.attribute("rel"), None | Some("alternate")))?
+                    .attribute("href")?
+                    .to_string(),
+            };
+            let date = ["updated", "published", "pubDate", "date"]
+                .iter()
+                .filter_map(|name| child_text(entry, name))
+                .find_map(|date| parse_w3c_date(&date).or_else(|| parse_rfc2822_date(&date)));
+            Some((link, date))
+        })
+        .collect()
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn timestamp(date: (i64, i64, i64), time: (i64, i64, i64), offset: i64) -> Option<i64> {
+    let ((year, month, day), (hour, minute, second)) = (date, time);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 24 || minute > 59 {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset)
+}
+
+/// Seconds since the epoch of a sitemap `lastmod` or Atom date: `YYYY`, `YYYY-MM`,
+/// `YYYY-MM-DD` or that with a time and a `Z` or `±hh:mm` offset.
+fn parse_w3c_date(date: &str) -> Option<i64> {
+    let (date, time) = match date.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (date, None),
+    };
+    let mut parts = date.split('-');
+    let year = parts.next()?.parse().ok()?;
+    let month = parts.next().map_or(Some(1), |m| m.parse().ok())?;
+    let day = parts.next().map_or(Some(1), |d| d.parse().ok())?;
+    if parts.next().is_some() {
+        return None;
+    }
+    let time = match time {
+        Some(time) => time,
+        None => return timestamp((year, month, day), (0, 0, 0), 0),
+    };
+    let (time, offset) = match time.find(&['Z', '+', '-'][..]) {
+        Some(i) => (&time[..i], parse_offset(&time[i..])?),
+        None => (time, 0),
+    };
+    let mut hms = time.split(':');
+    let hour = hms.next()?.parse().ok()?;
+    let minute = hms.next()?.parse().ok()?;
+    let second = hms.next().map_or(Some(0.0), |s| s.parse::<f64>().ok())? as i64;
+    timestamp((year, month, day), (hour, minute, second), offset)
+}
+
+/// `Z`, `±hh:mm` or `±hhmm` in seconds.
+fn parse_offset(offset: &str) -> Option<i64> {
+    let (sign, offset) = match offset.as_bytes().first()? {
+        b'Z' => return Some(0),
+        b'+' => (1, &offset[1..]),
+        b'-' => (-1, &offset[1..]),
+        _ => return None,
+    };
+    let digits: String = offset.chars().filter(|c| *c != ':').collect();
+    if digits.len() != 4 {
+        return None;
+    }
+    let hours: i64 = digits[..2].parse().ok()?;
+    let minutes: i64 = digits[2..].parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Seconds since the epoch of an RSS `pubDate`, e.g. `Tue, 02 Jan 2024 10:00:00 +0800`.
+fn parse_rfc2822_date(date: &str) -> Option<i64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let date = date.split_once(',').map_or(date, |(_, date)| date);
+    let mut parts = date.split_whitespace();
+    let day = parts.next()?.parse().ok()?;
+    let month = parts.next()?.get(..3)?.to_ascii_lowercase();
+    let month = MONTHS.iter().position(|m| *m == month)? as i64 + 1;
+    let year: i64 = parts.next()?.parse().ok()?;
+    // two digit years are from the last century, as the RFC's obsolete syntax says
+    let year = if year < 100 { year + 1900 } else { year };
+    let mut hms = parts.next()?.split(':');
+    let hour = hms.next()?.parse().ok()?;
+    let minute = hms.next()?.parse().ok()?;
+    let second = hms.next().map_or(Some(0), |s| s.parse().ok())?;
+    const HOUR: i64 = 3600;
+    let offset = match parts.next().unwrap_or("GMT") {
+        "GMT" | "UT" | "UTC" | "Z" => 0,
+        "EDT" => -4 * HOUR,
+        "EST" | "CDT" => -5 * HOUR,
+        "CST" | "MDT" => -6 * HOUR,
+        "MST" | "PDT" => -7 * HOUR,
+        "PST" => -8 * HOUR,
+        offset => parse_offset(offset)?,
+    };
+    timestamp((year, month, day), (hour, minute, second), offset)
+}
+
+/// The largest sitemap allowed by the protocol, uncompressed.
+const MAX_SITEMAP_SIZE: u64 = 50 * 1024 * 1024;
+
+/// Stops decompressing at `limit` bytes, so that a small file cannot fill the memory.
+fn gunzip_at_most(bytes: &[u8], limit: u64) -> Result<String, FetchError> {
+    let mut text = String::new();
+    flate2::read::GzDecoder::new(bytes)
+        .take(limit + 1)
+        .read_to_string(&mut text)?;
+    if text.len() as u64 > limit {
+        return Err(FetchError::Decode(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("larger than {} bytes uncompressed", limit),
+        )));
+    }
+    Ok(text)
+}
+
+impl crate::Response {
+    /// Whether a response is a gzip file, like `sitemap.xml.gz`, rather than text. Bodies
+    /// compressed only for the transfer are decompressed by the HTTP client.
+    pub fn is_gzip(url: &str, headers: &HeaderMap) -> bool {
+        let content_type = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        content_type.contains("gzip")
+            || url
+                .split(&['?', '#'][..])
+                .next()
+                .unwrap_or(url)
+                .ends_with(".gz")
+    }
+
+    /// The text of a gzip file, an error past the 50MB a sitemap may have uncompressed.
+    pub fn gunzip(bytes: &[u8]) -> Result<String, FetchError> {
+        gunzip_at_most(bytes, MAX_SITEMAP_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn sitemaps() {
+        let discovery = Discovery {
+            sitemaps: true,
+            feeds: false,
+        };
+        let urlset = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+              <url><loc>https://a.com/old</loc><lastmod>2020-01-01</lastmod></url>
+              <url><loc>https://a.com/undated</loc></url>
+              <url><loc> /new </loc><lastmod>2024-03-01T08:00:00+08:00</lastmod></url>
+              <url><loc>https://a.com/newer</loc><lastmod>2024-03-01T00:30Z</lastmod></url>
+            </urlset>"#;
+        assert_eq!(
+            discovery
+                .links("https://a.com/sitemap.xml", urlset)
+                .unwrap(),
+            vec![
+                "https://a.com/newer",
+                "https://a.com/new",
+                "https://a.com/old",
+                "https://a.com/undated",
+            ]
+        );
+
+        let index = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+              <sitemap><loc>https://a.com/poems.xml.gz</loc></sitemap>
+            </sitemapindex>"#;
+        assert_eq!(
+            discovery.links("https://a.com/sitemap.xml", index).unwrap(),
+            vec!["https://a.com/poems.xml.gz"]
+        );
+
+        assert_eq!(discovery.links("https://a.com/", "<html></html>"), None);
+        let html = "<html><p>a sitemap is a <urlset> of URLs</p></html>";
+        assert_eq!(discovery.links("https://a.com/", html), None);
+        assert_eq!(Discovery::default().links("https://a.com/", urlset), None);
+        assert_eq!(
+            discovery.well_known("https://a.com/a/b?c").as_deref(),
+            Some("https://a.com/sitemap.xml")
+        );
+        assert_eq!(Discovery::default().well_known("https://a.com/"), None);
+    }
+
+    #[test]
+    fn feeds() {
+        let discovery = Discovery {
+            sitemaps: false,
+            feeds: true,
+        };
+        let page = r#"<html><head>
+            <link rel="alternate" type="application/rss+xml" href="/rss.xml">
+            <link rel="alternate" type="application/atom+xml" href="https://a.com/atom">
+            <link rel="stylesheet" type="text/css" href="/a.css">
+            </head></html>"#;
+        assert_eq!(
+            discovery.feeds("https://a.com/poems/", page),
+            vec!["https://a.com/rss.xml", "https://a.com/atom"]
+        );
+        assert!(Discovery::default()
+            .feeds("https://a.com/", page)
+            .is_empty());
+
+        let rss = r#"<?xml version="1.0"?><rss version="2.0"><channel>
+            <item><link>https://a.com/1</link><pubDate>Mon, 01 Jan 2024 10:00:00 +0800</pubDate></item>
+            <item><guid>https://a.com/2</guid><pubDate>Mon, 01 Jan 2024 03:00:00 GMT</pubDate></item>
+            <item><guid isPermaLink="false">x</guid></item>
+            </channel></rss>"#;
+        assert_eq!(
+            discovery.links("https://a.com/rss.xml", rss).unwrap(),
+            vec!["https://a.com/2", "https://a.com/1"]
+        );
+
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+            <entry><link rel="edit" href="/edit/1"/><link href="/1"/><updated>2023-12-31T00:00:00Z</updated></entry>
+            <entry><link rel="alternate" href="/2"/><published>2024-01-01</published></entry>
+            </feed>"#;
+        assert_eq!(
+            discovery.links("https://a.com/atom", atom).unwrap(),
+            vec!["https://a.com/2", "https://a.com/1"]
+        );
+        assert_eq!(
+            Discovery {
+                sitemaps: true,
+                feeds: false
+            }
+            .links("https://a.com/atom", atom),
+            None
+        );
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(parse_w3c_date("1970-01-01"), Some(0));
+        assert_eq!(parse_w3c_date("1970-01-02T00:00:01Z"), Some(86401));
+        assert_eq!(parse_w3c_date("1970-01-01T08:00:00.5+08:00"), Some(0));
+        assert_eq!(parse_w3c_date("2000-03"), Some(951868800));
+        assert_eq!(parse_w3c_date("2000-13-01"), None);
+        assert_eq!(parse_w3c_date("yesterday"), None);
+        assert_eq!(parse_rfc2822_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(0));
+        assert_eq!(parse_rfc2822_date("1 Jan 70 00:00 -0100"), Some(3600));
+        assert_eq!(parse_rfc2822_date("Wed, 31 Dec 1969 19:00:00 EST"), Some(0));
+        assert_eq!(parse_rfc2822_date("Thu, 01 Foo 1970 00:00:00 GMT"), None);
+    }
+
+    #[test]
+    fn gzip() {
+        let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        gz.write_all("<urlset></urlset>".as_bytes()).unwrap();
+        let gz = gz.finish().unwrap();
+        assert_eq!(crate::Response::gunzip(&gz).unwrap(), "<urlset></urlset>");
+        assert!(crate::Response::gunzip(b"<urlset>").is_err());
+        assert!(gunzip_at_most(&gz, 17).is_ok());
+        assert!(gunzip_at_most(&gz, 16).is_err());
+
+        let headers = HeaderMap::new();
+        assert!(crate::Response::is_gzip(
+            "https://a.com/s.xml.gz?x=1",
+            &headers
+        ));
+        assert!(!crate::Response::is_gzip("https://a.com/s.xml", &headers));
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "application/x-gzip".parse().unwrap());
+        assert!(crate::Response::is_gzip("https://a.com/s", &headers));
+    }
+}
@@ -1,3 +1,10 @@
+use std::time::Duration;
+
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    StatusCode,
+};
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("fetch: {0}")]
@@ -12,11 +19,129 @@ pub enum Error {
 pub enum FetchError {
     #[error(transparent)]
     HTTPError(#[from] reqwest::Error),
+    /// A compressed body that could not be decompressed.
+    #[error("decode: {0}")]
+    Decode(#[from] std::io::Error),
+    /// 404 and 410.
     #[error("not found")]
     NotFound,
+    /// 429.
+    #[error("too many requests")]
+    TooManyRequests { retry_after: Option<Duration> },
+    /// Any other 4xx.
+    #[error("client error {0}")]
+    Client(u16),
+    /// 5xx.
+    #[error("server error {status}")]
+    Server {
+        status: u16,
+        retry_after: Option<Duration>,
+    },
+    #[error("disallowed by robots.txt")]
+    Disallowed,
+}
+
+impl FetchError {
+    /// The error for a response status, `None` unless it is 4xx or 5xx.
+    pub fn from_status(status: StatusCode, headers: &HeaderMap) -> Option<Self> {
+        let retry_after = || {
+            headers
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(crate::retry::parse_retry_after)
+        };
+        match status.as_u16() {
+            404 | 410 => Some(FetchError::NotFound),
+            429 => Some(FetchError::TooManyRequests {
+                retry_after: retry_after(),
+            }),
+            status @ 400..=499 => Some(FetchError::Client(status)),
+            status @ 500..=599 => Some(FetchError::Server {
+                status,
+                retry_after: retry_after(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Worth retrying: throttling, server errors other than 501 and connection problems.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            FetchError::HTTPError(e) => e.is_timeout() || e.is_connect(),
+            FetchError::TooManyRequests { .. } => true,
+            FetchError::Server { status, .. } => *status != 501,
+            FetchError::NotFound
+            | FetchError::Client(_)
+            | FetchError::Disallowed
+            | FetchError::Decode(_) => false,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            FetchError::TooManyRequests { retry_after } => *retry_after,
+            FetchError::Server { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
-pub enum ExtractError {}
+pub enum ExtractError {
+    #[error("item: {0}")]
+    Item(#[from] serde_json::Error),
+    #[error("item is not an object: {0}")]
+    NotAnObject(serde_json::Value),
+}
+
+/// Why extraction rules could not be loaded.
+#[derive(thiserror::Error, Debug)]
+pub enum RulesError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("toml: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("yaml: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    /// Rules files end in `.toml`, `.yaml` or `.yml`.
+    #[error("unknown rules format {0:?}")]
+    Format(String),
+    #[error("invalid selector {0:?}")]
+    Selector(String),
+    #[error(transparent)]
+    Regex(#[from] regex::Error),
+}
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[cfg(test)]
+mod test {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn from_status() {
+        let mut headers = HeaderMap::new();
+        assert!(FetchError::from_status(StatusCode::OK, &headers).is_none());
+        assert!(FetchError::from_status(StatusCode::MOVED_PERMANENTLY, &headers).is_none());
+        assert!(matches!(
+            FetchError::from_status(StatusCode::GONE, &headers),
+            Some(FetchError::NotFound)
+        ));
+        assert!(matches!(
+            FetchError::from_status(StatusCode::FORBIDDEN, &headers),
+            Some(FetchError::Client(403))
+        ));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+        let e = FetchError::from_status(StatusCode::SERVICE_UNAVAILABLE, &headers).unwrap();
+        assert!(e.is_transient());
+        assert_eq!(e.retry_after(), Some(Duration::from_secs(7)));
+        let e = FetchError::from_status(StatusCode::TOO_MANY_REQUESTS, &headers).unwrap();
+        assert!(e.is_transient());
+        assert_eq!(e.retry_after(), Some(Duration::from_secs(7)));
+        let e = FetchError::from_status(StatusCode::NOT_IMPLEMENTED, &headers).unwrap();
+        assert!(!e.is_transient());
+    }
+}
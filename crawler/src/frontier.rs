@@ -0,0 +1,701 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    checkpoint::Saved, uri, CrawlOptions, Error, FetchError, Result, SeenSet, StopReason, Summary,
+};
+
+/// A URL to crawl, its distance from the entrance and the page it was found on.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Request {
+    pub url: String,
+    pub depth: usize,
+    #[serde(default)]
+    pub referrer: Option<String>,
+}
+
+impl Request {
+    pub fn new<U: Into<String>>(url: U, depth: usize) -> Self {
+        Self {
+            url: url.into(),
+            depth,
+            referrer: None,
+        }
+    }
+
+    pub fn with_referrer<U: Into<String>>(mut self, referrer: U) -> Self {
+        self.referrer = Some(referrer.into());
+        self
+    }
+}
+
+type CrawlDelay = dyn Fn(&str) -> Option<Duration> + Send;
+
+/// URLs waiting to be crawled and the bookkeeping shared by every crawler: deduplication,
+/// politeness and the limits of `CrawlOptions`.
+///
+/// Each host has its own queue and the hosts take turns, so one slow or rate limited host
+/// does not hold up the others.
+pub(crate) struct Frontier {
+    options: CrawlOptions,
+    entrance: String,
+    /// The `/sitemap.xml` of the host of the entrance, crawled if its robots.txt lists no
+    /// sitemaps. Often missing, so not finding it is no error.
+    well_known: Option<String>,
+    started: Instant,
+    seen: Box<dyn SeenSet>,
+    hosts: HashMap<String, Host>,
+    /// Hosts with queued requests, in the order they get their next turn.
+    turns: VecDeque<String>,
+    queued: usize,
+    /// The robots.txt `Crawl-delay` of the host of a URL, if it is known yet.
+    crawl_delay: Option<Box<CrawlDelay>>,
+    /// Requests handed out by `next` and not completed yet.
+    inflight: HashSet<Request>,
+    /// Requests ever handed out by `next`, less those blocked by robots.txt: what
+    /// `max_pages` counts.
+    dispatched: usize,
+    pages: usize,
+    errors: usize,
+    blocked: usize,
+    bytes: u64,
+    stopped: Option<StopReason>,
+    last_checkpoint: Instant,
+}
+
+#[derive(Default)]
+struct Host {
+    queue: VecDeque<Request>,
+    inflight: usize,
+    /// No request to the host may start before then.
+    not_before: Option<Instant>,
+}
+
+impl Host {
+    fn is_full(&self, max_per_host: Option<usize>) -> bool {
+        matches!(max_per_host, Some(max) if self.inflight >= max)
+    }
+
+    fn is_ready(&self, max_per_host: Option<usize>, now: Instant) -> bool {
+        !self.is_full(max_per_host) && !matches!(self.not_before, Some(at) if at > now)
+    }
+}
+
+/// Requests are scheduled per host, ignoring ports. URLs without a host share one queue.
+fn host_of(url: &str) -> String {
+    uri::host(url).unwrap_or_default()
+}
+
+impl Frontier {
+    pub(crate) fn new(options: CrawlOptions, entrance: String) -> Result<Self> {
+        let entrance = options.canonicalizer.canonicalize(&entrance);
+        let seen = match options.checkpoint {
+            Some(_) => options.seen.open_until_saved()?,
+            None => options.seen.open()?,
+        };
+        let mut frontier = Self::with_seen(options, entrance.clone(), seen);
+        frontier.seen.insert(&entrance);
+        frontier.push(Request::new(entrance.clone(), 0));
+        frontier.well_known = frontier.options.discovery.well_known(&entrance);
+        if frontier.options.ignore_robots {
+            if let Some(sitemap) = frontier.well_known.clone() {
+                frontier.discover(&sitemap, 0, None);
+            }
+        }
+        Ok(frontier)
+    }
+
+    /// Continues the crawl saved in the checkpoint directory `dir`.
+    pub(crate) fn resume(options: CrawlOptions, dir: &Path) -> Result<Self> {
+        let saved = Saved::load(dir)?;
+        let seen = options.seen.load(dir)?;
+        let mut frontier = Self::with_seen(options, saved.entrance, seen);
+        frontier.started = Instant::now()
+            .checked_sub(saved.elapsed)
+            .unwrap_or_else(Instant::now);
+        frontier.dispatched = saved.dispatched - saved.inflight.len();
+        frontier.pages = saved.pages;
+        frontier.errors = saved.errors;
+        frontier.blocked = saved.blocked;
+        frontier.bytes = saved.bytes;
+        for request in saved.inflight.into_iter().chain(saved.queued) {
+            // the set is saved after the frontier and may miss what it queued
+            frontier.seen.insert(&request.url);
+            frontier.push(request);
+        }
+        Ok(frontier)
+    }
+
+    fn with_seen(options: CrawlOptions, entrance: String, seen: Box<dyn SeenSet>) -> Self {
+        Self {
+            options,
+            entrance,
+            well_known: None,
+            started: Instant::now(),
+            seen,
+            hosts: HashMap::new(),
+            turns: VecDeque::new(),
+            queued: 0,
+            crawl_delay: None,
+            inflight: HashSet::new(),
+            dispatched: 0,
+            pages: 0,
+            errors: 0,
+            blocked: 0,
+            bytes: 0,
+            stopped: None,
+            last_checkpoint: Instant::now(),
+        }
+    }
+
+    /// Waits at least the robots.txt `Crawl-delay` between requests to a host, once
+    /// `crawl_delay` knows it.
+    pub(crate) fn with_crawl_delay<F>(mut self, crawl_delay: F) -> Self
+    where
+        F: Fn(&str) -> Option<Duration> + Send + 'static,
+    {
+        self.crawl_delay = Some(Box::new(crawl_delay));
+        self
+    }
+
+    fn push(&mut self, request: Request) {
+        let key = host_of(&request.url);
+        let host = self.hosts.entry(key.clone()).or_default();
+        if host.queue.is_empty() {
+            self.turns.push_back(key);
+        }
+        host.queue.push_back(request);
+        self.queued += 1;
+    }
+
+    /// The next request to fetch, `None` if the queue is empty, a limit has been hit or every
+    /// host with queued requests is busy or waiting for its delay.
+    pub(crate) fn next(&mut self) -> Option<Request> {
+        if self.stopped.is_some() || self.queued == 0 || self.out_of_pages() {
+            return None;
+        }
+        let now = Instant::now();
+        let max_per_host = self.options.max_per_host;
+        let hosts = &self.hosts;
+        let turn = self
+            .turns
+            .iter()
+            .position(|key| hosts[key].is_ready(max_per_host, now))?;
+        let key = self.turns.remove(turn)?;
+        let request = self.hosts.get_mut(&key)?.queue.pop_front()?;
+
+        let delay = self
+            .crawl_delay
+            .as_ref()
+            .and_then(|crawl_delay| crawl_delay(&request.url))
+            .into_iter()
+            .chain(self.options.host_delay)
+            .max();
+        let host = self.hosts.get_mut(&key)?;
+        host.inflight += 1;
+        host.not_before = delay.map(|delay| now + delay);
+        if !host.queue.is_empty() {
+            self.turns.push_back(key);
+        }
+        self.queued -= 1;
+        self.inflight.insert(request.clone());
+        self.dispatched += 1;
+        Some(request)
+    }
+
+    /// When a host that is only waiting for its delay can be crawled again.
+    pub(crate) fn next_ready_at(&self) -> Option<Instant> {
+        if self.stopped.is_some() {
+            return None;
+        }
+        self.turns
+            .iter()
+            .map(|key| &self.hosts[key])
+            .filter(|host| !host.is_full(self.options.max_per_host))
+            .filter_map(|host| host.not_before)
+            .min()
+    }
+
+    /// `max_pages` requests have been handed out. The crawl only stops once none of them is
+    /// in flight, as those blocked by robots.txt give theirs back.
+    fn out_of_pages(&mut self) -> bool {
+        let out = matches!(self.options.max_pages, Some(max) if self.dispatched >= max);
+        if out && self.queued > 0 && self.inflight.is_empty() {
+            self.stop(StopReason::MaxPages);
+        }
+        out
+    }
+
+    fn release(&mut self, request: &Request) {
+        self.inflight.remove(request);
+        let key = host_of(&request.url);
+        if let Some(host) = self.hosts.get_mut(&key) {
+            host.inflight -= 1;
+            let waiting = matches!(host.not_before, Some(at) if at > Instant::now());
+            if host.inflight == 0 && host.queue.is_empty() && !waiting {
+                self.hosts.remove(&key);
+            }
+        }
+        self.out_of_pages();
+    }
+
+    /// Records a fetched page and returns the newly discovered requests among its links.
+    pub(crate) fn complete(
+        &mut self,
+        request: &Request,
+        bytes: usize,
+        links: Vec<String>,
+    ) -> Vec<Request> {
+        self.release(request);
+        self.pages += 1;
+        self.bytes += bytes as u64;
+        if matches!(self.options.max_bytes, Some(max) if self.bytes >= max) {
+            self.stop(StopReason::MaxBytes);
+        }
+        // links are still queued once the crawl has stopped, for a checkpoint to resume from
+        let depth = request.depth + 1;
+        if matches!(self.options.max_depth, Some(max) if depth > max) {
+            return vec![];
+        }
+        links
+            .iter()
+            .filter_map(|url| self.discover(url, depth, Some(&request.url)))
+            .collect()
+    }
+
+    /// Queues `url` unless it is out of scope or was seen before.
+    fn discover(&mut self, url: &str, depth: usize, referrer: Option<&str>) -> Option<Request> {
+        let url = self.options.canonicalizer.canonicalize(url);
+        if !self.options.scope.allows(&url, &self.entrance) || !self.seen.insert(&url) {
+            return None;
+        }
+        let mut request = Request::new(url, depth);
+        request.referrer = referrer.map(str::to_string);
+        self.push(request.clone());
+        Some(request)
+    }
+
+    /// Queues the sitemaps of the `(robots.txt URL, sitemap URLs)` loaded, at depth 0, if
+    /// sitemaps are discovered. The well-known sitemap stands in for those of the entrance's
+    /// robots.txt if it lists none.
+    pub(crate) fn seed_sitemaps(&mut self, sitemaps: Vec<(String, Vec<String>)>) {
+        if !self.options.discovery.sitemaps {
+            return;
+        }
+        for (robots, listed) in sitemaps {
+            if listed.is_empty() {
+                if let Some(well_known) = self.well_known.clone() {
+                    if uri::resolve(&robots, "/sitemap.xml").as_ref() == Some(&well_known) {
+                        self.discover(&well_known, 0, Some(&robots));
+                    }
+                }
+            }
+            for sitemap in listed {
+                if let Some(sitemap) = uri::resolve(&robots, &sitemap) {
+                    self.discover(&sitemap, 0, Some(&robots));
+                }
+            }
+        }
+    }
+
+    /// Records a request that failed to be fetched or extracted, or was not fetched at all
+    /// because of robots.txt.
+    pub(crate) fn fail(&mut self, request: &Request, error: &Error) {
+        if let Error::Fetch(FetchError::Disallowed) = error {
+            self.dispatched -= 1;
+            self.release(request);
+            self.blocked += 1;
+            return;
+        }
+        self.release(request);
+        if let Error::Fetch(FetchError::NotFound) = error {
+            if self.well_known.as_ref() == Some(&request.url) {
+                return;
+            }
+        }
+        self.pages += 1;
+        self.errors += 1;
+    }
+
+    /// Records a fetched page that the pipeline failed to process.
+    pub(crate) fn process_failed(&mut self) {
+        self.errors += 1;
+    }
+
+    pub(crate) fn stop(&mut self, reason: StopReason) {
+        self.stopped.get_or_insert(reason);
+    }
+
+    /// When the crawl must stop because of `max_duration`.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.options.max_duration.map(|max| self.started + max)
+    }
+
+    /// `max_duration` has passed.
+    pub(crate) fn is_expired(&self) -> bool {
+        matches!(self.deadline(), Some(deadline) if Instant::now() >= deadline)
+    }
+
+    /// When the crawler should look at the frontier again even if nothing completes: at the
+    /// deadline, when a host is done waiting for its delay or when a checkpoint is due.
+    pub(crate) fn wake_at(&self) -> Option<Instant> {
+        let checkpoint = self
+            .options
+            .checkpoint
+            .as_ref()
+            .map(|checkpoint| self.last_checkpoint + checkpoint.interval);
+        self.deadline()
+            .into_iter()
+            .chain(self.next_ready_at())
+            .chain(checkpoint)
+            .min()
+    }
+
+    pub(crate) fn inflight(&self) -> usize {
+        self.inflight.len()
+    }
+
+    /// Nothing is in flight and nothing more will be handed out.
+    pub(crate) fn is_done(&self) -> bool {
+        self.inflight.is_empty() && (self.stopped.is_some() || self.queued == 0)
+    }
+
+    pub(crate) fn checkpoint_if_due(&mut self) {
+        if matches!(&self.options.checkpoint, Some(checkpoint) if self.last_checkpoint.elapsed() >= checkpoint.interval)
+        {
+            self.checkpoint();
+        }
+    }
+
+    /// Called once the crawl is over: a crawl stopped early saves a last checkpoint to resume
+    /// from, one that drained its frontier removes it so that the next run starts afresh.
+    pub(crate) fn finish(&mut self) {
+        let drained = self.stopped.is_none() && self.queued == 0 && self.inflight.is_empty();
+        if !drained {
+            self.checkpoint();
+            return;
+        }
+        if let Some(checkpoint) = &self.options.checkpoint {
+            if let Err(e) = checkpoint.clear() {
+                println!(
+                    "[CHECKPOINT] failed to remove {}. {}",
+                    checkpoint.dir.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    /// Saves the frontier and the seen-set into the checkpoint directory, if there is one.
+    /// Failures are logged, the crawl goes on.
+    pub(crate) fn checkpoint(&mut self) {
+        self.last_checkpoint = Instant::now();
+        let dir = match &self.options.checkpoint {
+            Some(checkpoint) => checkpoint.dir.clone(),
+            None => return,
+        };
+        if let Err(e) = self.save(&dir) {
+            println!("[CHECKPOINT] failed to save {}. {}", dir.display(), e);
+        }
+    }
+
+    fn save(&mut self, dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(dir).map_err(anyhow::Error::from)?;
+        let queued = self
+            .turns
+            .iter()
+            .flat_map(|key| self.hosts[key].queue.iter().cloned())
+            .collect();
+        Saved {
+            entrance: self.entrance.clone(),
+            inflight: self.inflight.iter().cloned().collect(),
+            queued,
+            dispatched: self.dispatched,
+            pages: self.pages,
+            errors: self.errors,
+            blocked: self.blocked,
+            bytes: self.bytes,
+            elapsed: self.started.elapsed(),
+        }
+        .save(dir)?;
+        // after the frontier: a set ahead of it would lose the links found in between, one
+        // behind it only lets them be queued again, which `resume` prevents
+        self.seen.save(dir)
+    }
+
+    pub(crate) fn summary(&self) -> Summary {
+        Summary {
+            pages: self.pages,
+            errors: self.errors,
+            blocked: self.blocked,
+            bytes: self.bytes,
+            elapsed: self.started.elapsed(),
+            stopped: self.stopped,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Scope;
+
+    fn urls(urls: &[&str]) -> Vec<String> {
+        urls.iter().map(|url| url.to_string()).collect()
+    }
+
+    #[test]
+    fn max_depth() {
+        let options = CrawlOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        let mut frontier = Frontier::new(options, "a".to_string()).unwrap();
+        let a = frontier.next().unwrap();
+        assert_eq!(a.depth, 0);
+        assert_eq!(
+            frontier.complete(&a, 1, urls(&["a", "b"])),
+            vec![Request::new("b", 1).with_referrer("a")]
+        );
+        let b = frontier.next().unwrap();
+        assert!(frontier.complete(&b, 1, urls(&["c"])).is_empty());
+        assert!(frontier.is_done());
+        assert_eq!(frontier.summary().stopped, None);
+    }
+
+    #[test]
+    fn scope() {
+        let options = CrawlOptions {
+            scope: Scope::http().and(Scope::SameHost),
+            ..Default::default()
+        };
+        let mut frontier = Frontier::new(options, "https://a.com/".to_string()).unwrap();
+        let a = frontier.next().unwrap();
+        let discovered = frontier.complete(
+            &a,
+            1,
+            urls(&[
+                "https://a.com/x",
+                "https://b.com/",
+                "mailto:me@a.com",
+                "javascript:void(0)",
+            ]),
+        );
+        assert_eq!(
+            discovered,
+            vec![Request::new("https://a.com/x", 1).with_referrer("https://a.com/")]
+        );
+    }
+
+    #[test]
+    fn canonical() {
+        let mut frontier =
+            Frontier::new(CrawlOptions::default(), "HTTPS://A.com".to_string()).unwrap();
+        let a = frontier.next().unwrap();
+        assert_eq!(a.url, "https://a.com/");
+        let discovered = frontier.complete(
+            &a,
+            1,
+            urls(&[
+                "https://a.com/#top",
+                "https://a.com/x?b=1&c=2",
+                "https://a.com/x?c=2&b=1#y",
+                "https://A.com:443/y/../x?utm_medium=z&b=1&c=2",
+            ]),
+        );
+        assert_eq!(
+            discovered,
+            vec![Request::new("https://a.com/x?b=1&c=2", 1).with_referrer("https://a.com/")]
+        );
+    }
+
+    #[test]
+    fn checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = CrawlOptions {
+            checkpoint: Some(crate::Checkpoint::new(dir.path())),
+            ..Default::default()
+        };
+        let mut frontier = Frontier::new(options.clone(), "a".to_string()).unwrap();
+        let a = frontier.next().unwrap();
+        frontier.complete(&a, 1, urls(&["b", "c"]));
+        let b = frontier.next().unwrap();
+        frontier.checkpoint();
+
+        let mut resumed = Frontier::resume(options, dir.path()).unwrap();
+        // what was in flight goes first
+        assert_eq!(resumed.next(), Some(b));
+        let c = resumed.next().unwrap();
+        assert_eq!(c, Request::new("c", 1).with_referrer("a"));
+        assert_eq!(
+            resumed.complete(&c, 1, urls(&["a", "b", "d"])),
+            vec![Request::new("d", 2).with_referrer("c")]
+        );
+        let summary = resumed.summary();
+        assert_eq!((summary.pages, summary.bytes), (2, 2));
+    }
+
+    #[test]
+    fn checkpoint_disk_seen() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = CrawlOptions {
+            checkpoint: Some(crate::Checkpoint::new(dir.path().join("checkpoint"))),
+            seen: crate::SeenStore::Disk(dir.path().join("seen")),
+            ..Default::default()
+        };
+        let mut frontier = Frontier::new(options.clone(), "a".to_string()).unwrap();
+        let a = frontier.next().unwrap();
+        frontier.complete(&a, 1, urls(&["b"]));
+        frontier.checkpoint();
+        let b = frontier.next().unwrap();
+        // found after the checkpoint, then the crawl is killed
+        frontier.complete(&b, 1, urls(&["c"]));
+        drop(frontier);
+
+        let mut resumed = Frontier::resume(options, &dir.path().join("checkpoint")).unwrap();
+        assert_eq!(resumed.next(), Some(b.clone()));
+        assert_eq!(
+            resumed.complete(&b, 1, urls(&["a", "b", "c"])),
+            vec![Request::new("c", 2).with_referrer("b")]
+        );
+    }
+
+    #[test]
+    fn max_pages_and_bytes() {
+        let options = CrawlOptions {
+            max_pages: Some(2),
+            ..Default::default()
+        };
+        let mut frontier = Frontier::new(options, "a".to_string()).unwrap();
+        let a = frontier.next().unwrap();
+        frontier.complete(&a, 1, urls(&["b", "c"]));
+        let b = frontier.next().unwrap();
+        assert_eq!(frontier.next(), None);
+        assert!(!frontier.is_done());
+        frontier.fail(&b, &Error::Fetch(FetchError::NotFound));
+        assert!(frontier.is_done());
+        let summary = frontier.summary();
+        assert_eq!((summary.pages, summary.errors, summary.bytes), (2, 1, 1));
+        assert_eq!(summary.stopped, Some(StopReason::MaxPages));
+
+        let options = CrawlOptions {
+            max_bytes: Some(10),
+            ..Default::default()
+        };
+        let mut frontier = Frontier::new(options, "a".to_string()).unwrap();
+        let a = frontier.next().unwrap();
+        assert_eq!(
+            frontier.complete(&a, 10, urls(&["b"])),
+            vec![Request::new("b", 1).with_referrer("a")]
+        );
+        assert_eq!(frontier.next(), None);
+        assert!(frontier.is_done());
+        assert_eq!(frontier.summary().stopped, Some(StopReason::MaxBytes));
+
+        let options = CrawlOptions {
+            max_duration: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+        let frontier = Frontier::new(options, "a".to_string()).unwrap();
+        assert!(frontier.deadline().unwrap() > Instant::now());
+    }
+
+    #[test]
+    fn blocked() {
+        let mut frontier = Frontier::new(CrawlOptions::default(), "a".to_string()).unwrap();
+        let a = frontier.next().unwrap();
+        frontier.fail(&a, &Error::Fetch(FetchError::Disallowed));
+        assert!(frontier.is_done());
+        let summary = frontier.summary();
+        assert_eq!((summary.pages, summary.errors, summary.blocked), (0, 0, 1));
+
+        // a blocked request does not use up `max_pages`
+        let options = CrawlOptions {
+            max_pages: Some(2),
+            ..Default::default()
+        };
+        let mut frontier = Frontier::new(options, "a".to_string()).unwrap();
+        let a = frontier.next().unwrap();
+        frontier.complete(&a, 1, urls(&["b", "c", "d"]));
+        let b = frontier.next().unwrap();
+        assert_eq!(frontier.next(), None);
+        frontier.fail(&b, &Error::Fetch(FetchError::Disallowed));
+        let c = frontier.next().unwrap();
+        frontier.complete(&c, 1, vec![]);
+        assert_eq!(frontier.next(), None);
+        assert!(frontier.is_done());
+        let summary = frontier.summary();
+        assert_eq!((summary.pages, summary.blocked), (2, 1));
+        assert_eq!(summary.stopped, Some(StopReason::MaxPages));
+    }
+
+    #[test]
+    fn politeness() {
+        let options = CrawlOptions {
+            max_per_host: Some(2),
+            ..Default::default()
+        };
+        let mut frontier = Frontier::new(options, "https://a.com/".to_string()).unwrap();
+        let entrance = frontier.next().unwrap();
+        frontier.complete(
+            &entrance,
+            1,
+            urls(&[
+                "https://a.com/1",
+                "https://a.com/2",
+                "https://a.com/3",
+                "https://b.com/1",
+                "https://B.com:8080/2",
+                "https://c.com/1",
+            ]),
+        );
+        let dispatched: Vec<_> = std::iter::from_fn(|| frontier.next())
+            .map(|request| request.url)
+            .collect();
+        // hosts take turns and each has at most two requests in flight
+        assert_eq!(
+            dispatched,
+            vec![
+                "https://a.com/1",
+                "https://b.com/1",
+                "https://c.com/1",
+                "https://a.com/2",
+                "https://b.com:8080/2",
+            ]
+        );
+        assert_eq!(frontier.next_ready_at(), None);
+        let a1 = Request::new("https://a.com/1", 1).with_referrer("https://a.com/");
+        frontier.complete(&a1, 1, vec![]);
+        assert_eq!(frontier.next().unwrap().url, "https://a.com/3");
+
+        let options = CrawlOptions {
+            host_delay: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+        let mut frontier = Frontier::new(options, "https://a.com/".to_string())
+            .unwrap()
+            .with_crawl_delay(|url| {
+                url.starts_with("https://b.com/")
+                    .then(|| Duration::from_secs(5))
+            });
+        let entrance = frontier.next().unwrap();
+        frontier.complete(
+            &entrance,
+            1,
+            urls(&["https://a.com/1", "https://b.com/1", "https://b.com/2"]),
+        );
+        let now = Instant::now();
+        assert_eq!(frontier.next().unwrap().url, "https://b.com/1");
+        // a.com waits for `host_delay` since the entrance, b.com for its `Crawl-delay`
+        assert_eq!(frontier.next(), None);
+        let ready_at = frontier.next_ready_at().unwrap();
+        assert!(ready_at > now && ready_at <= now + Duration::from_secs(1));
+        assert!(frontier.wake_at().is_some());
+        assert!(!frontier.is_done());
+    }
+}
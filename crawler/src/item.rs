@@ -0,0 +1,87 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::ExtractError;
+
+/// A record extracted from a document, e.g. a poem, as the JSON object its type serializes to.
+pub type Item = Map<String, Value>;
+
+/// What an `Extractor` found in a document: links to follow and items to keep.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Extraction {
+    pub links: Vec<String>,
+    pub items: Vec<Item>,
+}
+
+impl Extraction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn links<I, S>(links: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            links: links.into_iter().map(Into::into).collect(),
+            items: vec![],
+        }
+    }
+
+    /// `item` must serialize to an object, like structs and maps do.
+    pub fn push_item<T: Serialize>(&mut self, item: &T) -> Result<(), ExtractError> {
+        match serde_json::to_value(item)? {
+            Value::Object(item) => {
+                self.items.push(item);
+                Ok(())
+            }
+            value => Err(ExtractError::NotAnObject(value)),
+        }
+    }
+}
+
+/// The text of a field, strings unquoted, other values as JSON and `null` as nothing.
+pub(crate) fn field_text(item: &Item, field: &str) -> Option<String> {
+    match item.get(field)? {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn push_item() {
+        #[derive(Serialize)]
+        struct Poem {
+            title: &'static str,
+            lines: usize,
+            note: Option<&'static str>,
+        }
+
+        let mut extraction = Extraction::links(vec!["a", "b"]);
+        extraction
+            .push_item(&Poem {
+                title: "静夜思",
+                lines: 4,
+                note: None,
+            })
+            .unwrap();
+        let item = &extraction.items[0];
+        assert_eq!(field_text(item, "title").as_deref(), Some("静夜思"));
+        assert_eq!(field_text(item, "lines").as_deref(), Some("4"));
+        assert_eq!(field_text(item, "note"), None);
+        assert_eq!(field_text(item, "author"), None);
+
+        assert!(matches!(
+            extraction.push_item(&"not an object"),
+            Err(ExtractError::NotAnObject(_))
+        ));
+        assert_eq!(extraction.links, vec!["a", "b"]);
+        assert_eq!(extraction.items.len(), 1);
+    }
+}
@@ -1,92 +1,220 @@
 use std::{
-    collections::HashSet,
-    sync::mpsc::{channel, Receiver, SendError, Sender},
+    sync::mpsc::{channel, Receiver, RecvError, RecvTimeoutError, SendError, Sender},
     sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime},
 };
 
+mod async_crawler;
+mod canonical;
+mod checkpoint;
+mod discovery;
 mod error;
+mod frontier;
+mod item;
+mod options;
+mod page;
+mod retry;
+mod robots;
+mod rules;
+mod scope;
+mod seen;
+mod sink;
+mod uri;
 
+pub use async_crawler::*;
+pub use canonical::Canonicalizer;
+pub use checkpoint::{Checkpoint, Shutdown};
+pub use discovery::Discovery;
 pub use error::*;
+pub use frontier::Request;
+pub use item::{Extraction, Item};
+pub use options::*;
+pub use page::{Page, Pipeline, Processor, Response};
+pub use retry::RetryPolicy;
+pub use robots::Robots;
+pub use rules::{Field, Rule, RuleExtractor, Rules};
+pub use scope::Scope;
+pub use seen::*;
+pub use sink::{Csv, ItemWriter, JsonLines, Sink, Sqlite};
+
+use frontier::Frontier;
+use page::Visit;
+use robots::SyncRobotsCache;
 
 pub trait Fetcher {
-    fn fetch<U: AsRef<str>>(&self, u: U) -> Result<String, FetchError>;
+    fn fetch<U: AsRef<str>>(&self, u: U) -> Result<Response, FetchError>;
 }
 
 pub trait Extractor {
-    fn extract<U: AsRef<str>>(
-        &self,
-        u: U,
-        document: &str,
-    ) -> Result<Option<Vec<String>>, ExtractError>;
+    /// `u` is where the document was fetched from, after redirects.
+    fn extract<U: AsRef<str>>(&self, u: U, document: &str) -> Result<Extraction, ExtractError>;
 }
 
 pub trait Crawler {
-    fn crawl<U, F, E>(
-        &self,
-        entrence: U,
-        fetcher: F,
-        extractor: E,
-    ) -> Result<Receiver<Result<String>>, Error>
+    fn crawl<U, F, E>(&self, entrence: U, fetcher: F, extractor: E) -> Result<Crawl, Error>
     where
         U: Into<String>,
         F: Fetcher + Clone + Send + 'static,
         E: Extractor + Clone + Send + 'static;
+
+    /// Continues the crawl saved in `checkpoint.dir`, crawling the pages that were in flight
+    /// again, and keeps saving it there.
+    fn crawl_resume<F, E>(&self, checkpoint: Checkpoint, fetcher: F, extractor: E) -> Result<Crawl>
+    where
+        F: Fetcher + Clone + Send + 'static,
+        E: Extractor + Clone + Send + 'static;
+}
+
+/// A running crawl: crawled pages and errors as they come, then a summary.
+pub struct Crawl {
+    pages: Receiver<Result<Page>>,
+    summary: JoinHandle<Summary>,
+    shutdown: Shutdown,
+}
+
+impl Crawl {
+    pub fn shutdown_handle(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    pub fn recv(&self) -> Result<Result<Page>, RecvError> {
+        self.pages.recv()
+    }
+
+    pub fn iter(&self) -> std::sync::mpsc::Iter<'_, Result<Page>> {
+        self.pages.iter()
+    }
+
+    /// Waits for the crawl to end.
+    pub fn summary(self) -> Summary {
+        drop(self.pages);
+        self.summary.join().expect("crawl thread panicked")
+    }
 }
 
 pub struct MultiThreadsCrawler {
     fetch_threadiness: usize,
+    options: CrawlOptions,
+    pipeline: Pipeline,
 }
 
 impl Crawler for MultiThreadsCrawler {
-    fn crawl<U, F, E>(
-        &self,
-        entrance: U,
-        fetcher: F,
-        extractor: E,
-    ) -> Result<Receiver<Result<String>>>
+    fn crawl<U, F, E>(&self, entrance: U, fetcher: F, extractor: E) -> Result<Crawl>
     where
         U: Into<String>,
         F: Fetcher + Clone + Send + 'static,
         E: Extractor + Clone + Send + 'static,
     {
-        let entrance = entrance.into();
-        let (tx_url, rx_doc) = self.start_fetch_threads(fetcher, entrance.clone()).unwrap();
-        let rx_urls = self.start_extractor_threads(extractor, rx_doc);
-        self.filter_and_forward_url(entrance, tx_url, rx_urls)
+        let frontier = Frontier::new(self.options.clone(), entrance.into())?;
+        self.run(frontier, fetcher, extractor)
+    }
+
+    fn crawl_resume<F, E>(&self, checkpoint: Checkpoint, fetcher: F, extractor: E) -> Result<Crawl>
+    where
+        F: Fetcher + Clone + Send + 'static,
+        E: Extractor + Clone + Send + 'static,
+    {
+        let dir = checkpoint.dir.clone();
+        let options = CrawlOptions {
+            checkpoint: Some(checkpoint),
+            ..self.options.clone()
+        };
+        let frontier = Frontier::resume(options, &dir)?;
+        self.run(frontier, fetcher, extractor)
     }
 }
 
-type FetchResult = (Sender<String>, Receiver<(String, Result<String, Error>)>);
+/// A fetched document and when and how long it was fetched, or why it could not be fetched.
+type Fetched = (Request, Result<(Response, SystemTime, Duration), Error>);
+
+type Extracted = (Request, Result<Visit>);
+
+type FetchResult = (Sender<Request>, Receiver<Fetched>);
 
-pub type MultiThreadsCrawlerFetchResult = Result<FetchResult, SendError<String>>;
+pub type MultiThreadsCrawlerFetchResult = Result<FetchResult, SendError<Request>>;
+
+/// How often a crawl blocked waiting for pages checks whether it was shut down.
+const SHUTDOWN_POLL: Duration = Duration::from_millis(200);
 
 impl MultiThreadsCrawler {
     pub fn new(fetch_threadiness: usize) -> Self {
-        Self { fetch_threadiness }
+        Self {
+            fetch_threadiness,
+            options: CrawlOptions::default(),
+            pipeline: Pipeline::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: CrawlOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Processors run on the extractor thread, one page at a time.
+    pub fn with_pipeline(mut self, pipeline: Pipeline) -> Self {
+        self.pipeline = pipeline;
+        self
     }
 
-    fn start_fetch_threads<F>(&self, fetcher: F, entrence: String) -> MultiThreadsCrawlerFetchResult
+    fn run<F, E>(&self, mut frontier: Frontier, fetcher: F, extractor: E) -> Result<Crawl>
+    where
+        F: Fetcher + Clone + Send + 'static,
+        E: Extractor + Clone + Send + 'static,
+    {
+        let robots = (!self.options.ignore_robots).then(|| {
+            Arc::new(SyncRobotsCache::new(
+                self.options.user_agent.as_deref(),
+                self.options.retry.clone(),
+            ))
+        });
+        if let Some(robots) = robots.clone() {
+            frontier = frontier.with_crawl_delay(move |url| robots.crawl_delay(url));
+        }
+        let (tx_url, rx_doc) = self.start_fetch_threads(fetcher, robots.clone()).unwrap();
+        let rx_visits = self.start_extractor_threads(extractor, rx_doc);
+        self.forward_pages(frontier, tx_url, rx_visits, robots)
+    }
+
+    fn start_fetch_threads<F>(
+        &self,
+        fetcher: F,
+        robots: Option<Arc<SyncRobotsCache>>,
+    ) -> MultiThreadsCrawlerFetchResult
     where
         F: Fetcher + Send + Clone + 'static,
     {
-        let (tx_url, rx_url) = channel();
+        let (tx_url, rx_url) = channel::<Request>();
         let rx_url = Arc::new(Mutex::new(rx_url));
         let (tx_doc, rx_doc) = channel();
-        tx_url.send(entrence)?;
         (0..self.fetch_threadiness).for_each(|_| {
             let tx_doc = tx_doc.clone();
             let rx_url = rx_url.clone();
             let fetcher = fetcher.clone();
+            let robots = robots.clone();
+            let retry = self.options.retry.clone();
             std::thread::spawn(move || loop {
-                match { rx_url.lock().unwrap().recv() } {
+                let request = rx_url.lock().unwrap().recv();
+                match request {
                     Err(e) => {
                         println!("[FETCHER] failed to receive url. {:?}", e);
                         return;
                     }
-                    Ok(url) => {
-                        if let Err(e) =
-                            tx_doc.send((url.clone(), fetcher.fetch(url).map_err(Error::Fetch)))
-                        {
+                    Ok(request) => {
+                        let document = match &robots {
+                            Some(robots) if !robots.allows(&request.url, &fetcher) => {
+                                Err(Error::Fetch(FetchError::Disallowed))
+                            }
+                            _ => {
+                                let fetched_at = SystemTime::now();
+                                let started = Instant::now();
+                                retry
+                                    .fetch(&fetcher, &request.url)
+                                    .map(|response| (response, fetched_at, started.elapsed()))
+                                    .map_err(Error::Fetch)
+                            }
+                        };
+                        if let Err(e) = tx_doc.send((request, document)) {
                             println!("[FETCHER] {}", e);
                             return;
                         }
@@ -100,17 +228,46 @@ impl MultiThreadsCrawler {
     fn start_extractor_threads<E>(
         &self,
         extractor: E,
-        rx_doc: Receiver<(String, Result<String, Error>)>,
-    ) -> Receiver<Result<Option<Vec<String>>>>
+        rx_doc: Receiver<Fetched>,
+    ) -> Receiver<Extracted>
     where
         E: Extractor + Send + 'static,
     {
         let (tx, rx) = channel();
+        let pipeline = self.pipeline.clone();
+        let discovery = self.options.discovery;
         std::thread::spawn(move || {
-            while let Ok((url, doc)) = rx_doc.recv() {
-                if let Err(e) = tx
-                    .send(doc.and_then(|doc| extractor.extract(url, &doc).map_err(Error::Extract)))
-                {
+            while let Ok((request, doc)) = rx_doc.recv() {
+                let visit = doc.and_then(|(response, fetched_at, elapsed)| {
+                    // relative links are relative to where the redirects ended
+                    let base = response.url.as_deref().unwrap_or(&request.url);
+                    let extraction = match discovery.links(base, &response.body) {
+                        Some(links) => Extraction::links(links),
+                        None => {
+                            let mut extraction = extractor
+                                .extract(base, &response.body)
+                                .map_err(Error::Extract)?;
+                            extraction
+                                .links
+                                .extend(discovery.feeds(base, &response.body));
+                            extraction
+                        }
+                    };
+                    let bytes = response.body.len();
+                    let page = Page::new(
+                        request.clone(),
+                        response,
+                        extraction.items,
+                        fetched_at,
+                        elapsed,
+                    );
+                    Ok(Visit {
+                        bytes,
+                        links: extraction.links,
+                        page: pipeline.process(page),
+                    })
+                });
+                if let Err(e) = tx.send((request, visit)) {
                     println!("[EXTRACTOR] {}", e);
                     return;
                 }
@@ -119,50 +276,95 @@ impl MultiThreadsCrawler {
         rx
     }
 
-    fn filter_and_forward_url(
+    fn forward_pages(
         &self,
-        entrence: String,
-        tx_url: Sender<String>,
-        rx_urls: Receiver<Result<Option<Vec<String>>>>,
-    ) -> Result<Receiver<Result<String>>> {
+        mut frontier: Frontier,
+        tx_url: Sender<Request>,
+        rx_visits: Receiver<Extracted>,
+        robots: Option<Arc<SyncRobotsCache>>,
+    ) -> Result<Crawl> {
         let (tx, rx) = channel();
-        std::thread::spawn(move || {
-            let mut cache = HashSet::new();
-            let mut inflight = 1;
-            cache.insert(entrence);
-            while let Ok(urls) = rx_urls.recv() {
-                inflight -= 1;
-
-                match urls {
-                    Ok(urls) => {
-                        if let Some(urls) = urls {
-                            for url in urls {
-                                if cache.insert(url.clone()) {
-                                    inflight += 1;
-                                    if let Err(e) = tx.send(Ok(url.clone())) {
-                                        println!("[] {}", e);
-                                    }
-                                    if let Err(e) = tx_url.send(url) {
-                                        println!("[] {}", e);
-                                    }
-                                }
+        let threadiness = self.fetch_threadiness.max(1);
+        let shutdown = Shutdown::default();
+        let requested = shutdown.clone();
+        let summary = std::thread::spawn(move || {
+            loop {
+                if requested.is_requested() {
+                    frontier.stop(StopReason::Shutdown);
+                    break;
+                }
+                // checked on every turn, not only when the workers go quiet: on a busy crawl
+                // results keep arriving and the receive below never times out
+                if frontier.is_expired() {
+                    frontier.stop(StopReason::MaxDuration);
+                    break;
+                }
+                if let Some(robots) = &robots {
+                    frontier.seed_sitemaps(robots.take_sitemaps());
+                }
+                // hand out no more than the workers can take, so the frontier keeps choosing
+                // which host goes next
+                while frontier.inflight() < threadiness {
+                    let request = match frontier.next() {
+                        Some(request) => request,
+                        None => break,
+                    };
+                    if let Err(e) = tx_url.send(request) {
+                        println!("[] {}", e);
+                    }
+                }
+                if frontier.is_done() {
+                    break;
+                }
+
+                let timeout = frontier
+                    .wake_at()
+                    .map_or(SHUTDOWN_POLL, |wake_at| {
+                        wake_at.saturating_duration_since(Instant::now())
+                    })
+                    .min(SHUTDOWN_POLL);
+                let extracted = rx_visits.recv_timeout(timeout);
+                frontier.checkpoint_if_due();
+                let (request, visit) = match extracted {
+                    Ok(extracted) => extracted,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+
+                match visit {
+                    Ok(visit) => {
+                        frontier.complete(&request, visit.bytes, visit.links);
+                        let page = match visit.page {
+                            Ok(Some(page)) => Ok(page),
+                            Ok(None) => continue,
+                            Err(e) => {
+                                frontier.process_failed();
+                                Err(e)
                             }
+                        };
+                        if let Err(e) = tx.send(page) {
+                            println!("[] {}", e);
                         }
                     }
                     Err(e) => {
+                        frontier.fail(&request, &e);
                         if let Err(err) = tx.send(Err(e)) {
                             println!("{}", err);
                         }
                     }
                 }
-                if inflight == 0 {
-                    println!("all done!");
-                    return;
-                }
             }
+            frontier.finish();
+            let summary = frontier.summary();
+            println!("{}", summary);
+            summary
         });
 
-        Ok(rx)
+        Ok(Crawl {
+            pages: rx,
+            summary,
+            shutdown,
+        })
     }
 }
 
@@ -177,19 +379,19 @@ mod test {
     struct Fetcher {}
 
     impl super::Fetcher for Fetcher {
-        fn fetch<U: AsRef<str>>(&self, u: U) -> crate::Result<String, crate::FetchError> {
+        fn fetch<U: AsRef<str>>(&self, u: U) -> crate::Result<crate::Response, crate::FetchError> {
             match u.as_ref().as_bytes() {
-                b"home-page" => Ok(r#"["page0", "page1", "page2"]"#.to_string()),
+                b"home-page" => Ok(r#"["page0", "page1", "page2"]"#.into()),
                 b"page0" => {
-                    Ok(r#"["page0", "page1", "page2", "page0-0","page0-1","page0-2"]"#.to_string())
+                    Ok(r#"["page0", "page1", "page2", "page0-0","page0-1","page0-2"]"#.into())
                 }
                 b"page1" => {
-                    Ok(r#"["page0", "page1", "page2", "page1-0","page1-1","page1-2"]"#.to_string())
+                    Ok(r#"["page0", "page1", "page2", "page1-0","page1-1","page1-2"]"#.into())
                 }
                 b"page2" => {
-                    Ok(r#"["page0", "page1", "page2", "page2-0","page2-1","page2-2"]"#.to_string())
+                    Ok(r#"["page0", "page1", "page2", "page2-0","page2-1","page2-2"]"#.into())
                 }
-                _ => Ok(r#"[]"#.to_string()),
+                _ => Ok(r#"[]"#.into()),
             }
         }
     }
@@ -199,13 +401,9 @@ mod test {
             &self,
             _u: U,
             document: &str,
-        ) -> crate::Result<Option<Vec<String>>, crate::ExtractError> {
+        ) -> crate::Result<crate::Extraction, crate::ExtractError> {
             let urls: Vec<String> = serde_json::from_str(document).unwrap();
-            if urls.is_empty() {
-                Ok(None)
-            } else {
-                Ok(Some(urls))
-            }
+            Ok(crate::Extraction::links(urls))
         }
     }
 
@@ -219,5 +417,335 @@ mod test {
         while let Ok(url) = urls.recv() {
             println!("{:?}", url);
         }
+        let summary = urls.summary();
+        assert_eq!((summary.pages, summary.errors), (13, 0));
+        assert_eq!(summary.stopped, None);
+    }
+
+    #[test]
+    fn pipeline() {
+        let stored = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let store = stored.clone();
+        let pipeline = crate::Pipeline::new()
+            .then(|page: crate::Page| Ok((!page.url.starts_with("page0")).then_some(page)))
+            .then(move |page: crate::Page| {
+                if page.url == "page2-2" {
+                    return Err(anyhow::anyhow!("cannot store {}", page.url).into());
+                }
+                store.lock().unwrap().push(page.url.clone());
+                Ok(Some(page))
+            });
+        let crawl = super::MultiThreadsCrawler::new(4)
+            .with_pipeline(pipeline)
+            .crawl("home-page", Fetcher {}, Extractor {})
+            .unwrap();
+        let results: Vec<_> = crawl.iter().collect();
+        let summary = crawl.summary();
+        // dropped pages are still followed
+        assert_eq!((summary.pages, summary.errors), (13, 1));
+        assert_eq!(results.len(), 9);
+        assert_eq!(results.iter().filter(|r| r.is_err()).count(), 1);
+        let mut stored = stored.lock().unwrap().clone();
+        stored.sort();
+        assert_eq!(
+            stored,
+            vec![
+                "home-page",
+                "page1",
+                "page1-0",
+                "page1-1",
+                "page1-2",
+                "page2",
+                "page2-0",
+                "page2-1"
+            ]
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let crawler = super::MultiThreadsCrawler::new(4).with_options(crate::CrawlOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        });
+        let crawl = crawler
+            .crawl("home-page", Fetcher {}, Extractor {})
+            .unwrap();
+        let mut pages: Vec<_> = crawl
+            .iter()
+            .map(|page| {
+                let page = page.unwrap();
+                (page.url, page.depth, page.referrer)
+            })
+            .collect();
+        pages.sort();
+        let home = Some("home-page".to_string());
+        assert_eq!(
+            pages,
+            vec![
+                ("home-page".to_string(), 0, None),
+                ("page0".to_string(), 1, home.clone()),
+                ("page1".to_string(), 1, home.clone()),
+                ("page2".to_string(), 1, home),
+            ]
+        );
+        assert_eq!(crawl.summary().pages, 4);
+
+        let crawler = super::MultiThreadsCrawler::new(4).with_options(crate::CrawlOptions {
+            max_pages: Some(2),
+            ..Default::default()
+        });
+        let crawl = crawler
+            .crawl("home-page", Fetcher {}, Extractor {})
+            .unwrap();
+        let summary = crawl.summary();
+        assert_eq!(summary.pages, 2);
+        assert_eq!(summary.stopped, Some(crate::StopReason::MaxPages));
+    }
+
+    #[test]
+    fn max_duration() {
+        // an endless site answering fast enough that results keep arriving
+        #[derive(Clone)]
+        struct Endless;
+
+        impl super::Fetcher for Endless {
+            fn fetch<U: AsRef<str>>(
+                &self,
+                u: U,
+            ) -> crate::Result<crate::Response, crate::FetchError> {
+                std::thread::sleep(std::time::Duration::from_millis(2));
+                let u = u.as_ref();
+                Ok(format!(r#"["{}-0", "{}-1"]"#, u, u).into())
+            }
+        }
+
+        let crawler = super::MultiThreadsCrawler::new(4).with_options(crate::CrawlOptions {
+            max_duration: Some(std::time::Duration::from_millis(100)),
+            ..Default::default()
+        });
+        let crawl = crawler.crawl("home-page", Endless, Extractor {}).unwrap();
+        let started = std::time::Instant::now();
+        assert!(crawl.iter().count() > 0);
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        assert_eq!(
+            crawl.summary().stopped,
+            Some(crate::StopReason::MaxDuration)
+        );
+    }
+
+    #[test]
+    fn robots() {
+        #[derive(Clone)]
+        struct Site;
+
+        impl super::Fetcher for Site {
+            fn fetch<U: AsRef<str>>(
+                &self,
+                u: U,
+            ) -> crate::Result<crate::Response, crate::FetchError> {
+                match u.as_ref() {
+                    "https://a.com/robots.txt" => Ok("User-agent: *\nDisallow: /private".into()),
+                    "https://a.com/" => {
+                        Ok(r#"["https://a.com/public", "https://a.com/private"]"#.into())
+                    }
+                    "https://a.com/public" | "https://a.com/private" => Ok("[]".into()),
+                    _ => Err(crate::FetchError::NotFound),
+                }
+            }
+        }
+
+        let crawl = super::MultiThreadsCrawler::new(2)
+            .crawl("https://a.com/", Site, Extractor {})
+            .unwrap();
+        let results: Vec<_> = crawl.iter().collect();
+        assert!(results
+            .iter()
+            .any(|r| matches!(r, Err(crate::Error::Fetch(crate::FetchError::Disallowed)))));
+        let summary = crawl.summary();
+        assert_eq!((summary.pages, summary.errors, summary.blocked), (2, 0, 1));
+
+        let crawl = super::MultiThreadsCrawler::new(2)
+            .with_options(crate::CrawlOptions {
+                ignore_robots: true,
+                ..Default::default()
+            })
+            .crawl("https://a.com/", Site, Extractor {})
+            .unwrap();
+        let summary = crawl.summary();
+        assert_eq!((summary.pages, summary.errors, summary.blocked), (3, 0, 0));
+    }
+
+    #[test]
+    fn discovery() {
+        #[derive(Clone)]
+        struct Site;
+
+        impl super::Fetcher for Site {
+            fn fetch<U: AsRef<str>>(
+                &self,
+                u: U,
+            ) -> crate::Result<crate::Response, crate::FetchError> {
+                let document = match u.as_ref() {
+                    "https://a.com/robots.txt" => "Sitemap: https://a.com/index.xml",
+                    "https://a.com/" => {
+                        r#"<html><link rel="alternate" type="application/rss+xml" href="/rss"></html>"#
+                    }
+                    "https://a.com/index.xml" => {
+                        "<sitemapindex><sitemap><loc>/pages.xml</loc></sitemap></sitemapindex>"
+                    }
+                    "https://a.com/pages.xml" => {
+                        "<urlset>
+                            <url><loc>https://a.com/old</loc><lastmod>2001-01-01</lastmod></url>
+                            <url><loc>https://a.com/new</loc><lastmod>2024-01-01</lastmod></url>
+                        </urlset>"
+                    }
+                    "https://a.com/rss" => {
+                        "<rss><channel><item><link>https://a.com/post</link></item></channel></rss>"
+                    }
+                    // listed in neither robots.txt nor a sitemap
+                    "https://a.com/sitemap.xml" => {
+                        "<urlset><url><loc>https://a.com/unlisted</loc></url></urlset>"
+                    }
+                    "https://a.com/new" | "https://a.com/old" | "https://a.com/post" => "",
+                    "https://b.com/" | "https://c.com/" | "https://c.com/page" => "",
+                    "https://c.com/sitemap.xml" => {
+                        "<urlset><url><loc>https://c.com/page</loc></url></urlset>"
+                    }
+                    _ => return Err(crate::FetchError::NotFound),
+                };
+                Ok(document.into())
+            }
+        }
+
+        #[derive(Clone)]
+        struct Nothing;
+
+        impl super::Extractor for Nothing {
+            fn extract<U: AsRef<str>>(
+                &self,
+                _u: U,
+                document: &str,
+            ) -> crate::Result<crate::Extraction, crate::ExtractError> {
+                assert!(!document.contains("<urlset") && !document.contains("<rss"));
+                Ok(crate::Extraction::new())
+            }
+        }
+
+        let crawl = super::MultiThreadsCrawler::new(1)
+            .with_options(crate::CrawlOptions {
+                discovery: crate::Discovery::all(),
+                ..Default::default()
+            })
+            .crawl("https://a.com/", Site, Nothing)
+            .unwrap();
+        let urls: Vec<_> = crawl
+            .iter()
+            .filter_map(|page| page.ok())
+            .map(|page| page.url)
+            .collect();
+        let summary = crawl.summary();
+        assert_eq!((summary.pages, summary.errors), (7, 0));
+        assert!(!urls.iter().any(|url| url == "https://a.com/unlisted"));
+        let position = |url: &str| urls.iter().position(|u| u == url).unwrap();
+        for url in ["index.xml", "pages.xml", "rss", "post"] {
+            position(&format!("https://a.com/{}", url));
+        }
+        assert!(position("https://a.com/new") < position("https://a.com/old"));
+
+        let crawl = super::MultiThreadsCrawler::new(1)
+            .crawl("https://a.com/", Site, Nothing)
+            .unwrap();
+        assert_eq!(crawl.summary().pages, 1);
+
+        // without sitemaps in robots.txt, the well-known one is crawled if there is one
+        let crawler = super::MultiThreadsCrawler::new(1).with_options(crate::CrawlOptions {
+            discovery: crate::Discovery::all(),
+            ..Default::default()
+        });
+        let crawl = crawler.crawl("https://b.com/", Site, Nothing).unwrap();
+        let summary = crawl.summary();
+        assert_eq!((summary.pages, summary.errors), (1, 0));
+        let crawl = crawler.crawl("https://c.com/", Site, Nothing).unwrap();
+        let summary = crawl.summary();
+        assert_eq!((summary.pages, summary.errors), (3, 0));
+    }
+
+    #[test]
+    fn resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = crate::Checkpoint::new(dir.path());
+        let crawler = super::MultiThreadsCrawler::new(4).with_options(crate::CrawlOptions {
+            max_pages: Some(3),
+            checkpoint: Some(checkpoint.clone()),
+            ..Default::default()
+        });
+        let crawl = crawler
+            .crawl("home-page", Fetcher {}, Extractor {})
+            .unwrap();
+        let mut urls: Vec<_> = crawl.iter().map(|page| page.unwrap().url).collect();
+        assert_eq!(crawl.summary().pages, 3);
+
+        let crawl = super::MultiThreadsCrawler::new(4)
+            .crawl_resume(checkpoint, Fetcher {}, Extractor {})
+            .unwrap();
+        urls.extend(crawl.iter().map(|page| page.unwrap().url));
+        let summary = crawl.summary();
+        assert_eq!((summary.pages, summary.stopped), (13, None));
+        urls.sort();
+        urls.dedup();
+        assert_eq!(urls.len(), 13);
+        assert!(!crate::Checkpoint::new(dir.path()).exists());
+    }
+
+    #[test]
+    fn finished_crawl_leaves_nothing_to_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = crate::Checkpoint {
+            interval: std::time::Duration::ZERO,
+            ..crate::Checkpoint::new(dir.path())
+        };
+        let crawler = super::MultiThreadsCrawler::new(4).with_options(crate::CrawlOptions {
+            checkpoint: Some(checkpoint.clone()),
+            ..Default::default()
+        });
+        let crawl = crawler
+            .crawl("home-page", Fetcher {}, Extractor {})
+            .unwrap();
+        assert_eq!(crawl.iter().count(), 13);
+        assert_eq!(crawl.summary().stopped, None);
+        assert!(!checkpoint.exists());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn fresh_crawls_share_disk_seen() {
+        let dir = tempfile::tempdir().unwrap();
+        let crawler = super::MultiThreadsCrawler::new(4).with_options(crate::CrawlOptions {
+            seen: crate::SeenStore::Disk(dir.path().join("seen")),
+            ..Default::default()
+        });
+        for _ in 0..2 {
+            let crawl = crawler
+                .crawl("home-page", Fetcher {}, Extractor {})
+                .unwrap();
+            assert_eq!(crawl.iter().count(), 13);
+        }
+    }
+
+    #[test]
+    fn shutdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let crawler = super::MultiThreadsCrawler::new(4).with_options(crate::CrawlOptions {
+            checkpoint: Some(crate::Checkpoint::new(dir.path())),
+            ..Default::default()
+        });
+        let crawl = crawler
+            .crawl("home-page", Fetcher {}, Extractor {})
+            .unwrap();
+        crawl.shutdown_handle().shutdown();
+        let summary = crawl.summary();
+        assert_eq!(summary.stopped, Some(crate::StopReason::Shutdown));
+        assert!(crate::Checkpoint::new(dir.path()).exists());
     }
 }
@@ -0,0 +1,86 @@
+use std::{fmt, time::Duration};
+
+use crate::{Canonicalizer, Checkpoint, Discovery, RetryPolicy, Scope, SeenStore};
+
+/// Limits and politeness of a single crawl, `None` means unlimited.
+#[derive(Clone, Debug, Default)]
+pub struct CrawlOptions {
+    /// Links are canonicalized before anything else, and reported and crawled as such.
+    pub canonicalizer: Canonicalizer,
+    /// Links out of scope are dropped before they enter the frontier.
+    pub scope: Scope,
+    /// Where URLs already queued are remembered.
+    pub seen: SeenStore,
+    /// Links found on a page at depth `max_depth` are not followed, the entrance is at depth 0.
+    pub max_depth: Option<usize>,
+    /// Total number of pages to fetch, including the entrance.
+    pub max_pages: Option<usize>,
+    /// Wall-clock time from the start of the crawl.
+    pub max_duration: Option<Duration>,
+    /// Total size of the fetched documents.
+    pub max_bytes: Option<u64>,
+    /// Requests to one host in flight at once.
+    pub max_per_host: Option<usize>,
+    /// Time between the start of two requests to one host, a longer robots.txt `Crawl-delay`
+    /// takes precedence.
+    pub host_delay: Option<Duration>,
+    /// Matched against the `User-agent` groups of robots.txt, only the `*` groups apply
+    /// when `None`.
+    pub user_agent: Option<String>,
+    /// Crawl URLs disallowed by robots.txt too, robots.txt is not even fetched.
+    pub ignore_robots: bool,
+    /// Sitemaps and feeds to crawl besides links. Sitemaps listed in robots.txt are missed
+    /// with `ignore_robots`.
+    pub discovery: Discovery,
+    pub retry: RetryPolicy,
+    /// Saves the progress of the crawl at intervals and when it ends.
+    pub checkpoint: Option<Checkpoint>,
+}
+
+/// Why a crawl ended before the frontier was empty.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    MaxPages,
+    MaxDuration,
+    MaxBytes,
+    /// `Shutdown::shutdown` was called.
+    Shutdown,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::MaxPages => write!(f, "max pages reached"),
+            StopReason::MaxDuration => write!(f, "max duration reached"),
+            StopReason::MaxBytes => write!(f, "max bytes reached"),
+            StopReason::Shutdown => write!(f, "shut down"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Summary {
+    /// Pages fetched and extracted, failed ones included.
+    pub pages: usize,
+    pub errors: usize,
+    /// Requests not fetched because robots.txt disallows them.
+    pub blocked: usize,
+    pub bytes: u64,
+    pub elapsed: Duration,
+    /// `None` if every reachable URL within `max_depth` was crawled.
+    pub stopped: Option<StopReason>,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "crawled {} pages ({} errors, {} blocked, {} bytes) in {:?}",
+            self.pages, self.errors, self.blocked, self.bytes, self.elapsed
+        )?;
+        match self.stopped {
+            Some(reason) => write!(f, ", stopped: {}", reason),
+            None => write!(f, ", all done"),
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use reqwest::header::HeaderMap;
+
+use crate::{Item, Request, Result};
+
+/// What a `Fetcher` got for a URL.
+#[derive(Clone, Debug, Default)]
+pub struct Response {
+    /// The URL after redirects, `None` if the fetcher does not know it.
+    pub url: Option<String>,
+    pub status: u16,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+/// A `200 OK` with no headers.
+impl From<String> for Response {
+    fn from(body: String) -> Self {
+        Self {
+            url: None,
+            status: 200,
+            headers: HeaderMap::new(),
+            body,
+        }
+    }
+}
+
+impl From<&str> for Response {
+    fn from(body: &str) -> Self {
+        body.to_string().into()
+    }
+}
+
+/// A crawled page.
+#[derive(Clone, Debug)]
+pub struct Page {
+    pub url: String,
+    /// `url` after redirects.
+    pub final_url: String,
+    pub status: u16,
+    pub headers: HeaderMap,
+    pub body: String,
+    /// What the extractor found in `body`.
+    pub items: Vec<Item>,
+    pub depth: usize,
+    /// The page `url` was found on, `None` for the entrance.
+    pub referrer: Option<String>,
+    /// When the fetch started.
+    pub fetched_at: SystemTime,
+    /// How long the fetch took, retries included.
+    pub elapsed: Duration,
+}
+
+impl Page {
+    pub(crate) fn new(
+        request: Request,
+        response: Response,
+        items: Vec<Item>,
+        fetched_at: SystemTime,
+        elapsed: Duration,
+    ) -> Self {
+        Self {
+            final_url: response.url.unwrap_or_else(|| request.url.clone()),
+            url: request.url,
+            status: response.status,
+            headers: response.headers,
+            body: response.body,
+            items,
+            depth: request.depth,
+            referrer: request.referrer,
+            fetched_at,
+            elapsed,
+        }
+    }
+}
+
+/// A stage of a `Pipeline`, e.g. storing, indexing or transforming pages.
+pub trait Processor: Send + Sync {
+    /// Returns the page for the next processor, or `None` to drop it.
+    fn process(&self, page: Page) -> Result<Option<Page>>;
+}
+
+impl<F> Processor for F
+where
+    F: Fn(Page) -> Result<Option<Page>> + Send + Sync,
+{
+    fn process(&self, page: Page) -> Result<Option<Page>> {
+        self(page)
+    }
+}
+
+/// The processors every crawled page passes through in order, before the crawl yields it.
+/// Links are followed whatever the pipeline does with the page.
+#[derive(Clone, Default)]
+pub struct Pipeline {
+    processors: Vec<Arc<dyn Processor>>,
+}
+
+impl fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Pipeline({} processors)", self.processors.len())
+    }
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn then<P: Processor + 'static>(mut self, processor: P) -> Self {
+        self.processors.push(Arc::new(processor));
+        self
+    }
+
+    /// Stops at the first processor that drops the page or fails.
+    pub fn process(&self, page: Page) -> Result<Option<Page>> {
+        let mut page = page;
+        for processor in &self.processors {
+            page = match processor.process(page)? {
+                Some(page) => page,
+                None => return Ok(None),
+            };
+        }
+        Ok(Some(page))
+    }
+}
+
+/// A fetched and extracted page: its size and links for the frontier, and what is left of
+/// it after the pipeline for the consumer of the crawl.
+pub(crate) struct Visit {
+    pub bytes: usize,
+    pub links: Vec<String>,
+    pub page: Result<Option<Page>>,
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn pipeline() {
+        let request = Request {
+            url: "https://a.com/".to_string(),
+            depth: 1,
+            referrer: Some("https://a.com/start".to_string()),
+        };
+        let mut response = Response::from("<html></html>");
+        response.url = Some("https://www.a.com/".to_string());
+        let page = Page::new(request, response, vec![], SystemTime::now(), Duration::ZERO);
+        assert_eq!(page.final_url, "https://www.a.com/");
+        assert_eq!(page.referrer.as_deref(), Some("https://a.com/start"));
+
+        let stored = Arc::new(Mutex::new(vec![]));
+        let store = stored.clone();
+        let pipeline = Pipeline::new()
+            .then(|mut page: Page| {
+                page.body = page.body.to_uppercase();
+                Ok(Some(page))
+            })
+            .then(move |page: Page| {
+                store.lock().unwrap().push(page.url.clone());
+                Ok(Some(page))
+            });
+        let page = pipeline.process(page).unwrap().unwrap();
+        assert_eq!(page.body, "<HTML></HTML>");
+        assert_eq!(*stored.lock().unwrap(), vec!["https://a.com/"]);
+
+        let pipeline = pipeline
+            .then(|page: Page| Ok((page.status != 200).then_some(page)))
+            .then(|_: Page| -> Result<Option<Page>> { panic!("dropped pages go no further") });
+        assert!(pipeline.process(page.clone()).unwrap().is_none());
+
+        let failing = Pipeline::new().then(|_: Page| Err(anyhow::anyhow!("disk full").into()));
+        assert_eq!(failing.process(page).unwrap_err().to_string(), "disk full");
+    }
+}
@@ -0,0 +1,197 @@
+use std::time::{Duration, SystemTime};
+
+use crate::{AsyncFetcher, FetchError, Fetcher, Response};
+
+/// How transient fetch errors are retried: exponential backoff with jitter, or the
+/// `Retry-After` of the response if it has one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts after the first one, 0 disables retries.
+    pub max_retries: usize,
+    /// Backoff before the first retry, doubled for every retry after it.
+    pub initial_backoff: Duration,
+    /// Longest backoff. A `Retry-After` asking for longer gives up instead.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// How long to wait before retrying after `error` on the `attempt`-th retry, counting
+    /// from 0, or `None` to give up.
+    pub fn backoff(&self, attempt: usize, error: &FetchError) -> Option<Duration> {
+        if attempt >= self.max_retries || !error.is_transient() {
+            return None;
+        }
+        if let Some(retry_after) = error.retry_after() {
+            return (retry_after <= self.max_backoff).then_some(retry_after);
+        }
+        let exp = self
+            .initial_backoff
+            .checked_mul(1 << attempt.min(31))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+        // somewhere between half and all of it, so that failed requests do not come back
+        // in lockstep
+        Some(exp / 2 + exp.mul_f64(fastrand::f64() / 2.0))
+    }
+
+    pub(crate) fn fetch<F: Fetcher>(&self, fetcher: &F, url: &str) -> Result<Response, FetchError> {
+        let mut attempt = 0;
+        loop {
+            let e = match fetcher.fetch(url) {
+                Ok(response) => return Ok(response),
+                Err(e) => e,
+            };
+            let backoff = match self.backoff(attempt, &e) {
+                Some(backoff) => backoff,
+                None => return Err(e),
+            };
+            println!("[FETCHER] retrying {} in {:?}. {}", url, backoff, e);
+            std::thread::sleep(backoff);
+            attempt += 1;
+        }
+    }
+
+    pub(crate) async fn fetch_async<F: AsyncFetcher + ?Sized>(
+        &self,
+        fetcher: &F,
+        url: &str,
+    ) -> Result<Response, FetchError> {
+        let mut attempt = 0;
+        loop {
+            let e = match fetcher.fetch(url).await {
+                Ok(response) => return Ok(response),
+                Err(e) => e,
+            };
+            let backoff = match self.backoff(attempt, &e) {
+                Some(backoff) => backoff,
+                None => return Err(e),
+            };
+            println!("[FETCHER] retrying {} in {:?}. {}", url, backoff, e);
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Delay seconds or an HTTP date, dates in the past mean now.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(
+        at.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn backoff() {
+        let policy = RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+        };
+        let unavailable = FetchError::Server {
+            status: 503,
+            retry_after: None,
+        };
+        for (attempt, max) in [(0, 100), (1, 200), (2, 300)] {
+            let backoff = policy.backoff(attempt, &unavailable).unwrap();
+            assert!(backoff >= Duration::from_millis(max / 2), "{:?}", backoff);
+            assert!(backoff <= Duration::from_millis(max), "{:?}", backoff);
+        }
+        assert_eq!(policy.backoff(3, &unavailable), None);
+        assert_eq!(policy.backoff(0, &FetchError::NotFound), None);
+        assert_eq!(policy.backoff(0, &FetchError::Client(403)), None);
+
+        let throttled = |secs| FetchError::TooManyRequests {
+            retry_after: Some(Duration::from_secs(secs)),
+        };
+        let policy = RetryPolicy {
+            max_backoff: Duration::from_secs(10),
+            ..policy
+        };
+        assert_eq!(
+            policy.backoff(0, &throttled(2)),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(policy.backoff(0, &throttled(60)), None);
+        assert_eq!(RetryPolicy::none().backoff(0, &throttled(2)), None);
+    }
+
+    #[test]
+    fn retry_after() {
+        assert_eq!(parse_retry_after(" 120 "), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        let later = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(60));
+        let after = parse_retry_after(&later).unwrap();
+        assert!(after > Duration::from_secs(55) && after <= Duration::from_secs(60));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn retries() {
+        struct Flaky(AtomicUsize);
+
+        impl Fetcher for Flaky {
+            fn fetch<U: AsRef<str>>(&self, _u: U) -> Result<Response, FetchError> {
+                match self.0.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(FetchError::Server {
+                        status: 502,
+                        retry_after: None,
+                    }),
+                    1 => Err(FetchError::TooManyRequests {
+                        retry_after: Some(Duration::ZERO),
+                    }),
+                    _ => Ok("ok".into()),
+                }
+            }
+        }
+
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let flaky = Flaky(AtomicUsize::new(0));
+        assert_eq!(policy.fetch(&flaky, "a").unwrap().body, "ok");
+        assert_eq!(flaky.0.load(Ordering::SeqCst), 3);
+
+        let flaky = Flaky(AtomicUsize::new(0));
+        let policy = RetryPolicy {
+            max_retries: 1,
+            ..policy
+        };
+        assert!(matches!(
+            policy.fetch(&flaky, "a"),
+            Err(FetchError::TooManyRequests { .. })
+        ));
+    }
+}
@@ -0,0 +1,496 @@
+//! robots.txt as specified by RFC 9309, plus the `Crawl-delay` extension.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use crate::{uri::Url, AsyncFetcher, FetchError, Fetcher, Response, RetryPolicy};
+
+/// The rules of a robots.txt that apply to one user agent.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Robots {
+    /// `(allow, pattern)`
+    rules: Vec<(bool, String)>,
+    crawl_delay: Option<Duration>,
+    sitemaps: Vec<String>,
+}
+
+#[derive(Default)]
+struct Group {
+    agents: Vec<String>,
+    rules: Vec<(bool, String)>,
+    crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    /// Keeps the groups whose `User-agent` is the product token of `user_agent`, e.g.
+    /// `gushiwen` for `gushiwen/0.1`, or the `*` groups if there are none.
+    pub fn parse(text: &str, user_agent: &str) -> Self {
+        let token = user_agent
+            .split(|c: char| c == '/' || c.is_whitespace())
+            .next()
+            .unwrap_or_default();
+
+        let mut groups: Vec<Group> = vec![];
+        let mut sitemaps = vec![];
+        // a `User-agent` line following a rule starts a new group
+        let mut in_rules = true;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim().to_ascii_lowercase(), value.trim()),
+                None => continue,
+            };
+            // not part of any group
+            if key == "sitemap" {
+                if !value.is_empty() {
+                    sitemaps.push(value.to_string());
+                }
+                continue;
+            }
+            if key == "user-agent" {
+                if in_rules {
+                    groups.push(Group::default());
+                    in_rules = false;
+                }
+                if let Some(group) = groups.last_mut() {
+                    group.agents.push(value.to_ascii_lowercase());
+                }
+                continue;
+            }
+            // rules before the first `User-agent` belong to no group
+            let group = match groups.last_mut() {
+                Some(group) => group,
+                None => continue,
+            };
+            in_rules = true;
+            match key.as_str() {
+                "allow" if !value.is_empty() => group.rules.push((true, value.to_string())),
+                "disallow" if !value.is_empty() => group.rules.push((false, value.to_string())),
+                "crawl-delay" => {
+                    group.crawl_delay = value.parse::<f64>().ok().and_then(|secs| {
+                        if secs.is_finite() && secs >= 0.0 {
+                            Some(Duration::from_secs_f64(secs))
+                        } else {
+                            None
+                        }
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        let matches = |agent: &str| !token.is_empty() && agent.eq_ignore_ascii_case(token);
+        let specific = groups.iter().any(|g| g.agents.iter().any(|a| matches(a)));
+        let mut robots = Robots {
+            sitemaps,
+            ..Default::default()
+        };
+        for group in groups.into_iter().filter(|g| {
+            g.agents
+                .iter()
+                .any(|a| if specific { matches(a) } else { a == "*" })
+        }) {
+            robots.rules.extend(group.rules);
+            robots.crawl_delay = robots.crawl_delay.or(group.crawl_delay);
+        }
+        robots
+    }
+
+    /// Whether `path`, including the query, may be crawled. The longest matching rule wins
+    /// and `Allow` wins ties.
+    pub fn allows(&self, path: &str) -> bool {
+        let path = if path.is_empty() { "/" } else { path };
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| matches(pattern, path))
+            .max_by_key(|&&(allow, ref pattern)| (pattern.len(), allow))
+            .map(|&(allow, _)| allow)
+            .unwrap_or(true)
+    }
+
+    pub fn crawl_delay(&self) -> Option<Duration> {
+        self.crawl_delay
+    }
+
+    /// Disallows every path, for hosts whose robots.txt cannot be reached.
+    pub fn disallow_all() -> Self {
+        Robots {
+            rules: vec![(false, "/".to_string())],
+            ..Default::default()
+        }
+    }
+
+    /// The `Sitemap` URLs, whatever the user agent.
+    pub fn sitemaps(&self) -> &[String] {
+        &self.sitemaps
+    }
+}
+
+/// `*` matches any sequence of characters and a trailing `$` anchors the end of the path.
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match path.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts: Vec<_> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+/// `(origin, path and query)` of an absolute URL, `None` for URLs without a host.
+fn split(url: &str) -> Option<(String, String)> {
+    let uri = Url::parse(url).ok()?;
+    let host = uri.host_str().filter(|host| !host.is_empty())?;
+    let mut origin = format!("{}://{}", uri.scheme(), host);
+    if let Some(port) = uri.port() {
+        origin.push_str(&format!(":{}", port));
+    }
+    let mut path = uri.path().to_string();
+    if let Some(query) = uri.query() {
+        path.push('?');
+        path.push_str(query);
+    }
+    Some((origin, path))
+}
+
+/// How long a host whose robots.txt is unreachable stays disallowed before it is fetched
+/// again.
+const UNREACHABLE_FOR: Duration = Duration::from_secs(5 * 60);
+
+/// The robots.txt of a host, `expires` once it could not be reached.
+pub(crate) struct Loaded {
+    robots: Robots,
+    expires: Option<Instant>,
+}
+
+/// A cell that a host's robots.txt is loaded into once.
+pub(crate) trait Slot: Default {
+    fn loaded(&self) -> Option<&Loaded>;
+}
+
+impl Slot for OnceLock<Loaded> {
+    fn loaded(&self) -> Option<&Loaded> {
+        self.get()
+    }
+}
+
+impl Slot for tokio::sync::OnceCell<Loaded> {
+    fn loaded(&self) -> Option<&Loaded> {
+        self.get()
+    }
+}
+
+pub(crate) type SyncRobotsCache = RobotsCache<OnceLock<Loaded>>;
+pub(crate) type AsyncRobotsCache = RobotsCache<tokio::sync::OnceCell<Loaded>>;
+
+/// The robots.txt of every host seen during a crawl, each fetched by the first request to
+/// that host while the others wait for it.
+pub(crate) struct RobotsCache<C> {
+    user_agent: String,
+    retry: RetryPolicy,
+    unreachable_for: Duration,
+    hosts: Mutex<HashMap<String, Arc<C>>>,
+    /// `(robots.txt URL, its sitemap URLs)` not taken yet.
+    sitemaps: Mutex<Vec<(String, Vec<String>)>>,
+}
+
+impl<C: Slot> RobotsCache<C> {
+    /// robots.txt is fetched with the same `retry` policy as the pages.
+    pub(crate) fn new(user_agent: Option<&str>, retry: RetryPolicy) -> Self {
+        Self {
+            user_agent: user_agent.unwrap_or("*").to_string(),
+            retry,
+            unreachable_for: UNREACHABLE_FOR,
+            hosts: Mutex::new(HashMap::new()),
+            sitemaps: Mutex::new(vec![]),
+        }
+    }
+
+    /// The robots.txt loaded since the last call and their sitemaps, none for a missing one.
+    /// Unreachable ones are left out until they are fetched again.
+    pub(crate) fn take_sitemaps(&self) -> Vec<(String, Vec<String>)> {
+        std::mem::take(&mut *self.sitemaps.lock().unwrap())
+    }
+
+    /// `None` until the robots.txt of the host of `url` has been fetched.
+    pub(crate) fn crawl_delay(&self, url: &str) -> Option<Duration> {
+        let (origin, _) = split(url)?;
+        let host = self.hosts.lock().unwrap().get(&origin).cloned()?;
+        host.loaded()?.robots.crawl_delay()
+    }
+
+    /// The slot of the host, a fresh one once an unreachable robots.txt has expired so that
+    /// it is fetched again.
+    fn host(&self, origin: &str) -> Arc<C> {
+        let mut hosts = self.hosts.lock().unwrap();
+        let host = hosts.entry(origin.to_string()).or_default();
+        let expired = host
+            .loaded()
+            .and_then(|loaded| loaded.expires)
+            .is_some_and(|expires| Instant::now() >= expires);
+        if expired {
+            *host = Arc::default();
+        }
+        host.clone()
+    }
+
+    /// As in RFC 9309 section 2.3.1, a robots.txt that is unavailable (4xx) allows
+    /// everything while one that is unreachable (5xx, 429 or a network error) disallows
+    /// everything for a while: the host may well have rules it failed to serve.
+    fn load(&self, origin: &str, robots: Result<Response, FetchError>) -> Loaded {
+        let robots = match robots {
+            Ok(response) => Robots::parse(&response.body, &self.user_agent),
+            Err(FetchError::NotFound | FetchError::Client(_)) => Robots::default(),
+            Err(e) => {
+                println!(
+                    "[ROBOTS] failed to fetch {}/robots.txt, disallowing the host for {:?}. {}",
+                    origin, self.unreachable_for, e
+                );
+                return Loaded {
+                    robots: Robots::disallow_all(),
+                    expires: Some(Instant::now() + self.unreachable_for),
+                };
+            }
+        };
+        self.sitemaps
+            .lock()
+            .unwrap()
+            .push((format!("{}/robots.txt", origin), robots.sitemaps().to_vec()));
+        Loaded {
+            robots,
+            expires: None,
+        }
+    }
+}
+
+impl SyncRobotsCache {
+    pub(crate) fn allows<F: Fetcher>(&self, url: &str, fetcher: &F) -> bool {
+        let (origin, path) = match split(url) {
+            Some(split) => split,
+            None => return true,
+        };
+        let robots_url = format!("{}/robots.txt", origin);
+        self.host(&origin)
+            .get_or_init(|| self.load(&origin, self.retry.fetch(fetcher, &robots_url)))
+            .robots
+            .allows(&path)
+    }
+}
+
+impl AsyncRobotsCache {
+    pub(crate) async fn allows<F: AsyncFetcher + ?Sized>(&self, url: &str, fetcher: &F) -> bool {
+        let (origin, path) = match split(url) {
+            Some(split) => split,
+            None => return true,
+        };
+        let robots_url = format!("{}/robots.txt", origin);
+        self.host(&origin)
+            .get_or_init(|| async {
+                self.load(&origin, self.retry.fetch_async(fetcher, &robots_url).await)
+            })
+            .await
+            .robots
+            .allows(&path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use super::*;
+
+    const ROBOTS: &str = "
+# comments are ignored
+Sitemap: https://www.gushiwen.cn/sitemap.xml
+User-agent: *
+Disallow: /search
+Allow: /search/about   # longer wins
+Disallow: /*.aspx$
+Crawl-delay: 2
+
+User-agent: gushiwen
+User-agent: other
+Disallow: /private/
+Allow: /private/*.html
+Crawl-delay: 0.5
+
+User-agent: gushiwen
+Disallow: /tmp
+Sitemap: https://www.gushiwen.cn/poems.xml.gz
+";
+
+    #[test]
+    fn groups() {
+        let any = Robots::parse(ROBOTS, "curl/7.0");
+        assert!(!any.allows("/search?q=1"));
+        assert!(any.allows("/search/about"));
+        assert!(!any.allows("/shiwen/a.aspx"));
+        assert!(any.allows("/shiwen/a.aspx?id=1"));
+        assert!(any.allows("/private/"));
+        assert!(any.allows(""));
+        assert_eq!(any.crawl_delay(), Some(Duration::from_secs(2)));
+
+        let ours = Robots::parse(ROBOTS, "Gushiwen/0.1 (+https://example.com)");
+        assert!(ours.allows("/search"));
+        assert!(!ours.allows("/private/a.txt"));
+        assert!(ours.allows("/private/a.html"));
+        assert!(!ours.allows("/tmp/a"));
+        assert_eq!(ours.crawl_delay(), Some(Duration::from_millis(500)));
+        let sitemaps = [
+            "https://www.gushiwen.cn/sitemap.xml",
+            "https://www.gushiwen.cn/poems.xml.gz",
+        ];
+        assert_eq!(any.sitemaps(), sitemaps);
+        assert_eq!(ours.sitemaps(), sitemaps);
+
+        assert_eq!(Robots::parse("Disallow: /", "*"), Robots::default());
+        assert!(Robots::parse("User-agent: *\nDisallow:", "*").allows("/"));
+        assert!(!Robots::parse("User-agent: *\nDisallow: /", "*").allows("/"));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("/", "/anything"));
+        assert!(matches("/*", "/"));
+        assert!(matches("/a*b*c", "/a-b-b-c-d"));
+        assert!(!matches("/a*b*c", "/a-c-b"));
+        assert!(matches("/*.php$", "/x/index.php"));
+        assert!(!matches("/*.php$", "/x/index.php?a=1"));
+        assert!(matches("/a$", "/a"));
+        assert!(!matches("/a$", "/ab"));
+        assert!(matches("/a*$", "/abc"));
+    }
+
+    #[derive(Default)]
+    struct Fetcher {
+        fetched: AtomicUsize,
+        flaked: AtomicBool,
+    }
+
+    impl crate::Fetcher for Fetcher {
+        fn fetch<U: AsRef<str>>(&self, u: U) -> Result<Response, FetchError> {
+            self.fetched.fetch_add(1, Ordering::SeqCst);
+            match u.as_ref() {
+                "https://a.com/robots.txt" => {
+                    Ok("User-agent: *\nDisallow: /x\nCrawl-delay: 1\nSitemap: /s.xml".into())
+                }
+                "https://forbidden.com/robots.txt" => Err(FetchError::Client(403)),
+                "https://down.com/robots.txt" => Err(FetchError::Server {
+                    status: 503,
+                    retry_after: None,
+                }),
+                "https://busy.com/robots.txt" => {
+                    Err(FetchError::TooManyRequests { retry_after: None })
+                }
+                "https://reset.com/robots.txt" => Err(FetchError::Decode(
+                    std::io::ErrorKind::ConnectionReset.into(),
+                )),
+                "https://flaky.com/robots.txt" if !self.flaked.swap(true, Ordering::SeqCst) => {
+                    Err(FetchError::Server {
+                        status: 503,
+                        retry_after: None,
+                    })
+                }
+                "https://flaky.com/robots.txt" => Ok("User-agent: *\nDisallow: /x".into()),
+                _ => Err(FetchError::NotFound),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl AsyncFetcher for Fetcher {
+        async fn fetch(&self, u: &str) -> Result<Response, FetchError> {
+            crate::Fetcher::fetch(self, u)
+        }
+    }
+
+    #[test]
+    fn cache() {
+        let fetcher = Fetcher::default();
+        let cache = SyncRobotsCache::new(None, RetryPolicy::none());
+        assert!(!cache.allows("https://a.com/x/1", &fetcher));
+        assert!(!cache.allows("HTTPS://A.COM/x?1", &fetcher));
+        assert!(cache.allows("https://a.com/y", &fetcher));
+        assert!(cache.allows("https://b.com/x", &fetcher));
+        assert!(cache.allows("page0", &fetcher));
+        assert_eq!(fetcher.fetched.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            cache.crawl_delay("https://a.com/"),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(cache.crawl_delay("https://c.com/"), None);
+        assert_eq!(
+            cache.take_sitemaps(),
+            vec![
+                (
+                    "https://a.com/robots.txt".to_string(),
+                    vec!["/s.xml".to_string()]
+                ),
+                ("https://b.com/robots.txt".to_string(), vec![]),
+            ]
+        );
+        assert!(cache.take_sitemaps().is_empty());
+    }
+
+    #[test]
+    fn unavailable_and_unreachable() {
+        let fetcher = Fetcher::default();
+        let cache = SyncRobotsCache::new(None, RetryPolicy::none());
+        assert!(cache.allows("https://forbidden.com/x", &fetcher));
+        assert!(!cache.allows("https://down.com/", &fetcher));
+        assert!(!cache.allows("https://down.com/x?1", &fetcher));
+        assert!(!cache.allows("https://busy.com/x", &fetcher));
+        assert!(!cache.allows("https://reset.com/x", &fetcher));
+        assert_eq!(fetcher.fetched.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn unreachable_then_reachable() {
+        let fetcher = Fetcher::default();
+        let retry = RetryPolicy {
+            max_retries: 1,
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let cache = SyncRobotsCache::new(None, retry);
+        assert!(cache.allows("https://flaky.com/y", &fetcher));
+        assert!(!cache.allows("https://flaky.com/x", &fetcher));
+        assert_eq!(fetcher.fetched.load(Ordering::SeqCst), 2);
+
+        let fetcher = Fetcher::default();
+        let mut cache = SyncRobotsCache::new(None, RetryPolicy::none());
+        cache.unreachable_for = Duration::ZERO;
+        assert!(!cache.allows("https://flaky.com/y", &fetcher));
+        assert!(cache.allows("https://flaky.com/y", &fetcher));
+        assert!(cache.allows("https://flaky.com/z", &fetcher));
+        assert_eq!(fetcher.fetched.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn async_cache() {
+        let fetcher = Fetcher::default();
+        let cache = AsyncRobotsCache::new(Some("gushiwen"), RetryPolicy::none());
+        assert!(!cache.allows("https://a.com/x", &fetcher).await);
+        assert!(cache.allows("https://a.com:8080/x", &fetcher).await);
+        assert!(cache.allows("https://a.com/y", &fetcher).await);
+        assert_eq!(fetcher.fetched.load(Ordering::SeqCst), 2);
+    }
+}
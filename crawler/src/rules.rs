@@ -0,0 +1,378 @@
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+
+use regex::{Regex, RegexSet};
+use scraper::{ElementRef, Html, Selector};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{uri, AsyncExtractor, ExtractError, Extraction, Extractor, Item, RulesError};
+
+/// The rules of a site as written in a TOML or YAML file, see `RuleExtractor`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct Rules {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<Rule>,
+}
+
+/// What to follow and extract on the pages whose URL matches one of `urls`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Rule {
+    /// Regexes matched against the page URL, any page if empty.
+    pub urls: Vec<String>,
+    /// Selectors of the elements whose `href` is followed.
+    pub follow: Vec<String>,
+    /// One item per element matched by this selector, with its fields selected inside it.
+    /// Without it, fields make one item per page.
+    pub item: Option<String>,
+    pub fields: BTreeMap<String, Field>,
+}
+
+/// The value of a field: every element matched by `selector`, processed in the order of
+/// the options below.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Field {
+    pub selector: String,
+    /// The attribute to read, the text of the element if `None`.
+    pub attr: Option<String>,
+    /// Trims whitespace from each value.
+    pub trim: bool,
+    /// Keeps the first capture group, or the whole match if there is none, dropping values
+    /// that do not match.
+    pub regex: Option<String>,
+    /// Joins the values with this separator, only the first value is kept if `None`.
+    pub join: Option<String>,
+}
+
+/// An `Extractor` that follows declarative CSS-selector rules, so that a site can be crawled
+/// without writing one.
+///
+/// ```
+/// use crawler::RuleExtractor;
+///
+/// let extractor = RuleExtractor::from_toml(r#"
+///     [[rule]]
+///     urls = ['^https://www\.gushiwen\.cn/']
+///     follow = ["a.next"]
+///     item = "div.sons"
+///
+///     [rule.fields.title]
+///     selector = "h1"
+///     trim = true
+///
+///     [rule.fields.text]
+///     selector = "div.contson p"
+///     trim = true
+///     join = "\n"
+/// "#)?;
+/// # Ok::<(), crawler::RulesError>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct RuleExtractor {
+    rules: Arc<Vec<CompiledRule>>,
+}
+
+#[derive(Debug)]
+struct CompiledRule {
+    urls: Option<RegexSet>,
+    follow: Vec<Selector>,
+    item: Option<Selector>,
+    fields: Vec<(String, CompiledField)>,
+}
+
+#[derive(Debug)]
+struct CompiledField {
+    selector: Selector,
+    attr: Option<String>,
+    trim: bool,
+    regex: Option<Regex>,
+    join: Option<String>,
+}
+
+fn selector(selector: &str) -> Result<Selector, RulesError> {
+    Selector::parse(selector).map_err(|_| RulesError::Selector(selector.to_string()))
+}
+
+impl RuleExtractor {
+    pub fn new(rules: &Rules) -> Result<Self, RulesError> {
+        let rules = rules
+            .rules
+            .iter()
+            .map(|rule| {
+                Ok(CompiledRule {
+                    urls: match rule.urls.is_empty() {
+                        true => None,
+                        false => Some(RegexSet::new(&rule.urls)?),
+                    },
+                    follow: rule
+                        .follow
+                        .iter()
+                        .map(|s| selector(s))
+                        .collect::<Result<_, _>>()?,
+                    item: rule.item.as_deref().map(selector).transpose()?,
+                    fields: rule
+                        .fields
+                        .iter()
+                        .map(|(name, field)| {
+                            Ok((
+                                name.clone(),
+                                CompiledField {
+                                    selector: selector(&field.selector)?,
+                                    attr: field.attr.clone(),
+                                    trim: field.trim,
+                                    regex: field.regex.as_deref().map(Regex::new).transpose()?,
+                                    join: field.join.clone(),
+                                },
+                            ))
+                        })
+                        .collect::<Result<_, RulesError>>()?,
+                })
+            })
+            .collect::<Result<_, RulesError>>()?;
+        Ok(Self {
+            rules: Arc::new(rules),
+        })
+    }
+
+    pub fn from_toml(rules: &str) -> Result<Self, RulesError> {
+        Self::new(&toml::from_str(rules)?)
+    }
+
+    pub fn from_yaml(rules: &str) -> Result<Self, RulesError> {
+        Self::new(&serde_yaml::from_str(rules)?)
+    }
+
+    /// Reads TOML or YAML depending on the extension of `path`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, RulesError> {
+        let path = path.as_ref();
+        let rules = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml(&rules),
+            Some("yaml") | Some("yml") => Self::from_yaml(&rules),
+            _ => Err(RulesError::Format(path.display().to_string())),
+        }
+    }
+
+    fn extract_document(&self, url: &str, document: &str) -> Extraction {
+        let html = Html::parse_document(document);
+        let mut extraction = Extraction::new();
+        for rule in self.rules.iter() {
+            if matches!(&rule.urls, Some(urls) if !urls.is_match(url)) {
+                continue;
+            }
+            for follow in &rule.follow {
+                let links = html
+                    .select(follow)
+                    .filter_map(|e| e.value().attr("href"))
+                    .filter_map(|href| uri::resolve(url, href));
+                extraction.links.extend(links);
+            }
+            if rule.fields.is_empty() {
+                continue;
+            }
+            let items: Box<dyn Iterator<Item = ElementRef>> = match &rule.item {
+                Some(item) => Box::new(html.select(item)),
+                None => Box::new(std::iter::once(html.root_element())),
+            };
+            let items = items
+                .map(|element| rule.extract_item(element))
+                .filter(|item| !item.is_empty());
+            extraction.items.extend(items);
+        }
+        extraction
+    }
+}
+
+impl CompiledRule {
+    /// Fields without a value are left out.
+    fn extract_item(&self, element: ElementRef) -> Item {
+        let mut item = Item::new();
+        for (name, field) in &self.fields {
+            let mut values = element.select(&field.selector).filter_map(|e| {
+                let value = match &field.attr {
+                    Some(attr) => e.value().attr(attr)?.to_string(),
+                    None => e.text().collect(),
+                };
+                let value = match field.trim {
+                    true => value.trim().to_string(),
+                    false => value,
+                };
+                match &field.regex {
+                    Some(regex) => {
+                        let captures = regex.captures(&value)?;
+                        let value = captures.get(1).or_else(|| captures.get(0))?;
+                        Some(value.as_str().to_string())
+                    }
+                    None => Some(value),
+                }
+            });
+            let value = match &field.join {
+                Some(separator) => {
+                    let values: Vec<_> = values.collect();
+                    (!values.is_empty()).then(|| values.join(separator))
+                }
+                None => values.next(),
+            };
+            if let Some(value) = value {
+                item.insert(name.clone(), Value::String(value));
+            }
+        }
+        item
+    }
+}
+
+impl Extractor for RuleExtractor {
+    fn extract<U: AsRef<str>>(&self, u: U, document: &str) -> Result<Extraction, ExtractError> {
+        Ok(self.extract_document(u.as_ref(), document))
+    }
+}
+
+#[async_trait::async_trait]
+impl AsyncExtractor for RuleExtractor {
+    async fn extract(&self, u: &str, document: &str) -> Result<Extraction, ExtractError> {
+        Ok(self.extract_document(u, document))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    const DOCUMENT: &str = r#"
+        <html><head><title> 唐诗三百首 </title></head><body>
+        <div class="sons">
+            <h1> 静夜思 </h1>
+            <p class="source"><a href="/author_1.aspx">李白</a><a>〔唐代〕</a></p>
+            <div class="contson"><p>床前明月光，疑是地上霜。</p><p>举头望明月，低头思故乡。</p></div>
+        </div>
+        <div class="sons"><h1>无题</h1></div>
+        <div class="sons"><p class="source"><a>佚名</a></p></div>
+        <a class="next" href="?page=2">下一页</a>
+        <a href="https://other.com/">elsewhere</a>
+        </body></html>
+    "#;
+
+    const TOML: &str = r#"
+        [[rule]]
+        urls = ['^https://www\.gushiwen\.cn/gushi/']
+        follow = ["a.next", "p.source a"]
+        item = "div.sons"
+
+        [rule.fields.title]
+        selector = "h1"
+        trim = true
+
+        [rule.fields.author]
+        selector = "p.source a"
+
+        [rule.fields.dynasty]
+        selector = "p.source a"
+        regex = "〔(.+)〕"
+
+        [rule.fields.text]
+        selector = "div.contson p"
+        join = "\n"
+
+        [[rule]]
+        [rule.fields.page]
+        selector = "title"
+        trim = true
+
+        [[rule]]
+        urls = ['^https://elsewhere/']
+        follow = ["a"]
+    "#;
+
+    const YAML: &str = r#"
+        rule:
+          - urls: ['^https://www\.gushiwen\.cn/gushi/']
+            follow: [a.next, p.source a]
+            item: div.sons
+            fields:
+              title: { selector: h1, trim: true }
+              author: { selector: p.source a }
+              dynasty: { selector: p.source a, regex: "〔(.+)〕" }
+              text: { selector: div.contson p, join: "\n" }
+          - fields:
+              page: { selector: title, trim: true }
+          - urls: ['^https://elsewhere/']
+            follow: [a]
+    "#;
+
+    #[test]
+    fn extract() {
+        let rules: Rules = toml::from_str(TOML).unwrap();
+        assert_eq!(rules, serde_yaml::from_str(YAML).unwrap());
+
+        let extractor = RuleExtractor::new(&rules).unwrap();
+        let extraction = Extractor::extract(
+            &extractor,
+            "https://www.gushiwen.cn/gushi/tangshi.aspx",
+            DOCUMENT,
+        )
+        .unwrap();
+        assert_eq!(
+            extraction.links,
+            vec![
+                "https://www.gushiwen.cn/gushi/tangshi.aspx?page=2",
+                "https://www.gushiwen.cn/author_1.aspx",
+            ]
+        );
+        let items: Vec<_> = extraction.items.into_iter().map(Value::Object).collect();
+        assert_eq!(
+            items,
+            vec![
+                json!({
+                    "title": "静夜思",
+                    "author": "李白",
+                    "dynasty": "唐代",
+                    "text": "床前明月光，疑是地上霜。\n举头望明月，低头思故乡。",
+                }),
+                json!({"title": "无题"}),
+                json!({"author": "佚名"}),
+                json!({"page": "唐诗三百首"}),
+            ]
+        );
+
+        // only the rules without URL patterns apply
+        let extraction =
+            Extractor::extract(&extractor, "https://www.gushiwen.cn/", DOCUMENT).unwrap();
+        assert!(extraction.links.is_empty());
+        assert_eq!(extraction.items.len(), 1);
+    }
+
+    #[test]
+    fn errors() {
+        let invalid = |rules: &str| RuleExtractor::from_toml(rules).unwrap_err();
+        assert!(matches!(
+            invalid("[[rule]]\nfollow = ['a[']"),
+            RulesError::Selector(_)
+        ));
+        assert!(matches!(
+            invalid("[[rule]]\nurls = ['(']"),
+            RulesError::Regex(_)
+        ));
+        assert!(matches!(
+            invalid("[[rule]]\n[rule.fields.a]\nselector = 'a'\nregex = '['"),
+            RulesError::Regex(_)
+        ));
+        assert!(matches!(
+            invalid("[[rule]]\nfollow = 'a'"),
+            RulesError::Toml(_)
+        ));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rules.json");
+        std::fs::write(&path, "{}").unwrap();
+        assert!(matches!(
+            RuleExtractor::from_file(&path),
+            Err(RulesError::Format(_))
+        ));
+        let path = dir.path().join("rules.yml");
+        std::fs::write(&path, YAML).unwrap();
+        assert!(RuleExtractor::from_file(&path).is_ok());
+    }
+}
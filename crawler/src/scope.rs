@@ -0,0 +1,203 @@
+use std::{fmt, sync::Arc};
+
+use regex::RegexSet;
+
+use crate::uri::{host, registered_domain, Url};
+
+type Predicate = dyn Fn(&str, &str) -> bool + Send + Sync;
+
+/// Which discovered links may enter the frontier, checked against the entrance of the crawl.
+///
+/// Scopes compose with `and`, `or` and `not`:
+///
+/// ```
+/// use crawler::Scope;
+///
+/// let scope = Scope::http()
+///     .and(Scope::SameDomain)
+///     .and(Scope::deny([r"\.(jpg|png|zip)$"]).unwrap());
+/// assert!(scope.allows("https://so.gushiwen.cn/shiwen/", "https://www.gushiwen.cn/"));
+/// assert!(!scope.allows("https://www.gushiwen.cn/a.jpg", "https://www.gushiwen.cn/"));
+/// assert!(!scope.allows("mailto:someone@gushiwen.cn", "https://www.gushiwen.cn/"));
+/// ```
+#[derive(Clone, Default)]
+pub enum Scope {
+    /// Every link.
+    #[default]
+    Any,
+    /// Same host as the entrance, ports are ignored.
+    SameHost,
+    /// Same registered domain as the entrance, so `so.gushiwen.cn` is in scope of
+    /// `www.gushiwen.cn`.
+    SameDomain,
+    /// The path starts with the prefix.
+    PathPrefix(String),
+    /// One of the schemes, compared case-insensitively.
+    Schemes(Vec<String>),
+    /// The whole URL matches one of the patterns.
+    Matches(RegexSet),
+    /// `fn(url, entrance)`
+    Custom(Arc<Predicate>),
+    All(Vec<Scope>),
+    AnyOf(Vec<Scope>),
+    Not(Box<Scope>),
+}
+
+impl fmt::Debug for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Any => write!(f, "Any"),
+            Scope::SameHost => write!(f, "SameHost"),
+            Scope::SameDomain => write!(f, "SameDomain"),
+            Scope::PathPrefix(prefix) => f.debug_tuple("PathPrefix").field(prefix).finish(),
+            Scope::Schemes(schemes) => f.debug_tuple("Schemes").field(schemes).finish(),
+            Scope::Matches(patterns) => f
+                .debug_tuple("Matches")
+                .field(&patterns.patterns())
+                .finish(),
+            Scope::Custom(_) => write!(f, "Custom"),
+            Scope::All(scopes) => f.debug_tuple("All").field(scopes).finish(),
+            Scope::AnyOf(scopes) => f.debug_tuple("AnyOf").field(scopes).finish(),
+            Scope::Not(scope) => f.debug_tuple("Not").field(scope).finish(),
+        }
+    }
+}
+
+impl Scope {
+    /// `http` and `https` links only, which excludes `mailto:`, `javascript:` and the like.
+    pub fn http() -> Self {
+        Scope::Schemes(vec!["http".to_string(), "https".to_string()])
+    }
+
+    pub fn path_prefix<P: Into<String>>(prefix: P) -> Self {
+        Scope::PathPrefix(prefix.into())
+    }
+
+    /// Links matching any of the patterns.
+    pub fn allow<I, S>(patterns: I) -> Result<Self, regex::Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        RegexSet::new(patterns).map(Scope::Matches)
+    }
+
+    /// Links matching none of the patterns.
+    pub fn deny<I, S>(patterns: I) -> Result<Self, regex::Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self::allow(patterns).map(Scope::not)
+    }
+
+    pub fn custom<F>(f: F) -> Self
+    where
+        F: Fn(&str, &str) -> bool + Send + Sync + 'static,
+    {
+        Scope::Custom(Arc::new(f))
+    }
+
+    pub fn and(self, other: Scope) -> Self {
+        match self {
+            Scope::Any => other,
+            Scope::All(mut scopes) => {
+                scopes.push(other);
+                Scope::All(scopes)
+            }
+            scope => Scope::All(vec![scope, other]),
+        }
+    }
+
+    pub fn or(self, other: Scope) -> Self {
+        match self {
+            Scope::AnyOf(mut scopes) => {
+                scopes.push(other);
+                Scope::AnyOf(scopes)
+            }
+            scope => Scope::AnyOf(vec![scope, other]),
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        Scope::Not(Box::new(self))
+    }
+
+    /// Whether `url`, found while crawling from `entrance`, is in scope. Links that are not
+    /// absolute URLs are only in scope of `Any`, `Matches` and `Custom`.
+    pub fn allows(&self, url: &str, entrance: &str) -> bool {
+        match self {
+            Scope::Any => true,
+            Scope::SameHost => match (host(url), host(entrance)) {
+                (Some(a), Some(b)) => a == b,
+                _ => false,
+            },
+            Scope::SameDomain => match (host(url), host(entrance)) {
+                (Some(a), Some(b)) => registered_domain(&a) == registered_domain(&b),
+                _ => false,
+            },
+            Scope::PathPrefix(prefix) => match Url::parse(url) {
+                Ok(uri) => uri.path().starts_with(prefix.as_str()),
+                Err(_) => false,
+            },
+            Scope::Schemes(schemes) => match Url::parse(url) {
+                Ok(uri) => schemes.iter().any(|s| s.eq_ignore_ascii_case(uri.scheme())),
+                Err(_) => false,
+            },
+            Scope::Matches(patterns) => patterns.is_match(url),
+            Scope::Custom(f) => f(url, entrance),
+            Scope::All(scopes) => scopes.iter().all(|scope| scope.allows(url, entrance)),
+            Scope::AnyOf(scopes) => scopes.iter().any(|scope| scope.allows(url, entrance)),
+            Scope::Not(scope) => !scope.allows(url, entrance),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ENTRANCE: &str = "https://www.gushiwen.cn/default.aspx";
+
+    #[test]
+    fn scopes() {
+        assert!(Scope::SameHost.allows("HTTPS://WWW.gushiwen.cn:443/a", ENTRANCE));
+        assert!(!Scope::SameHost.allows("https://so.gushiwen.cn/a", ENTRANCE));
+        assert!(Scope::SameDomain.allows("https://so.gushiwen.cn/a", ENTRANCE));
+        assert!(!Scope::SameDomain.allows("https://gushiwen.com/a", ENTRANCE));
+        assert!(!Scope::SameDomain.allows("https://b.herokuapp.com/", "https://a.herokuapp.com/"));
+        assert!(!Scope::SameDomain.allows("javascript:void(0)", ENTRANCE));
+
+        let prefix = Scope::path_prefix("/shiwen");
+        assert!(prefix.allows("https://www.gushiwen.cn/shiwenv_1.aspx", ENTRANCE));
+        assert!(!prefix.allows("https://www.gushiwen.cn/mingjus/", ENTRANCE));
+
+        assert!(Scope::http().allows("HTTP://www.gushiwen.cn/", ENTRANCE));
+        assert!(!Scope::http().allows("mailto:a@gushiwen.cn", ENTRANCE));
+        assert!(!Scope::http().allows("page0", ENTRANCE));
+    }
+
+    #[test]
+    fn compose() {
+        let scope = Scope::Any
+            .and(Scope::http())
+            .and(Scope::SameHost)
+            .and(Scope::deny([r"\.jpg$", r"/login"]).unwrap())
+            .and(Scope::path_prefix("/shiwen").or(Scope::allow([r"/gushi/\w+\.aspx$"]).unwrap()));
+        assert!(matches!(&scope, Scope::All(scopes) if scopes.len() == 4));
+        assert!(scope.allows("https://www.gushiwen.cn/shiwenv_1.aspx", ENTRANCE));
+        assert!(scope.allows("https://www.gushiwen.cn/gushi/tangshi.aspx", ENTRANCE));
+        assert!(!scope.allows("https://www.gushiwen.cn/shiwen/a.jpg", ENTRANCE));
+        assert!(!scope.allows("https://www.gushiwen.cn/user/login.aspx", ENTRANCE));
+        assert!(!scope.allows("https://so.gushiwen.cn/shiwenv_1.aspx", ENTRANCE));
+
+        let custom = Scope::custom(|url, _| url.len() < 30);
+        assert!(custom.allows("https://a.cn/", ENTRANCE));
+        assert!(!custom.clone().not().allows("https://a.cn/", ENTRANCE));
+        assert_eq!(
+            format!("{:?}", Scope::SameHost.and(Scope::deny(["x"]).unwrap())),
+            r#"All([SameHost, Not(Matches(["x"]))])"#
+        );
+    }
+}
@@ -0,0 +1,438 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{checkpoint::save_file, Result};
+
+/// The URLs a crawl has already queued, so that each is crawled once.
+pub trait SeenSet: Send {
+    /// Returns whether `url` was not seen before.
+    fn insert(&mut self, url: &str) -> bool;
+
+    fn contains(&self, url: &str) -> bool;
+
+    /// Saves the set into the checkpoint directory `dir`, for `SeenStore::load`.
+    fn save(&mut self, dir: &Path) -> Result<()>;
+}
+
+pub(crate) const SEEN: &str = "seen";
+
+impl SeenSet for HashSet<String> {
+    fn insert(&mut self, url: &str) -> bool {
+        HashSet::insert(self, url.to_string())
+    }
+
+    fn contains(&self, url: &str) -> bool {
+        HashSet::contains(self, url)
+    }
+
+    /// One URL per line.
+    fn save(&mut self, dir: &Path) -> Result<()> {
+        save_file(dir, SEEN, |file| {
+            for url in self.iter() {
+                writeln!(file, "{}", url)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Where a crawl keeps its `SeenSet`.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum SeenStore {
+    /// Every URL in a `HashSet`, exact but unbounded.
+    #[default]
+    Memory,
+    /// A `BloomFilter`, a few bits per URL at the cost of skipping some URLs that were never
+    /// seen.
+    Bloom { capacity: usize, fp_rate: f64 },
+    /// A `DiskSeenSet` in the directory, exact and bounded but slower. Only a resumed crawl
+    /// keeps what is in it, a new one starts by removing the directory.
+    Disk(PathBuf),
+}
+
+impl SeenStore {
+    /// An empty set for a new crawl.
+    pub fn open(&self) -> Result<Box<dyn SeenSet>> {
+        Ok(match self {
+            SeenStore::Memory => Box::new(HashSet::new()),
+            SeenStore::Bloom { capacity, fp_rate } => {
+                Box::new(BloomFilter::new(*capacity, *fp_rate))
+            }
+            SeenStore::Disk(path) => Box::new(DiskSeenSet::create(path)?),
+        })
+    }
+
+    /// Like `open`, for a crawl that saves checkpoints: a `DiskSeenSet` then keeps new URLs
+    /// until the next one.
+    pub(crate) fn open_until_saved(&self) -> Result<Box<dyn SeenSet>> {
+        match self {
+            SeenStore::Disk(path) => Ok(Box::new(DiskSeenSet::create(path)?.until_saved())),
+            _ => self.open(),
+        }
+    }
+
+    /// The set saved into the checkpoint directory `dir`.
+    pub fn load(&self, dir: &Path) -> Result<Box<dyn SeenSet>> {
+        let load = || -> std::io::Result<Box<dyn SeenSet>> {
+            let mut file = match File::open(dir.join(SEEN)) {
+                Ok(file) => BufReader::new(file),
+                // killed between saving the first frontier and the set, the frontier puts
+                // back what it queued
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    return self.open().map_err(std::io::Error::other)
+                }
+                Err(e) => return Err(e),
+            };
+            Ok(match self {
+                SeenStore::Bloom { .. } => Box::new(BloomFilter::read(&mut file)?),
+                _ => Box::new(file.lines().collect::<std::io::Result<HashSet<_>>>()?),
+            })
+        };
+        match self {
+            SeenStore::Disk(path) => Ok(Box::new(DiskSeenSet::open(path)?.until_saved())),
+            _ => load().map_err(|e| anyhow::Error::from(e).into()),
+        }
+    }
+}
+
+/// A scalable Bloom filter: when a filter is full a twice as large one with half the false
+/// positive rate is added, so the rate of the whole stays below `fp_rate` however many URLs
+/// are inserted.
+pub struct BloomFilter {
+    fp_rate: f64,
+    filters: Vec<Filter>,
+}
+
+struct Filter {
+    bits: Vec<u64>,
+    hashes: u32,
+    capacity: usize,
+    len: usize,
+}
+
+impl Filter {
+    fn new(capacity: usize, fp_rate: f64) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-(capacity as f64) * fp_rate.ln() / (ln2 * ln2))
+            .ceil()
+            .max(64.0);
+        let hashes = ((bits / capacity as f64) * ln2).round().max(1.0) as u32;
+        Self {
+            bits: vec![0; (bits as usize).div_ceil(64)],
+            hashes,
+            capacity,
+            len: 0,
+        }
+    }
+
+    /// Double hashing, the `i`-th bit of a URL is `h1 + i * h2`.
+    fn indexes(&self, (h1, h2): (u64, u64)) -> impl Iterator<Item = usize> {
+        let bits = self.bits.len() as u64 * 64;
+        (0..self.hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bits) as usize)
+    }
+
+    fn contains(&self, hash: (u64, u64)) -> bool {
+        self.indexes(hash)
+            .all(|i| self.bits[i / 64] & (1 << (i % 64)) != 0)
+    }
+
+    fn insert(&mut self, hash: (u64, u64)) {
+        let indexes: Vec<_> = self.indexes(hash).collect();
+        for i in indexes {
+            self.bits[i / 64] |= 1 << (i % 64);
+        }
+        self.len += 1;
+    }
+}
+
+/// FNV-1a mixed with the splitmix64 finalizer. Unlike `DefaultHasher` it is the same in every
+/// build, so a filter can be saved and loaded again.
+fn hash(url: &str) -> (u64, u64) {
+    fn mix(mut z: u64) -> u64 {
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+    let fnv = url.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    let h1 = mix(fnv);
+    (h1, mix(h1 ^ 0x9e37_79b9_7f4a_7c15) | 1)
+}
+
+impl BloomFilter {
+    /// `capacity` is the size of the first filter, `fp_rate` the false positive rate of the
+    /// whole.
+    pub fn new(capacity: usize, fp_rate: f64) -> Self {
+        let fp_rate = fp_rate.clamp(f64::MIN_POSITIVE, 0.5);
+        Self {
+            fp_rate,
+            filters: vec![Filter::new(capacity.max(1), fp_rate / 2.0)],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.filters.iter().map(|filter| filter.len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Little-endian: the false positive rate, the number of filters, then for each filter its
+    /// number of hashes, capacity, length, number of words and the words.
+    fn write<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        w.write_all(&self.fp_rate.to_le_bytes())?;
+        w.write_all(&(self.filters.len() as u64).to_le_bytes())?;
+        for filter in &self.filters {
+            for n in [
+                filter.hashes as u64,
+                filter.capacity as u64,
+                filter.len as u64,
+                filter.bits.len() as u64,
+            ] {
+                w.write_all(&n.to_le_bytes())?;
+            }
+            for word in &filter.bits {
+                w.write_all(&word.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read<R: Read>(r: &mut R) -> std::io::Result<Self> {
+        let mut read_u64 = || -> std::io::Result<u64> {
+            let mut buf = [0; 8];
+            r.read_exact(&mut buf)?;
+            Ok(u64::from_le_bytes(buf))
+        };
+        let fp_rate = f64::from_bits(read_u64()?);
+        let filters = (0..read_u64()?)
+            .map(|_| {
+                let hashes = read_u64()? as u32;
+                let capacity = read_u64()? as usize;
+                let len = read_u64()? as usize;
+                let bits = (0..read_u64()?)
+                    .map(|_| read_u64())
+                    .collect::<Result<_, _>>()?;
+                Ok(Filter {
+                    bits,
+                    hashes,
+                    capacity,
+                    len,
+                })
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        if filters.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "bloom filter without filters",
+            ));
+        }
+        Ok(Self { fp_rate, filters })
+    }
+}
+
+impl SeenSet for BloomFilter {
+    fn insert(&mut self, url: &str) -> bool {
+        let hash = hash(url);
+        if self.filters.iter().any(|filter| filter.contains(hash)) {
+            return false;
+        }
+        let last = self.filters.last().unwrap();
+        if last.len >= last.capacity {
+            let capacity = last.capacity * 2;
+            let fp_rate = self.fp_rate / 2f64.powi(self.filters.len() as i32 + 1);
+            self.filters.push(Filter::new(capacity, fp_rate));
+        }
+        self.filters.last_mut().unwrap().insert(hash);
+        true
+    }
+
+    fn contains(&self, url: &str) -> bool {
+        let hash = hash(url);
+        self.filters.iter().any(|filter| filter.contains(hash))
+    }
+
+    fn save(&mut self, dir: &Path) -> Result<()> {
+        save_file(dir, SEEN, |file| self.write(file))
+    }
+}
+
+/// URLs in a sled database, only its page cache stays in memory.
+pub struct DiskSeenSet {
+    db: sled::Db,
+    /// Inserted since the last `save`, see `until_saved`.
+    staged: Option<HashSet<String>>,
+}
+
+impl DiskSeenSet {
+    /// Opens or creates the database in the directory `path`, URLs seen by an earlier crawl
+    /// stay seen.
+    ///
+    /// sled lets go of the lock of a dropped database from its background threads, so a
+    /// crawl resumed in the same process waits a moment for it.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let config = sled::Config::new()
+            .path(path.as_ref())
+            .cache_capacity(64 << 20);
+        let deadline = Instant::now() + Duration::from_secs(1);
+        loop {
+            match config.open() {
+                Ok(db) => return Ok(Self { db, staged: None }),
+                Err(sled::Error::Io(e))
+                    if Instant::now() < deadline && e.to_string().contains("acquire lock") =>
+                {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Err(e) => return Err(anyhow::Error::from(e).into()),
+            }
+        }
+    }
+
+    /// Creates an empty database in the directory `path`, removing whatever was in it.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        match fs::remove_dir_all(path.as_ref()) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(anyhow::Error::from(e).into())
+            }
+            _ => {}
+        }
+        Self::open(path)
+    }
+
+    /// Keeps new URLs in memory and writes them only on `save`, so that the database never
+    /// gets ahead of the frontier checkpointed with it. Otherwise links found after the last
+    /// checkpoint would be seen, but not queued, when the crawl resumes.
+    pub fn until_saved(mut self) -> Self {
+        self.staged = Some(HashSet::new());
+        self
+    }
+}
+
+impl SeenSet for DiskSeenSet {
+    /// Errors are logged and the URL treated as new, so a broken store crawls some URLs twice
+    /// instead of none.
+    fn insert(&mut self, url: &str) -> bool {
+        if let Some(staged) = &mut self.staged {
+            return !self.db.contains_key(url).unwrap_or(false) && staged.insert(url.to_string());
+        }
+        match self.db.insert(url, &[]) {
+            Ok(previous) => previous.is_none(),
+            Err(e) => {
+                println!("[SEEN] failed to insert {}. {}", url, e);
+                true
+            }
+        }
+    }
+
+    fn contains(&self, url: &str) -> bool {
+        self.staged
+            .as_ref()
+            .is_some_and(|staged| staged.contains(url))
+            || self.db.contains_key(url).unwrap_or(false)
+    }
+
+    /// The database is its own checkpoint, the staged URLs are written at once and flushed.
+    fn save(&mut self, _dir: &Path) -> Result<()> {
+        if let Some(staged) = &mut self.staged {
+            let mut batch = sled::Batch::default();
+            for url in staged.iter() {
+                batch.insert(url.as_str(), &[]);
+            }
+            self.db.apply_batch(batch).map_err(anyhow::Error::from)?;
+            staged.clear();
+        }
+        self.db.flush().map_err(anyhow::Error::from)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bloom() {
+        let mut bloom = BloomFilter::new(1000, 0.01);
+        assert!(bloom.is_empty());
+        let inserted = (0..10_000)
+            .filter(|i| bloom.insert(&format!("https://a.com/{}", i)))
+            .count();
+        // 1000 + 2000 + 4000 + 8000 URLs
+        assert_eq!(bloom.filters.len(), 4);
+        assert!(inserted > 9_900, "{}", inserted);
+        assert_eq!(bloom.len(), inserted);
+        assert!((0..10_000).all(|i| bloom.contains(&format!("https://a.com/{}", i))));
+        assert!(!bloom.insert("https://a.com/0"));
+
+        let false_positives = (0..10_000)
+            .filter(|i| bloom.contains(&format!("https://b.com/{}", i)))
+            .count();
+        assert!(false_positives < 100, "{}", false_positives);
+    }
+
+    #[test]
+    fn disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut seen = SeenStore::Disk(dir.path().join("seen")).open().unwrap();
+        assert!(seen.insert("https://a.com/"));
+        assert!(!seen.insert("https://a.com/"));
+        assert!(seen.contains("https://a.com/"));
+        assert!(!seen.contains("https://b.com/"));
+        drop(seen);
+
+        let mut seen = DiskSeenSet::open(dir.path().join("seen"))
+            .unwrap()
+            .until_saved();
+        assert!(seen.contains("https://a.com/"));
+        assert!(!seen.insert("https://a.com/"));
+        assert!(seen.insert("https://b.com/"));
+        assert!(!seen.insert("https://b.com/"));
+        assert!(seen.contains("https://b.com/"));
+        drop(seen);
+
+        // not saved, so forgotten
+        let mut seen = DiskSeenSet::open(dir.path().join("seen"))
+            .unwrap()
+            .until_saved();
+        assert!(!seen.contains("https://b.com/"));
+        assert!(seen.insert("https://b.com/"));
+        seen.save(dir.path()).unwrap();
+        drop(seen);
+
+        let seen = DiskSeenSet::open(dir.path().join("seen")).unwrap();
+        assert!(seen.contains("https://b.com/"));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let stores = [
+            SeenStore::Memory,
+            SeenStore::Bloom {
+                capacity: 10,
+                fp_rate: 0.01,
+            },
+            SeenStore::Disk(dir.path().join("db")),
+        ];
+        for store in stores {
+            let mut seen = store.open().unwrap();
+            for i in 0..100 {
+                seen.insert(&format!("https://a.com/{}", i));
+            }
+            seen.save(dir.path()).unwrap();
+            drop(seen);
+
+            let mut seen = store.load(dir.path()).unwrap();
+            assert!(seen.contains("https://a.com/99"), "{:?}", store);
+            assert!(!seen.insert("https://a.com/0"), "{:?}", store);
+            assert!(seen.insert("https://a.com/100"), "{:?}", store);
+        }
+    }
+}
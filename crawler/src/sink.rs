@@ -0,0 +1,467 @@
+use std::{
+    collections::HashSet,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use crate::{item::field_text, Item, Page, Processor, Result};
+
+/// Where a `Sink` writes items.
+pub trait ItemWriter: Send {
+    fn write(&mut self, item: &Item) -> Result<()>;
+
+    /// Called after the items of each page.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// The values of `key` among the items written by earlier runs.
+    fn keys(&mut self, key: &str) -> Result<Vec<String>>;
+}
+
+/// A `Processor` that writes the items of every page and passes the page on.
+///
+/// ```no_run
+/// use crawler::{Pipeline, Sink};
+///
+/// let pipeline = Pipeline::new().then(Sink::jsonl("poems.jsonl")?.dedupe_by("id")?);
+/// # Ok::<(), crawler::Error>(())
+/// ```
+pub struct Sink<W> {
+    inner: Mutex<Inner<W>>,
+}
+
+struct Inner<W> {
+    writer: W,
+    key: Option<String>,
+    written: HashSet<String>,
+}
+
+impl<W: ItemWriter> Sink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                writer,
+                key: None,
+                written: HashSet::new(),
+            }),
+        }
+    }
+
+    /// Writes one item per value of the `key` field, including the items already written by
+    /// earlier runs, so a resumed crawl does not duplicate them. Items without the field are
+    /// all written.
+    pub fn dedupe_by<K: Into<String>>(self, key: K) -> Result<Self> {
+        let key = key.into();
+        {
+            let mut inner = self.inner.lock().unwrap();
+            let written = inner.writer.keys(&key)?;
+            inner.written.extend(written);
+            inner.key = Some(key);
+        }
+        Ok(self)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner.into_inner().unwrap().writer
+    }
+}
+
+impl<W: ItemWriter> Processor for Sink<W> {
+    fn process(&self, page: Page) -> Result<Option<Page>> {
+        let mut inner = self.inner.lock().unwrap();
+        let inner = &mut *inner;
+        for item in &page.items {
+            let key = inner.key.as_deref().and_then(|key| field_text(item, key));
+            if matches!(&key, Some(key) if inner.written.contains(key)) {
+                continue;
+            }
+            inner.writer.write(item)?;
+            inner.written.extend(key);
+        }
+        inner.writer.flush()?;
+        Ok(Some(page))
+    }
+}
+
+impl Sink<JsonLines<BufWriter<File>>> {
+    /// Appends to `path`.
+    pub fn jsonl<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(JsonLines::create(path)?))
+    }
+}
+
+impl Sink<Csv<File>> {
+    /// Appends to `path`, writing the header if it is a new file.
+    pub fn csv<P: AsRef<Path>>(path: P, columns: &[&str]) -> Result<Self> {
+        Ok(Self::new(Csv::create(path, columns)?))
+    }
+}
+
+impl Sink<Sqlite> {
+    /// Inserts into `table` of the database at `path`, creating it if needed.
+    pub fn sqlite<P: AsRef<Path>>(path: P, table: &str, columns: &[&str]) -> Result<Self> {
+        Ok(Self::new(Sqlite::open(path, table, columns)?))
+    }
+}
+
+/// One JSON object per line.
+pub struct JsonLines<W> {
+    writer: W,
+    /// What `keys` reads, if anything was written before.
+    written: Option<File>,
+}
+
+impl<W: Write> JsonLines<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            written: None,
+        }
+    }
+}
+
+impl JsonLines<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(anyhow::Error::from)?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+            written: Some(File::open(&path).map_err(anyhow::Error::from)?),
+        })
+    }
+}
+
+impl<W: Write + Send> ItemWriter for JsonLines<W> {
+    fn write(&mut self, item: &Item) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, item).map_err(anyhow::Error::from)?;
+        self.writer.write_all(b"\n").map_err(anyhow::Error::from)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush().map_err(anyhow::Error::from)?)
+    }
+
+    fn keys(&mut self, key: &str) -> Result<Vec<String>> {
+        let file = match self.written.take() {
+            Some(file) => file,
+            None => return Ok(vec![]),
+        };
+        let mut keys = vec![];
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(anyhow::Error::from)?;
+            // a line cut short when the last run was killed
+            if let Ok(item) = serde_json::from_str::<Item>(&line) {
+                keys.extend(field_text(&item, key));
+            }
+        }
+        Ok(keys)
+    }
+}
+
+/// One row per item with the given columns, fields that are not strings are written as JSON.
+pub struct Csv<W: Write> {
+    writer: csv::Writer<W>,
+    columns: Vec<String>,
+    /// What `keys` reads, if anything was written before.
+    written: Option<File>,
+}
+
+impl<W: Write> Csv<W> {
+    /// Writes the header first.
+    pub fn new(writer: W, columns: &[&str]) -> Result<Self> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(columns).map_err(anyhow::Error::from)?;
+        Ok(Self {
+            writer,
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            written: None,
+        })
+    }
+}
+
+impl Csv<File> {
+    pub fn create<P: AsRef<Path>>(path: P, columns: &[&str]) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(anyhow::Error::from)?;
+        let is_new = file.metadata().map_err(anyhow::Error::from)?.len() == 0;
+        if is_new {
+            return Self::new(file, columns);
+        }
+        Ok(Self {
+            writer: csv::Writer::from_writer(file),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            written: Some(File::open(&path).map_err(anyhow::Error::from)?),
+        })
+    }
+}
+
+impl<W: Write + Send> ItemWriter for Csv<W> {
+    fn write(&mut self, item: &Item) -> Result<()> {
+        let row = self
+            .columns
+            .iter()
+            .map(|column| field_text(item, column).unwrap_or_default());
+        self.writer.write_record(row).map_err(anyhow::Error::from)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush().map_err(anyhow::Error::from)?)
+    }
+
+    fn keys(&mut self, key: &str) -> Result<Vec<String>> {
+        let file = match self.written.take() {
+            Some(file) => file,
+            None => return Ok(vec![]),
+        };
+        let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(file);
+        let headers = reader.headers().map_err(anyhow::Error::from)?;
+        let column = match headers.iter().position(|header| header == key) {
+            Some(column) => column,
+            None => return Ok(vec![]),
+        };
+        Ok(reader
+            .records()
+            .filter_map(|record| record.ok()?.get(column).map(str::to_string))
+            .filter(|key| !key.is_empty())
+            .collect())
+    }
+}
+
+/// One row per item in a table of `TEXT` columns, fields that are not strings are written as
+/// JSON.
+pub struct Sqlite {
+    connection: rusqlite::Connection,
+    table: String,
+    columns: Vec<String>,
+}
+
+/// Quotes a table or column name.
+fn identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+impl Sqlite {
+    pub fn open<P: AsRef<Path>>(path: P, table: &str, columns: &[&str]) -> Result<Self> {
+        Self::new(
+            rusqlite::Connection::open(path).map_err(anyhow::Error::from)?,
+            table,
+            columns,
+        )
+    }
+
+    /// Creates `table` if it does not exist.
+    pub fn new(connection: rusqlite::Connection, table: &str, columns: &[&str]) -> Result<Self> {
+        let definitions: Vec<_> = columns
+            .iter()
+            .map(|column| format!("{} TEXT", identifier(column)))
+            .collect();
+        connection
+            .execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {} ({})",
+                    identifier(table),
+                    definitions.join(", ")
+                ),
+                [],
+            )
+            .map_err(anyhow::Error::from)?;
+        Ok(Self {
+            connection,
+            table: table.to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+        })
+    }
+
+    pub fn connection(&self) -> &rusqlite::Connection {
+        &self.connection
+    }
+}
+
+impl ItemWriter for Sqlite {
+    fn write(&mut self, item: &Item) -> Result<()> {
+        let columns: Vec<_> = self.columns.iter().map(|c| identifier(c)).collect();
+        let placeholders = vec!["?"; columns.len()];
+        let values: Vec<_> = self
+            .columns
+            .iter()
+            .map(|column| field_text(item, column))
+            .collect();
+        self.connection
+            .prepare_cached(&format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                identifier(&self.table),
+                columns.join(", "),
+                placeholders.join(", ")
+            ))
+            .and_then(|mut insert| insert.execute(rusqlite::params_from_iter(values)))
+            .map_err(anyhow::Error::from)?;
+        Ok(())
+    }
+
+    fn keys(&mut self, key: &str) -> Result<Vec<String>> {
+        if !self.columns.iter().any(|column| column == key) {
+            return Ok(vec![]);
+        }
+        let mut select = self
+            .connection
+            .prepare(&format!(
+                "SELECT {0} FROM {1} WHERE {0} IS NOT NULL",
+                identifier(key),
+                identifier(&self.table)
+            ))
+            .map_err(anyhow::Error::from)?;
+        let keys = select
+            .query_map([], |row| row.get(0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>())
+            .map_err(anyhow::Error::from)?;
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Request, Response};
+
+    fn page(items: &[serde_json::Value]) -> Page {
+        let items = items
+            .iter()
+            .map(|item| item.as_object().unwrap().clone())
+            .collect();
+        Page::new(
+            Request::new("https://a.com/", 0),
+            Response::from(""),
+            items,
+            SystemTime::now(),
+            Duration::ZERO,
+        )
+    }
+
+    fn poems() -> Page {
+        page(&[
+            json!({"id": "1", "title": "静夜思", "lines": 4}),
+            json!({"id": "2", "title": "春晓, 孟浩然"}),
+            json!({"id": "1", "title": "静夜思"}),
+            json!({"title": "无题"}),
+        ])
+    }
+
+    #[test]
+    fn jsonl() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("poems.jsonl");
+        let sink = Sink::jsonl(&path).unwrap().dedupe_by("id").unwrap();
+        sink.process(poems()).unwrap().unwrap();
+        drop(sink);
+        // a resumed crawl appends, skipping what was written
+        let sink = Sink::jsonl(&path).unwrap().dedupe_by("id").unwrap();
+        sink.process(page(&[json!({"id": "2"}), json!({"id": "3"})]))
+            .unwrap();
+        drop(sink);
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                json!({"id": "1", "title": "静夜思", "lines": 4}),
+                json!({"id": "2", "title": "春晓, 孟浩然"}),
+                json!({"title": "无题"}),
+                json!({"id": "3"}),
+            ]
+        );
+
+        let sink = Sink::new(JsonLines::new(vec![]));
+        sink.process(poems()).unwrap();
+        assert_eq!(
+            sink.into_inner()
+                .writer
+                .iter()
+                .filter(|&&b| b == b'\n')
+                .count(),
+            4
+        );
+    }
+
+    #[test]
+    fn csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("poems.csv");
+        let columns = ["id", "title", "lines"];
+        let sink = Sink::csv(&path, &columns).unwrap().dedupe_by("id").unwrap();
+        sink.process(poems()).unwrap();
+        drop(sink);
+        let sink = Sink::csv(&path, &columns).unwrap().dedupe_by("id").unwrap();
+        sink.process(page(&[json!({"id": "2"}), json!({"id": "3"})]))
+            .unwrap();
+        drop(sink);
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "id,title,lines\n1,静夜思,4\n2,\"春晓, 孟浩然\",\n,无题,\n3,,\n"
+        );
+    }
+
+    #[test]
+    fn sqlite() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("poems.db");
+        let columns = ["id", "title", "lines"];
+        let sink = Sink::sqlite(&path, "poems", &columns)
+            .unwrap()
+            .dedupe_by("id")
+            .unwrap();
+        sink.process(poems()).unwrap();
+        drop(sink);
+        let sink = Sink::sqlite(&path, "poems", &columns)
+            .unwrap()
+            .dedupe_by("id")
+            .unwrap();
+        sink.process(page(&[json!({"id": "2"}), json!({"id": "3"})]))
+            .unwrap();
+
+        let sqlite = sink.into_inner();
+        let rows: Vec<(Option<String>, Option<String>, Option<String>)> = sqlite
+            .connection()
+            .prepare("SELECT id, title, lines FROM poems ORDER BY rowid")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect();
+        let row = |id: Option<&str>, title: Option<&str>, lines: Option<&str>| {
+            (
+                id.map(str::to_string),
+                title.map(str::to_string),
+                lines.map(str::to_string),
+            )
+        };
+        assert_eq!(
+            rows,
+            vec![
+                row(Some("1"), Some("静夜思"), Some("4")),
+                row(Some("2"), Some("春晓, 孟浩然"), None),
+                row(None, Some("无题"), None),
+                row(Some("3"), None, None),
+            ]
+        );
+    }
+}
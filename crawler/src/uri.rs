@@ -0,0 +1,108 @@
+//! Resolving relative links and finding registrable domains, on top of the `url` crate that
+//! reqwest parses URLs with.
+
+pub(crate) use reqwest::Url;
+
+/// The host of an absolute URL, lowercase. `None` for URLs without one such as `mailto:`.
+pub(crate) fn host(url: &str) -> Option<String> {
+    Url::parse(url)
+        .ok()?
+        .host_str()
+        .filter(|host| !host.is_empty())
+        .map(str::to_string)
+}
+
+/// The registrable part of `host` according to the public suffix list, e.g.
+/// `www.gushiwen.cn` -> `gushiwen.cn` and `a.b.example.com.cn` -> `example.com.cn`. Private
+/// domains such as `github.io` and `herokuapp.com` count as suffixes too, since every
+/// subdomain there belongs to someone else. IP addresses and hosts that are public suffixes
+/// themselves are returned unchanged.
+pub(crate) fn registered_domain(host: &str) -> &str {
+    let host = host.trim_end_matches('.');
+    if host.starts_with('[') || host.parse::<std::net::Ipv4Addr>().is_ok() {
+        return host;
+    }
+    match psl::domain_str(&host.to_ascii_lowercase()) {
+        Some(domain) => &host[host.len() - domain.len()..],
+        None => host,
+    }
+}
+
+/// Resolves `reference` against `base` as in RFC 3986 section 5.2.2, `None` if `base` is not
+/// absolute and `reference` is not either.
+pub(crate) fn resolve(base: &str, reference: &str) -> Option<String> {
+    let reference = reference.trim();
+    Url::parse(reference)
+        .or_else(|_| Url::parse(base)?.join(reference))
+        .ok()
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hosts() {
+        assert_eq!(
+            host("HTTPS://user:pw@www.Gushiwen.cn:8443/a?id=1#top").as_deref(),
+            Some("www.gushiwen.cn")
+        );
+        assert_eq!(host("http://[::1]:8080").as_deref(), Some("[::1]"));
+        assert_eq!(host("mailto:someone@example.com"), None);
+        assert_eq!(host("page0"), None);
+    }
+
+    #[test]
+    fn resolve() {
+        let base = "http://a/b/c/d;p?q";
+        // RFC 3986 section 5.4
+        let examples = [
+            ("g:h", "g:h"),
+            ("g", "http://a/b/c/g"),
+            ("./g", "http://a/b/c/g"),
+            ("g/", "http://a/b/c/g/"),
+            ("/g", "http://a/g"),
+            ("//g", "http://g/"),
+            ("?y", "http://a/b/c/d;p?y"),
+            ("g?y", "http://a/b/c/g?y"),
+            ("#s", "http://a/b/c/d;p?q#s"),
+            ("g#s", "http://a/b/c/g#s"),
+            ("", "http://a/b/c/d;p?q"),
+            (".", "http://a/b/c/"),
+            ("..", "http://a/b/"),
+            ("../g", "http://a/b/g"),
+            ("../../../g", "http://a/g"),
+            ("/./g", "http://a/g"),
+            ("g;x=1/../y", "http://a/b/c/y"),
+        ];
+        for (reference, resolved) in examples {
+            assert_eq!(
+                super::resolve(base, reference).as_deref(),
+                Some(resolved),
+                "{}",
+                reference
+            );
+        }
+        assert_eq!(
+            super::resolve("https://www.gushiwen.cn", " shiwen/a.aspx ").as_deref(),
+            Some("https://www.gushiwen.cn/shiwen/a.aspx")
+        );
+        assert_eq!(super::resolve("page0", "page1"), None);
+    }
+
+    #[test]
+    fn registered() {
+        assert_eq!(registered_domain("www.gushiwen.cn"), "gushiwen.cn");
+        assert_eq!(registered_domain("gushiwen.cn"), "gushiwen.cn");
+        assert_eq!(registered_domain("a.b.example.com.cn"), "example.com.cn");
+        assert_eq!(registered_domain("news.bbc.co.uk"), "bbc.co.uk");
+        assert_eq!(registered_domain("WWW.Example.CO.ZA"), "Example.CO.ZA");
+        assert_eq!(registered_domain("shop.tienda.com.mx"), "tienda.com.mx");
+        assert_eq!(registered_domain("a.herokuapp.com"), "a.herokuapp.com");
+        assert_eq!(registered_domain("x.a.blogspot.com"), "a.blogspot.com");
+        assert_eq!(registered_domain("herokuapp.com"), "herokuapp.com");
+        assert_eq!(registered_domain("localhost"), "localhost");
+        assert_eq!(registered_domain("127.0.0.1"), "127.0.0.1");
+    }
+}
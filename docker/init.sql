@@ -2,18 +2,34 @@ DROP DATABASE IF EXISTS `testing`;
 
 CREATE DATABASE `testing` CHARSET utf8mb4 COLLATE utf8mb4_general_ci;
 
+-- 测试中代替从库
+DROP DATABASE IF EXISTS `testing_replica`;
+
+CREATE DATABASE `testing_replica` CHARSET utf8mb4 COLLATE utf8mb4_general_ci;
+
 CREATE TABLE IF NOT EXISTS `testing`.`algorithm` (
     `id` BIGINT NOT NULL,
     `name` VARCHAR(255) CHARSET utf8mb4 COLLATE utf8mb4_general_ci NOT NULL,
     `display_name` VARCHAR(255) CHARSET utf8mb4 COLLATE utf8mb4_general_ci NOT NULL,
     `location` VARCHAR(255) CHARSET utf8mb4 COLLATE utf8mb4_general_ci NOT NULL,
     `image` BIGINT NOT NULL,
+    `checksum` CHAR(64) CHARSET ascii DEFAULT NULL,
+    `size` BIGINT DEFAULT NULL,
     `created_at` DATETIME DEFAULT CURRENT_TIMESTAMP,
     `updated_at` DATETIME DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
     PRIMARY KEY (`id`),
     UNIQUE KEY unique_name (`name`)  
 ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE utf8mb4_general_ci;
 
+CREATE TABLE IF NOT EXISTS `testing`.`algorithm_label` (
+    `algorithm_id` BIGINT NOT NULL,
+    `key` VARCHAR(317) CHARSET utf8mb4 COLLATE utf8mb4_bin NOT NULL,
+    `value` VARCHAR(63) CHARSET utf8mb4 COLLATE utf8mb4_bin NOT NULL,
+    `created_at` DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (`algorithm_id`, `key`),
+    KEY index_key_value (`key`, `value`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE utf8mb4_general_ci;
+
 
 CREATE TABLE IF NOT EXISTS `testing`.`image` (
     `id` BIGINT NOT NULL,
@@ -27,6 +43,10 @@ CREATE TABLE IF NOT EXISTS `testing`.`trainset` (
     `id` BIGINT NOT NULL,
     `name` VARCHAR(255) CHARSET utf8mb4 COLLATE utf8mb4_general_ci NOT NULL,
     `location` VARCHAR(255) CHARSET utf8mb4 COLLATE utf8mb4_general_ci NOT NULL,
+    `manifest` MEDIUMTEXT CHARSET utf8mb4 COLLATE utf8mb4_general_ci DEFAULT NULL,
+    `verification_status` VARCHAR(32) CHARSET ascii NOT NULL DEFAULT 'unverified',
+    `verification_problems` MEDIUMTEXT CHARSET utf8mb4 COLLATE utf8mb4_general_ci DEFAULT NULL,
+    `verified_at` DATETIME DEFAULT NULL,
     `created_at` DATETIME DEFAULT CURRENT_TIMESTAMP,
     PRIMARY KEY (`id`)
 ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE utf8mb4_general_ci;
@@ -35,6 +55,26 @@ CREATE TABLE IF NOT EXISTS `testing`.`testset` (
     `id` BIGINT NOT NULL,
     `name` VARCHAR(255) CHARSET utf8mb4 COLLATE utf8mb4_general_ci NOT NULL,
     `location` VARCHAR(255) CHARSET utf8mb4 COLLATE utf8mb4_general_ci NOT NULL,
+    `manifest` MEDIUMTEXT CHARSET utf8mb4 COLLATE utf8mb4_general_ci DEFAULT NULL,
+    `verification_status` VARCHAR(32) CHARSET ascii NOT NULL DEFAULT 'unverified',
+    `verification_problems` MEDIUMTEXT CHARSET utf8mb4 COLLATE utf8mb4_general_ci DEFAULT NULL,
+    `verified_at` DATETIME DEFAULT NULL,
     `created_at` DATETIME DEFAULT CURRENT_TIMESTAMP,
     PRIMARY KEY (`id`)
 ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE utf8mb4_general_ci;
+
+CREATE TABLE IF NOT EXISTS `testing`.`outbox` (
+    `id` BIGINT NOT NULL AUTO_INCREMENT,
+    `aggregate_type` VARCHAR(64) CHARSET ascii NOT NULL,
+    `aggregate_id` BIGINT NOT NULL,
+    `event_type` VARCHAR(64) CHARSET ascii NOT NULL,
+    `payload` MEDIUMTEXT CHARSET utf8mb4 COLLATE utf8mb4_general_ci NOT NULL,
+    `attempts` INT NOT NULL DEFAULT 0,
+    `last_error` TEXT CHARSET utf8mb4 COLLATE utf8mb4_general_ci DEFAULT NULL,
+    `next_attempt_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `published_at` DATETIME DEFAULT NULL,
+    `created_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (`id`),
+    KEY index_published_at (`published_at`, `id`),
+    KEY index_aggregate (`aggregate_type`, `aggregate_id`, `id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE utf8mb4_general_ci;